@@ -0,0 +1,5 @@
+-- Lets clients retry transaction creation without paying twice
+ALTER TABLE IF EXISTS transactions ADD COLUMN IF NOT EXISTS idempotency_key VARCHAR(128) NULL;
+ALTER TABLE IF EXISTS transactions ADD COLUMN IF NOT EXISTS request_hash CHAR(64) NULL;
+
+CREATE UNIQUE INDEX idx_transactions_idempotency_key ON transactions ("from", idempotency_key) WHERE idempotency_key IS NOT NULL;
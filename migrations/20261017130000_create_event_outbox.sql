@@ -0,0 +1,10 @@
+-- Websocket events are written here in the same transaction as the change they describe,
+-- and only delivered to clients once that transaction has committed.
+CREATE TABLE event_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    dispatched_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX idx_event_outbox_pending ON event_outbox (id) WHERE dispatched_at IS NULL;
@@ -0,0 +1,8 @@
+-- Indexes backing the search endpoints, metadata is matched with ILIKE so it needs trigrams.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_transactions_metadata_trgm ON transactions USING GIN (metadata gin_trgm_ops);
+CREATE INDEX idx_transactions_from ON transactions ("from");
+CREATE INDEX idx_transactions_to ON transactions ("to");
+CREATE INDEX idx_transactions_name ON transactions (name) WHERE name IS NOT NULL;
+CREATE INDEX idx_transactions_sent_name ON transactions (sent_name) WHERE sent_name IS NOT NULL;
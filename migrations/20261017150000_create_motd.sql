@@ -0,0 +1,6 @@
+-- Every MOTD ever set, the newest row is the current one.
+CREATE TABLE motd (
+    id SERIAL PRIMARY KEY,
+    motd VARCHAR(1024) NOT NULL,
+    set_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,10 @@
+-- Names put up for sale by their owner, at most one listing per name.
+CREATE TABLE name_listings (
+    id SERIAL PRIMARY KEY,
+    name_id INTEGER NOT NULL REFERENCES names (id) ON DELETE CASCADE,
+    seller CHAR(10) NOT NULL,
+    price NUMERIC(16, 2) NOT NULL CHECK (price > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_name_listings_name_id ON name_listings (name_id);
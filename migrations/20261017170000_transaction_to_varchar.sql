@@ -0,0 +1,2 @@
+-- Name A record transactions are sent to `a`, which CHAR(10) would pad with spaces.
+ALTER TABLE transactions ALTER COLUMN "to" TYPE VARCHAR(10);
@@ -0,0 +1,12 @@
+-- Names now have to be renewed, see `jobs::name_lifecycle`.
+ALTER TYPE transaction_type ADD VALUE 'name_renewal';
+ALTER TYPE transaction_type ADD VALUE 'name_expiry';
+ALTER TYPE transaction_type ADD VALUE 'name_release';
+
+-- Existing names get a full term from now rather than expiring straight away.
+ALTER TABLE names ADD COLUMN expires_at TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '30 days';
+ALTER TABLE names ALTER COLUMN expires_at DROP DEFAULT;
+-- Set once the expiry has been recorded, the name is in its grace period until it is renewed or released.
+ALTER TABLE names ADD COLUMN expired BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX idx_names_expires_at ON names (expires_at);
@@ -0,0 +1,9 @@
+-- Registration prices set by an admin for specific names, taking precedence over the configured rules.
+CREATE TABLE name_price_overrides (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    cost BIGINT NOT NULL CHECK (cost >= 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_name_price_overrides_name ON name_price_overrides (name);
@@ -0,0 +1,11 @@
+-- Names nobody may register through the public API. `pattern` may use `*` and `?` wildcards,
+-- `like_pattern` is the same pattern translated for `LIKE`.
+CREATE TABLE reserved_names (
+    id SERIAL PRIMARY KEY,
+    pattern VARCHAR(64) NOT NULL,
+    like_pattern VARCHAR(128) NOT NULL,
+    reason VARCHAR(255) NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_reserved_names_pattern ON reserved_names (pattern);
@@ -0,0 +1,11 @@
+-- Where payments to `metaname@name.kro` are forwarded. A route without a metaname is the
+-- fallback for metanames that have no route of their own; without one payments go to the owner.
+CREATE TABLE name_routes (
+    id SERIAL PRIMARY KEY,
+    name_id INTEGER NOT NULL REFERENCES names (id) ON DELETE CASCADE,
+    metaname VARCHAR(32) NULL,
+    address CHAR(10) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_name_routes_name_id_metaname ON name_routes (name_id, COALESCE(metaname, ''));
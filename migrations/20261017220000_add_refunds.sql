@@ -0,0 +1,9 @@
+ALTER TYPE transaction_type ADD VALUE 'refund';
+
+-- The transaction a refund was made for.
+ALTER TABLE transactions ADD COLUMN original_id INTEGER NULL REFERENCES transactions (id);
+CREATE INDEX idx_transactions_original_id ON transactions (original_id) WHERE original_id IS NOT NULL;
+
+-- Name owners can opt into refunding payments to metanames that are not in their allowlist.
+ALTER TABLE names ADD COLUMN auto_refund BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE names ADD COLUMN metaname_allowlist VARCHAR(32)[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,2 @@
+-- Compensating transactions made by staff to undo an earlier transaction, see `Transaction::reverse`.
+ALTER TYPE transaction_type ADD VALUE 'reversal';
@@ -0,0 +1,3 @@
+-- A transaction can only ever be reversed once. This lives in its own migration because a new enum
+-- value can't be used in the transaction that added it.
+CREATE UNIQUE INDEX idx_transactions_reversal_original_id ON transactions (original_id) WHERE transaction_type = 'reversal';
@@ -0,0 +1,14 @@
+-- Every change to `wallets.locked`, with who made it and why.
+CREATE TABLE wallet_lock_events (
+    id SERIAL PRIMARY KEY,
+    wallet_id INTEGER NOT NULL REFERENCES wallets(id) ON DELETE CASCADE,
+    locked BOOLEAN NOT NULL,
+    reason VARCHAR(255) NOT NULL,
+    actor VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_wallet_lock_events_wallet_id ON wallet_lock_events (wallet_id, created_at DESC);
+
+UPDATE wallets SET locked = FALSE WHERE locked IS NULL;
+ALTER TABLE wallets ALTER COLUMN locked SET NOT NULL;
@@ -0,0 +1,2 @@
+-- Corrections written by the ledger audit, see `jobs::ledger_audit`. The amount is signed.
+ALTER TYPE transaction_type ADD VALUE 'adjustment';
@@ -0,0 +1,5 @@
+-- Every transaction is hashed together with the hash of the one before it, see
+-- `Transaction::chain_hash`. Rows from before the chain existed are linked once on startup.
+ALTER TABLE transactions
+    ADD COLUMN prev_hash VARCHAR(64) NULL,
+    ADD COLUMN hash VARCHAR(64) NULL;
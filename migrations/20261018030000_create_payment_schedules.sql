@@ -0,0 +1,32 @@
+-- Payments a wallet makes on its own, on a cron schedule or every `interval_secs` seconds.
+-- `next_run_at` is NULL once the schedule finished or was cancelled.
+CREATE TABLE payment_schedules (
+    id SERIAL PRIMARY KEY,
+    "from" CHAR(10) NOT NULL,
+    "to" CHAR(10) NOT NULL,
+    amount NUMERIC(16, 2) NOT NULL CHECK (amount > 0),
+    metadata VARCHAR(512) NULL,
+    cron VARCHAR(255) NULL,
+    interval_secs BIGINT NULL CHECK (interval_secs > 0),
+    next_run_at TIMESTAMPTZ NULL,
+    ends_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    cancelled_at TIMESTAMPTZ NULL,
+    CHECK ((cron IS NULL) <> (interval_secs IS NULL))
+);
+
+CREATE INDEX idx_payment_schedules_from ON payment_schedules ("from", created_at DESC);
+CREATE INDEX idx_payment_schedules_next_run_at ON payment_schedules (next_run_at)
+    WHERE next_run_at IS NOT NULL;
+
+-- Every payment a schedule attempted, with the transaction it made or why it failed.
+CREATE TABLE payment_schedule_runs (
+    id SERIAL PRIMARY KEY,
+    schedule_id INTEGER NOT NULL REFERENCES payment_schedules(id) ON DELETE CASCADE,
+    due_at TIMESTAMPTZ NOT NULL,
+    transaction_id INTEGER NULL REFERENCES transactions(id),
+    error VARCHAR(255) NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_payment_schedule_runs_schedule_id ON payment_schedule_runs (schedule_id, id DESC);
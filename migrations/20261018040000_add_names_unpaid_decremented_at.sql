@@ -0,0 +1,3 @@
+-- The `unpaid` bonus counts down with time rather than with lifecycle runs, so it no longer
+-- depends on how often or on how many instances the job runs.
+ALTER TABLE names ADD COLUMN unpaid_decremented_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
@@ -0,0 +1,508 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use rust_decimal::{Decimal, dec};
+use serde::{Deserialize, Serialize};
+
+use crate::Args;
+use crate::errors::config::ConfigError;
+use crate::utils::receipts;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Where the config file is looked for when `--config` is not given.
+pub const DEFAULT_CONFIG_PATH: &str = "kromer.toml";
+
+/// Metadata is stored in a `VARCHAR(512)` column.
+const METADATA_COLUMN_LENGTH: usize = 512;
+
+/// Server configuration, read from a TOML file and overridden by `Args`.
+///
+/// Every field has a default, so a missing file or section is fine.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub motd: MotdConfig,
+    pub currency: CurrencyConfig,
+    pub names: NamesConfig,
+    pub wallets: WalletsConfig,
+    pub limits: LimitsConfig,
+    pub receipts: ReceiptsConfig,
+    pub schedules: SchedulesConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// The URL clients reach the HTTP API on.
+    pub public_url: String,
+    /// The URL clients reach the websocket API on.
+    pub public_ws_url: String,
+    pub debug_mode: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MotdConfig {
+    /// Message of the day shown to clients.
+    pub text: String,
+    pub notice: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CurrencyConfig {
+    pub currency_name: String,
+    pub currency_symbol: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamesConfig {
+    /// How much registering a name costs, unless a length tier or an override says otherwise.
+    pub cost: i64,
+    /// Registration costs for short names, the tier with the smallest `max_length` that fits wins.
+    pub length_tiers: Vec<NameLengthTier>,
+    /// Names containing any of these words cost `reserved_word_surcharge` more to register.
+    pub reserved_words: Vec<String>,
+    pub reserved_word_surcharge: i64,
+    /// How much renewing a name for another term costs.
+    pub renewal_cost: i64,
+    /// How long a registration or renewal lasts, in days.
+    pub term_days: i64,
+    /// How long an expired name is kept for its owner to renew before it is released, in days.
+    pub grace_period_days: i64,
+    /// The `unpaid` value of a newly registered name, it goes down by one every
+    /// `unpaid_decrement_secs`.
+    pub unpaid_bonus: i64,
+    /// How long each point of `unpaid` lasts, in seconds. Independent of how often the lifecycle
+    /// job runs or how many instances run it.
+    pub unpaid_decrement_secs: i64,
+    /// How often the name lifecycle job runs, in seconds.
+    pub lifecycle_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NameLengthTier {
+    /// The longest name this tier applies to, in characters.
+    pub max_length: usize,
+    pub cost: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WalletsConfig {
+    /// Balance granted to wallets when they are first created.
+    pub initial_balance: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Longest transaction metadata accepted, in characters.
+    pub max_metadata_length: usize,
+    /// Longest websocket message accepted, in characters.
+    pub max_ws_message_length: usize,
+    /// Most transfers accepted in a single batch.
+    pub max_batch_transfers: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReceiptsConfig {
+    /// Hex ed25519 seed transaction receipts are signed with, shared by every instance. The server
+    /// requires it (see [`Config::require_signing_key`]), elsewhere empty means a temporary key.
+    pub signing_key: String,
+    /// Hex public keys of earlier signing keys, receipts they signed keep verifying.
+    pub retired_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulesConfig {
+    /// How often due scheduled payments are made, in seconds.
+    pub worker_interval_secs: u64,
+    /// Most schedules a wallet can have active at once.
+    pub max_per_wallet: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            public_url: "https://kromer.reconnected.cc".to_string(),
+            public_ws_url: "wss://kromer.reconnected.cc/api/krist/ws".to_string(),
+            debug_mode: false,
+        }
+    }
+}
+
+impl Default for MotdConfig {
+    fn default() -> Self {
+        Self {
+            text: "Welcome to Kromer!".to_string(),
+            notice: String::new(),
+        }
+    }
+}
+
+impl Default for CurrencyConfig {
+    fn default() -> Self {
+        Self {
+            currency_name: "Kromer".to_string(),
+            currency_symbol: "KRO".to_string(),
+        }
+    }
+}
+
+impl Default for NamesConfig {
+    fn default() -> Self {
+        Self {
+            cost: 500,
+            length_tiers: vec![
+                NameLengthTier {
+                    max_length: 1,
+                    cost: 5000,
+                },
+                NameLengthTier {
+                    max_length: 2,
+                    cost: 2500,
+                },
+                NameLengthTier {
+                    max_length: 3,
+                    cost: 1000,
+                },
+            ],
+            reserved_words: Vec::new(),
+            reserved_word_surcharge: 1000,
+            renewal_cost: 500,
+            term_days: 30,
+            grace_period_days: 7,
+            unpaid_bonus: 500,
+            unpaid_decrement_secs: 60,
+            lifecycle_interval_secs: 60,
+        }
+    }
+}
+
+impl Default for WalletsConfig {
+    fn default() -> Self {
+        Self {
+            initial_balance: dec!(0),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_metadata_length: METADATA_COLUMN_LENGTH,
+            max_ws_message_length: 512,
+            max_batch_transfers: 100,
+        }
+    }
+}
+
+impl Default for SchedulesConfig {
+    fn default() -> Self {
+        Self {
+            worker_interval_secs: 30,
+            max_per_wallet: 25,
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file named by `args` (if any), apply the overrides from `args` and validate the result.
+    pub fn load(args: &Args) -> Result<Self, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(DEFAULT_CONFIG_PATH)?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_args(args);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| ConfigError::Read(path.to_path_buf(), err))?;
+
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    fn apply_args(&mut self, args: &Args) {
+        if let Some(public_url) = &args.public_url {
+            self.server.public_url = public_url.clone();
+        }
+        if let Some(public_ws_url) = &args.public_ws_url {
+            self.server.public_ws_url = public_ws_url.clone();
+        }
+        if let Some(motd) = &args.motd {
+            self.motd.text = motd.clone();
+        }
+        if let Some(name_cost) = args.name_cost {
+            self.names.cost = name_cost;
+        }
+        if let Some(initial_balance) = args.initial_balance {
+            self.wallets.initial_balance = initial_balance;
+        }
+        if let Some(receipt_key) = &args.receipt_key {
+            self.receipts.signing_key = receipt_key.clone();
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, reason: &str| Err(ConfigError::Invalid(field, reason.to_string()));
+
+        if !self.server.public_url.starts_with("http://")
+            && !self.server.public_url.starts_with("https://")
+        {
+            return invalid("server.public_url", "must be an http(s) URL");
+        }
+
+        let ws_schemes = ["ws://", "wss://", "http://", "https://"];
+        if !ws_schemes
+            .iter()
+            .any(|scheme| self.server.public_ws_url.starts_with(scheme))
+        {
+            return invalid("server.public_ws_url", "must be a ws(s) or http(s) URL");
+        }
+
+        if self.currency.currency_name.is_empty() {
+            return invalid("currency.currency_name", "must not be empty");
+        }
+
+        if self.currency.currency_symbol.is_empty() {
+            return invalid("currency.currency_symbol", "must not be empty");
+        }
+
+        if self.names.cost <= 0 {
+            return invalid("names.cost", "must be positive");
+        }
+
+        for tier in &self.names.length_tiers {
+            if !(1..=64).contains(&tier.max_length) {
+                return invalid("names.length_tiers", "max_length must be between 1 and 64");
+            }
+
+            if tier.cost <= 0 {
+                return invalid("names.length_tiers", "cost must be positive");
+            }
+        }
+
+        if self
+            .names
+            .reserved_words
+            .iter()
+            .any(|word| word.is_empty() || *word != word.to_lowercase())
+        {
+            return invalid("names.reserved_words", "must be non-empty and lowercase");
+        }
+
+        if self.names.reserved_word_surcharge < 0 {
+            return invalid("names.reserved_word_surcharge", "must not be negative");
+        }
+
+        if self.names.renewal_cost <= 0 {
+            return invalid("names.renewal_cost", "must be positive");
+        }
+
+        if self.names.term_days <= 0 {
+            return invalid("names.term_days", "must be positive");
+        }
+
+        if self.names.grace_period_days < 0 {
+            return invalid("names.grace_period_days", "must not be negative");
+        }
+
+        if self.names.unpaid_bonus < 0 {
+            return invalid("names.unpaid_bonus", "must not be negative");
+        }
+
+        if self.names.unpaid_decrement_secs <= 0 {
+            return invalid("names.unpaid_decrement_secs", "must be positive");
+        }
+
+        if self.names.lifecycle_interval_secs == 0 {
+            return invalid("names.lifecycle_interval_secs", "must be positive");
+        }
+
+        let initial_balance = self.wallets.initial_balance;
+        if initial_balance.is_sign_negative() || initial_balance.round_dp(2) != initial_balance {
+            return invalid(
+                "wallets.initial_balance",
+                "must not be negative or have more than 2 decimals",
+            );
+        }
+
+        if !(1..=METADATA_COLUMN_LENGTH).contains(&self.limits.max_metadata_length) {
+            return invalid("limits.max_metadata_length", "must be between 1 and 512");
+        }
+
+        if self.limits.max_ws_message_length == 0 {
+            return invalid("limits.max_ws_message_length", "must be positive");
+        }
+
+        if self.limits.max_batch_transfers == 0 {
+            return invalid("limits.max_batch_transfers", "must be positive");
+        }
+
+        if self.schedules.worker_interval_secs == 0 {
+            return invalid("schedules.worker_interval_secs", "must be positive");
+        }
+
+        if !self.receipts.signing_key.is_empty()
+            && receipts::parse_signing_key(&self.receipts.signing_key).is_none()
+        {
+            return invalid("receipts.signing_key", "must be a 32 byte hex seed");
+        }
+
+        if self
+            .receipts
+            .retired_keys
+            .iter()
+            .any(|key| receipts::parse_public_key(key).is_none())
+        {
+            return invalid("receipts.retired_keys", "must be 32 byte hex public keys");
+        }
+
+        Ok(())
+    }
+
+    /// Check that a receipt signing key is configured, which the server needs but e.g. the audit
+    /// doesn't.
+    ///
+    /// Every instance has to sign with the same key that outlives restarts, or receipts stop
+    /// verifying offline.
+    pub fn require_signing_key(&self) -> Result<(), ConfigError> {
+        if self.receipts.signing_key.is_empty() {
+            return Err(ConfigError::Invalid(
+                "receipts.signing_key",
+                "must be set, e.g. through KROMER_RECEIPT_KEY".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn init_config(config: Config) {
+    CONFIG.set(config).unwrap();
+}
+
+/// The loaded config, or the defaults if none was loaded (e.g. in tests).
+pub fn get_config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn test_example_config_file() {
+        let mut config =
+            Config::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/kromer.example.toml")).unwrap();
+
+        // The signing key is a placeholder, so a copy of the example doesn't run with a known key.
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("receipts.signing_key", _))
+        ));
+        config.receipts.signing_key.clear();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_require_signing_key() {
+        let mut config = Config::default();
+        assert!(matches!(
+            config.require_signing_key(),
+            Err(ConfigError::Invalid("receipts.signing_key", _))
+        ));
+
+        config.receipts.signing_key = "ab".repeat(32);
+        config.validate().unwrap();
+        config.require_signing_key().unwrap();
+    }
+
+    #[test]
+    fn test_partial_config_file() {
+        let config: Config = toml::from_str(
+            r#"
+            [motd]
+            text = "Hello"
+
+            [wallets]
+            initial_balance = 10.5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.motd.text, "Hello");
+        assert_eq!(config.wallets.initial_balance, dec!(10.5));
+        assert_eq!(config.names, NamesConfig::default());
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        assert!(toml::from_str::<Config>("[names]\nprice = 5").is_err());
+
+        let mut config = Config::default();
+        config.names.cost = 0;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("names.cost", _))
+        ));
+
+        let mut config = Config::default();
+        config.limits.max_metadata_length = 1024;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("limits.max_metadata_length", _))
+        ));
+
+        let mut config = Config::default();
+        config.names.length_tiers[0].cost = 0;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("names.length_tiers", _))
+        ));
+
+        let mut config = Config::default();
+        config.names.reserved_words = vec!["Kromer".to_owned()];
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.wallets.initial_balance = dec!(0.001);
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.receipts.signing_key = "abcd".to_owned();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("receipts.signing_key", _))
+        ));
+
+        let mut config = Config::default();
+        config.receipts.retired_keys = vec!["zz".repeat(32)];
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid("receipts.retired_keys", _))
+        ));
+    }
+}
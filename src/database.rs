@@ -1,7 +1,17 @@
+pub mod locks;
+pub mod motd;
 pub mod name;
+pub mod name_listing;
+pub mod name_price_override;
+pub mod name_route;
+pub mod outbox;
+pub mod payment_schedule;
+pub mod payment_schedule_run;
 pub mod player;
+pub mod reserved_name;
 pub mod transaction;
 pub mod wallet;
+pub mod wallet_lock_event;
 
 use sqlx::{Encode, Executor, Postgres, prelude::Type};
 
@@ -55,6 +65,10 @@ pub enum DatabaseError {
 
     #[error(transparent)]
     Generic(#[from] GenericError),
+
+    /// Something wrong with a single transfer of a batch, at the given index.
+    #[error("Transfer {0}: {1}")]
+    BatchTransfer(usize, Box<DatabaseError>),
 }
 
 impl From<DatabaseError> for KromerError {
@@ -66,6 +80,7 @@ impl From<DatabaseError> for KromerError {
             DatabaseError::Transaction(error) => KromerError::Transaction(error),
             DatabaseError::Wallet(error) => KromerError::Wallet(error),
             DatabaseError::Generic(error) => KromerError::Validation(error.to_string()), // nyehehehe
+            DatabaseError::BatchTransfer(_, error) => KromerError::from(*error),
         }
     }
 }
@@ -81,6 +96,9 @@ impl From<DatabaseError> for KristError {
             DatabaseError::Transaction(error) => KristError::Transaction(error.into()),
             DatabaseError::Wallet(error) => KristError::Address(error.into()),
             DatabaseError::Generic(error) => KristError::Generic(error),
+            DatabaseError::BatchTransfer(index, error) => {
+                KristError::BatchTransfer(index, Box::new(KristError::from(*error)))
+            }
         }
     }
 }
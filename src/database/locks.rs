@@ -0,0 +1,96 @@
+//! Postgres advisory locks shared by every kromer instance.
+//!
+//! All keys are defined here so they can't collide. They are offsets from "kromer" in ASCII,
+//! which keeps them out of the way of anything else sharing the database.
+//!
+//! # Lock order
+//!
+//! Transactions that move funds take their locks in this order, and never go back to an earlier
+//! step:
+//!
+//! 0. the idempotency key of the request, if it has one ([`lock_idempotency_key`]),
+//! 1. wallet rows, all at once and in id order ([`super::wallet::Model::lock_for_update`]),
+//! 2. [`AdvisoryLock::Chain`], taken by every insert into `transactions`,
+//! 3. [`AdvisoryLock::Outbox`], taken by every queued event.
+//!
+//! Both advisory locks are held until commit, so a transaction must never lock a wallet after
+//! it inserted a transaction or queued an event. Everything it is going to pay out of or into,
+//! including refund recipients, has to be resolved and locked up front. Anything that doesn't
+//! fit has to go into a transaction of its own.
+
+use sqlx::{Executor, Postgres};
+
+use crate::database::{DatabaseError, Result};
+
+const KEY_BASE: i64 = 0x6b726f6d6572; // "kromer"
+/// Class of the locks taken by [`lock_idempotency_key`]. They use the two key form, which doesn't
+/// overlap with the keys of [`AdvisoryLock`].
+const IDEMPOTENCY_KEY_CLASS: i32 = 0x6b72; // "kr"
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvisoryLock {
+    /// Held from queueing an outbox event until commit, see [`super::outbox`].
+    Outbox,
+    /// Held by whichever instance is currently publishing outbox events.
+    Dispatcher,
+    /// Held by whichever instance is currently running the name lifecycle.
+    Lifecycle,
+    /// Held from reading the transaction chain head until commit, see [`super::transaction`].
+    Chain,
+    /// Held by whichever instance is currently making scheduled payments.
+    PaymentsWorker,
+}
+
+impl AdvisoryLock {
+    pub const fn key(self) -> i64 {
+        match self {
+            Self::Outbox => KEY_BASE,
+            Self::Dispatcher => KEY_BASE + 0x01,
+            Self::Lifecycle => KEY_BASE + 0x10,
+            Self::Chain => KEY_BASE + 0x20,
+            Self::PaymentsWorker => KEY_BASE + 0x30,
+        }
+    }
+}
+
+/// Wait for `lock` and hold it until the surrounding transaction ends.
+pub async fn lock_xact<'q, E>(executor: E, lock: AdvisoryLock) -> Result<()>
+where
+    E: 'q + Executor<'q, Database = Postgres>,
+{
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(lock.key())
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Take `lock` until the surrounding transaction ends, if nobody else holds it.
+pub async fn try_lock_xact<'q, E>(executor: E, lock: AdvisoryLock) -> Result<bool>
+where
+    E: 'q + Executor<'q, Database = Postgres>,
+{
+    sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+        .bind(lock.key())
+        .fetch_one(executor)
+        .await
+        .map_err(DatabaseError::Sqlx)
+}
+
+/// Wait for the lock on idempotency key `key` of wallet `from` and hold it until the surrounding
+/// transaction ends.
+///
+/// Keys are hashed, so unrelated requests may wait for each other now and then.
+pub async fn lock_idempotency_key<'q, E>(executor: E, from: &str, key: &str) -> Result<()>
+where
+    E: 'q + Executor<'q, Database = Postgres>,
+{
+    sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+        .bind(IDEMPOTENCY_KEY_CLASS)
+        .bind(format!("{from}:{key}"))
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::database::{DatabaseError, ModelExt, Result};
+
+/// Longest MOTD that fits in the `motd` column.
+pub const MOTD_MAX_LENGTH: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i32,
+    pub motd: String,
+    pub set_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM motd WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM motd ORDER BY id DESC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM motd";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    /// The MOTD that was set last, `None` if it was never set and the configured one applies.
+    pub async fn fetch_current<E>(executor: E) -> Result<Option<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM motd ORDER BY id DESC LIMIT 1";
+
+        sqlx::query_as(q)
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    pub async fn set<E>(executor: E, motd: &str) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "INSERT INTO motd(motd) VALUES ($1) RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(motd)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Pool;
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_latest_motd_is_current(pool: Pool<Postgres>) -> Result<()> {
+        assert_eq!(Model::fetch_current(&pool).await?, None);
+
+        let first = Model::set(&pool, "Hello").await?;
+        let second = Model::set(&pool, "Goodbye").await?;
+        assert!(second.set_at >= first.set_at);
+
+        let current = Model::fetch_current(&pool).await?.unwrap();
+        assert_eq!(current, second);
+        assert_eq!(Model::total_count(&pool).await?, 2);
+
+        Ok(())
+    }
+}
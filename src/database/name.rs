@@ -3,6 +3,11 @@ use chrono::{DateTime, Utc};
 use rust_decimal::{Decimal, dec};
 use sqlx::{Acquire, Encode, Executor, Pool, Postgres, Type};
 
+use crate::config::get_config;
+use crate::database::locks::{self, AdvisoryLock};
+use crate::database::name_listing::Model as NameListing;
+use crate::database::name_route::Model as NameRoute;
+use crate::database::outbox::Model as Outbox;
 use crate::database::transaction::Model as Transaction;
 use crate::database::transaction::{TransactionCreateData, TransactionType};
 use crate::database::wallet::Model as Wallet;
@@ -10,13 +15,20 @@ use crate::database::{DatabaseError, Result};
 
 use crate::errors::name::NameError;
 use crate::errors::wallet::WalletError;
-use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
+use crate::models::krist::webserver::lookup::{LookupParams, NameLookupFields};
+use crate::models::krist::websockets::WebSocketEvent;
 use crate::websockets::WebSocketServer;
 use crate::{
     database::ModelExt, errors::krist::generic::GenericError,
     models::krist::names::NameDataUpdateBody, routes::PaginationParams, utils::validation,
 };
 
+/// Wallet lifecycle payments and ledger entries are made out to.
+pub const NAME_LEDGER_ADDRESS: &str = "serverwelf";
+
+/// Metadata message of refunds made because a name does not accept a metaname.
+pub const AUTO_REFUND_MESSAGE: &str = "This name does not accept payments to this metaname";
+
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
 pub struct Model {
     pub id: i32,
@@ -27,7 +39,14 @@ pub struct Model {
     pub original_owner: String,
     pub time_registered: DateTime<Utc>,
     pub unpaid: Decimal,
+    /// Time up to which `unpaid` has been counted down.
+    pub unpaid_decremented_at: DateTime<Utc>,
     pub metadata: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub expired: bool,
+    /// Whether payments to metanames outside `metaname_allowlist` are refunded straight away.
+    pub auto_refund: bool,
+    pub metaname_allowlist: Vec<String>,
 }
 
 #[async_trait]
@@ -118,66 +137,136 @@ impl<'q> Model {
         sqlx::query_scalar(q).fetch_one(pool).await
     }
 
-    pub async fn create<E>(pool: E, name: String, owner: String) -> Result<Model>
+    /// Names owned by any of `owners`, or every name if none are given.
+    pub async fn lookup_owners<E>(
+        executor: E,
+        owners: Option<&'q [&'q str]>,
+        params: &LookupParams<NameLookupFields>,
+    ) -> Result<Vec<Model>>
     where
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let q = "INSERT INTO names(name, owner, original_owner, time_registered) VALUES ($1, $2, $2, NOW()) RETURNING *";
-
-        sqlx::query_as(q)
-            .bind(name)
-            .bind(owner)
-            .fetch_one(pool)
+        let order = params.order.as_sql();
+        let q = format!(
+            "SELECT * FROM names WHERE ($1::text[] IS NULL OR owner = ANY($1)) ORDER BY {} {order}, id {order} LIMIT $2 OFFSET $3",
+            params.order_by.column()
+        );
+
+        sqlx::query_as(&q)
+            .bind(owners)
+            .bind(params.limit)
+            .bind(params.offset)
+            .fetch_all(executor)
             .await
             .map_err(DatabaseError::Sqlx)
     }
 
-    pub async fn update_metadata<E, S>(pool: E, name: S, metadata: String) -> Result<Model>
+    pub async fn count_lookup_owners<E>(executor: E, owners: Option<&'q [&'q str]>) -> Result<usize>
     where
-        S: AsRef<str>,
         E: 'q + Executor<'q, Database = Postgres>,
     {
-        let name = name.as_ref();
+        let q = "SELECT COUNT(*) FROM names WHERE ($1::text[] IS NULL OR owner = ANY($1))";
+        let result: i64 = sqlx::query_scalar(q)
+            .bind(owners)
+            .fetch_one(executor)
+            .await?;
 
-        let q = "UPDATE names SET metadata = $2 WHERE name = $1 RETURNING *";
+        Ok(result as usize)
+    }
+
+    pub async fn create<E>(pool: E, name: String, owner: String) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let config = &get_config().names;
+        let q = "INSERT INTO names(name, owner, original_owner, time_registered, unpaid, expires_at) VALUES ($1, $2, $2, NOW(), $3, NOW() + make_interval(days => $4)) RETURNING *";
 
         sqlx::query_as(q)
             .bind(name)
-            .bind(metadata)
+            .bind(owner)
+            .bind(Decimal::from(config.unpaid_bonus))
+            .bind(config.term_days as i32)
             .fetch_one(pool)
             .await
             .map_err(DatabaseError::Sqlx)
     }
 
+    /// Set (or clear, with `None`) the A record of the name.
+    ///
+    /// Like Krist, every change is recorded as a `name_a_record` transaction from the owner to `a`.
+    pub async fn update_a_record<A>(
+        self,
+        conn: A,
+        server: &WebSocketServer,
+        metadata: Option<String>,
+    ) -> Result<Model>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        if self.expired {
+            return Err(DatabaseError::Name(NameError::NameExpired(self.name)));
+        }
+
+        let mut tx = conn.begin().await?;
+
+        let q = "UPDATE names SET metadata = $3, last_updated = NOW() WHERE id = $1 AND owner = $2 AND NOT expired RETURNING *";
+
+        let updated_name: Model = sqlx::query_as(q)
+            .bind(self.id)
+            .bind(&self.owner)
+            .bind(&metadata)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| DatabaseError::Name(NameError::NotNameOwner(self.name.clone())))?;
+
+        let creation_data = TransactionCreateData {
+            from: self.owner,
+            to: "a".to_owned(),
+            amount: dec!(0),
+            metadata,
+            name: Some(self.name),
+            transaction_type: TransactionType::NameARecord,
+            ..Default::default()
+        };
+
+        let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        let event = WebSocketEvent::Name {
+            name: updated_name.clone().into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(updated_name)
+    }
+
     pub async fn ctrl_update_metadata<S: AsRef<str>>(
         pool: &Pool<Postgres>,
+        server: &WebSocketServer,
         name: S,
         body: NameDataUpdateBody,
     ) -> Result<Model> {
-        let name = name.as_ref();
-
-        let metadata_record = match body.a {
-            Some(metadata_record) => metadata_record,
-            None => {
-                return Err(DatabaseError::Generic(GenericError::InvalidParameter(
-                    "name".to_owned(),
-                )));
-            }
-        };
+        // An empty or missing record removes the data from the name.
+        let metadata_record = body.a.filter(|a| !a.is_empty());
 
-        if !validation::is_valid_name(name, false) {
-            return Err(DatabaseError::Generic(GenericError::InvalidParameter(
-                "name".to_owned(),
-            )));
-        }
+        let name = validation::normalize_name(name.as_ref()).ok_or_else(|| {
+            DatabaseError::Generic(GenericError::InvalidParameter("name".to_owned()))
+        })?;
 
-        if !validation::is_valid_a_record(&metadata_record) {
+        if let Some(metadata_record) = &metadata_record
+            && !validation::is_valid_a_record(metadata_record)
+        {
             return Err(DatabaseError::Generic(GenericError::InvalidParameter(
                 "a".to_owned(),
             )));
         }
 
-        let name = name.trim().to_lowercase();
         let wallet = Wallet::verify_address(pool, body.private_key).await?;
         if !wallet.authed {
             tracing::info!("Auth failed on name update");
@@ -191,13 +280,42 @@ impl<'q> Model {
             return Err(DatabaseError::Name(NameError::NotNameOwner(name)));
         }
 
-        if model.metadata == Some(metadata_record.clone()) {
+        if model.metadata == metadata_record {
             return Ok(model);
         }
 
-        let updated_model = Self::update_metadata(pool, &name, metadata_record).await?;
+        model.update_a_record(pool, server, metadata_record).await
+    }
 
-        Ok(updated_model)
+    /// Whether a payment sent to `metaname@name.kro` should be refunded automatically.
+    ///
+    /// Payments without a metaname are never refunded.
+    pub fn should_refund(&self, metaname: Option<&str>) -> bool {
+        metaname.is_some_and(|metaname| {
+            self.auto_refund && !self.metaname_allowlist.iter().any(|m| m == metaname)
+        })
+    }
+
+    /// Set whether payments to metanames outside `allowlist` are refunded automatically.
+    pub async fn set_refund_settings<E>(
+        &self,
+        executor: E,
+        auto_refund: bool,
+        allowlist: &[String],
+    ) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q =
+            "UPDATE names SET auto_refund = $2, metaname_allowlist = $3 WHERE id = $1 RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(self.id)
+            .bind(auto_refund)
+            .bind(allowlist)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
     }
 
     /// Fetches the owner of the wallet and returns its database model.
@@ -224,14 +342,30 @@ impl<'q> Model {
     where
         A: Acquire<'q, Database = Postgres>,
     {
+        // Names in their grace period can only be renewed by their owner or released.
+        if self.expired {
+            return Err(DatabaseError::Name(NameError::NameExpired(self.name)));
+        }
+
         let mut tx = conn.begin().await?;
-        let q = "UPDATE names SET owner = $2, last_updated = NOW(), last_transfered = NOW() WHERE owner = $1 RETURNING *";
+
+        // A listing only makes sense for the owner that created it. Deleting it first also means
+        // transfers lock the listing before the name, in the same order as purchases do.
+        NameListing::delete_for_name(&mut *tx, self.id).await?;
+
+        // Only touch this one name, and only if nobody transferred it in the meantime.
+        let q = "UPDATE names SET owner = $3, last_updated = NOW(), last_transfered = NOW(), auto_refund = FALSE, metaname_allowlist = '{}' WHERE id = $1 AND owner = $2 AND NOT expired RETURNING *";
 
         let updated_name: Model = sqlx::query_as(q)
+            .bind(self.id)
             .bind(&self.owner)
             .bind(&new_owner_address)
-            .fetch_one(&mut *tx)
-            .await?;
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| DatabaseError::Name(NameError::NotNameOwner(self.name.clone())))?;
+
+        // Routes point at wallets of the previous owner, the new owner has to set up their own.
+        NameRoute::delete_for_name(&mut *tx, self.id).await?;
 
         let creation_data = TransactionCreateData {
             from: self.owner,
@@ -243,13 +377,620 @@ impl<'q> Model {
         };
 
         let transaction = Transaction::create(&mut *tx, creation_data).await?;
-        let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
+        let event = WebSocketEvent::Transaction {
             transaction: transaction.into(),
-        });
-        server.broadcast_event(event).await;
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        let event = WebSocketEvent::Name {
+            name: updated_name.clone().into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
 
         tx.commit().await?;
+        server.notify_outbox();
 
         Ok(updated_name)
     }
+
+    /// Pay for another term. It is added to the current expiry, so renewing early loses nothing.
+    ///
+    /// Renewing during the grace period takes the name out of it again.
+    pub async fn renew<A>(self, conn: A, server: &WebSocketServer) -> Result<Model>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let config = &get_config().names;
+        let mut tx = conn.begin().await?;
+
+        let q = "UPDATE names SET expires_at = expires_at + make_interval(days => $3), expired = FALSE, last_updated = NOW() WHERE id = $1 AND owner = $2 RETURNING *";
+
+        let renewed_name: Model = sqlx::query_as(q)
+            .bind(self.id)
+            .bind(&self.owner)
+            .bind(config.term_days as i32)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| DatabaseError::Name(NameError::NotNameOwner(self.name.clone())))?;
+
+        let creation_data = TransactionCreateData {
+            from: self.owner,
+            to: NAME_LEDGER_ADDRESS.to_owned(),
+            amount: Decimal::from(config.renewal_cost),
+            name: Some(self.name),
+            transaction_type: TransactionType::NameRenewal,
+            ..Default::default()
+        };
+
+        let transaction = Transaction::create(&mut *tx, creation_data).await?;
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        let event = WebSocketEvent::Name {
+            name: renewed_name.clone().into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(renewed_name)
+    }
+
+    /// Takes the lifecycle lock until the surrounding transaction ends, if no other instance has it.
+    pub async fn try_lock_lifecycle<E>(executor: E) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        locks::try_lock_xact(executor, AdvisoryLock::Lifecycle).await
+    }
+
+    /// Count down the `unpaid` bonus of every name that still has some left, by one for every
+    /// full `names.unpaid_decrement_secs` since it was last counted down, up to `now`.
+    ///
+    /// Running this again for the same `now`, from any instance, changes nothing.
+    pub async fn decrement_unpaid<E>(executor: E, now: DateTime<Utc>) -> Result<u64>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let period_secs = get_config().names.unpaid_decrement_secs;
+        let q = r#"
+        WITH due AS (
+            SELECT id, FLOOR(EXTRACT(EPOCH FROM ($1 - unpaid_decremented_at)) / $2)::BIGINT AS steps
+            FROM names
+            WHERE unpaid > 0
+        )
+        UPDATE names SET
+            unpaid = GREATEST(names.unpaid - due.steps, 0),
+            unpaid_decremented_at = names.unpaid_decremented_at + make_interval(secs => due.steps * $2)
+        FROM due
+        WHERE names.id = due.id AND due.steps > 0
+        "#;
+        let result = sqlx::query(q)
+            .bind(now)
+            .bind(period_secs)
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Names whose term ran out at `now` but whose expiry has not been recorded yet.
+    pub async fn fetch_due_for_expiry<E>(executor: E, now: DateTime<Utc>) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM names WHERE NOT expired AND expires_at <= $1 ORDER BY expires_at";
+
+        sqlx::query_as(q)
+            .bind(now)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Expired names whose grace period is over at `now`.
+    pub async fn fetch_due_for_release<E>(executor: E, now: DateTime<Utc>) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM names WHERE expired AND expires_at + make_interval(days => $2) <= $1 ORDER BY expires_at";
+
+        sqlx::query_as(q)
+            .bind(now)
+            .bind(get_config().names.grace_period_days as i32)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Record that the name expired, which starts its grace period and takes it off the market.
+    ///
+    /// Returns `None` if the name was renewed or transferred in the meantime.
+    pub async fn expire<A>(
+        self,
+        conn: A,
+        server: &WebSocketServer,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Model>>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        NameListing::delete_for_name(&mut *tx, self.id).await?;
+
+        let q = "UPDATE names SET expired = TRUE WHERE id = $1 AND owner = $2 AND NOT expired AND expires_at <= $3 RETURNING *";
+
+        let expired_name: Option<Model> = sqlx::query_as(q)
+            .bind(self.id)
+            .bind(&self.owner)
+            .bind(now)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(expired_name) = expired_name else {
+            return Ok(None);
+        };
+
+        let creation_data = TransactionCreateData {
+            from: self.owner,
+            to: NAME_LEDGER_ADDRESS.to_owned(),
+            amount: dec!(0),
+            name: Some(self.name),
+            transaction_type: TransactionType::NameExpiry,
+            ..Default::default()
+        };
+
+        let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        let event = WebSocketEvent::Name {
+            name: expired_name.clone().into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(Some(expired_name))
+    }
+
+    /// Release a name whose grace period is over, making it available for anyone to register.
+    ///
+    /// Returns `None` if the name was renewed or transferred in the meantime.
+    pub async fn release<A>(
+        self,
+        conn: A,
+        server: &WebSocketServer,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Model>>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        NameListing::delete_for_name(&mut *tx, self.id).await?;
+
+        let q = "DELETE FROM names WHERE id = $1 AND owner = $2 AND expired AND expires_at + make_interval(days => $4) <= $3 RETURNING *";
+
+        let released_name: Option<Model> = sqlx::query_as(q)
+            .bind(self.id)
+            .bind(&self.owner)
+            .bind(now)
+            .bind(get_config().names.grace_period_days as i32)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(released_name) = released_name else {
+            return Ok(None);
+        };
+
+        let creation_data = TransactionCreateData {
+            from: self.owner,
+            to: NAME_LEDGER_ADDRESS.to_owned(),
+            amount: dec!(0),
+            name: Some(self.name),
+            transaction_type: TransactionType::NameRelease,
+            ..Default::default()
+        };
+
+        let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(Some(released_name))
+    }
+
+    /// Take a name away from its owner and make it available again, regardless of its term.
+    ///
+    /// Recorded like a regular release, with `reason` as the metadata.
+    pub async fn revoke<A>(
+        self,
+        conn: A,
+        server: &WebSocketServer,
+        reason: Option<String>,
+    ) -> Result<Model>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        NameListing::delete_for_name(&mut *tx, self.id).await?;
+
+        let q = "DELETE FROM names WHERE id = $1 RETURNING *";
+
+        let revoked_name: Model = sqlx::query_as(q)
+            .bind(self.id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| DatabaseError::Name(NameError::NameNotFound(self.name.clone())))?;
+
+        let creation_data = TransactionCreateData {
+            from: revoked_name.owner.clone(),
+            to: NAME_LEDGER_ADDRESS.to_owned(),
+            amount: dec!(0),
+            metadata: reason,
+            name: Some(revoked_name.name.clone()),
+            transaction_type: TransactionType::NameRelease,
+            ..Default::default()
+        };
+
+        let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?;
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(revoked_name)
+    }
+
+    /// Locks the given names with `FOR UPDATE` until the surrounding transaction ends, in id order.
+    pub async fn lock_by_names<E>(executor: E, names: &[&str]) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM names WHERE name = ANY($1) ORDER BY id FOR UPDATE";
+
+        sqlx::query_as(q)
+            .bind(names)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Transfer several names owned by `owner` at once, either all of them move or none do.
+    ///
+    /// Every name gets its own `name_transfer` transaction. Names are returned in the order given.
+    pub async fn transfer_many<A>(
+        conn: A,
+        server: &WebSocketServer,
+        names: &[&str],
+        owner: &str,
+        new_owner_address: &str,
+    ) -> Result<Vec<Model>>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        let locked = Self::lock_by_names(&mut *tx, names).await?;
+
+        // Check everything before moving anything, so the error names the first offending name.
+        for name in names {
+            let model = locked
+                .iter()
+                .find(|model| model.name == *name)
+                .ok_or_else(|| DatabaseError::Name(NameError::NameNotFound(name.to_string())))?;
+            if model.owner != owner {
+                return Err(DatabaseError::Name(NameError::NotNameOwner(
+                    model.name.clone(),
+                )));
+            }
+            if model.expired {
+                return Err(DatabaseError::Name(NameError::NameExpired(
+                    model.name.clone(),
+                )));
+            }
+        }
+
+        let mut transferred = Vec::with_capacity(names.len());
+        for name in names {
+            let model = locked
+                .iter()
+                .find(|model| model.name == *name)
+                .cloned()
+                .expect("name was checked above");
+
+            let updated = match model.owner == new_owner_address {
+                true => model, // Transferring to yourself does nothing, like the single transfer.
+                false => {
+                    model
+                        .transfer_ownership(&mut *tx, server, new_owner_address.to_owned())
+                        .await?
+                }
+            };
+            transferred.push(updated);
+        }
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(transferred)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::ModelExt;
+    use crate::database::transaction::TransactionNameData;
+    use crate::models::krist::names::NameJson;
+
+    async fn setup(pool: &Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(pool, "kowner0000", "hash", None).await?;
+        Wallet::create_wallet(pool, "kother0000", "hash", None).await?;
+
+        for name in ["alpha", "beta", "gamma"] {
+            Model::create(pool, name.to_owned(), "kowner0000".to_owned()).await?;
+        }
+        Model::create(pool, "delta".to_owned(), "kother0000".to_owned()).await?;
+
+        Ok(())
+    }
+
+    async fn owner_of(pool: &Pool<Postgres>, name: &str) -> String {
+        Model::fetch_by_name(pool, name)
+            .await
+            .unwrap()
+            .unwrap()
+            .owner
+    }
+
+    async fn name_transfers(pool: &Pool<Postgres>) -> i64 {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM transactions WHERE transaction_type = 'name_transfer'",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_transfer_only_moves_one_name(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let beta = Model::fetch_by_name(&pool, "beta").await?.unwrap();
+        let updated = beta
+            .transfer_ownership(&pool, &server, "kother0000".to_owned())
+            .await?;
+
+        assert_eq!(updated.name, "beta");
+        assert_eq!(updated.owner, "kother0000");
+        assert_eq!(owner_of(&pool, "alpha").await, "kowner0000");
+        assert_eq!(owner_of(&pool, "gamma").await, "kowner0000");
+        assert_eq!(name_transfers(&pool).await, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_stale_transfer_is_rejected(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let alpha = Model::fetch_by_name(&pool, "alpha").await?.unwrap();
+        alpha
+            .clone()
+            .transfer_ownership(&pool, &server, "kother0000".to_owned())
+            .await?;
+
+        let result = alpha
+            .transfer_ownership(&pool, &server, "kowner0000".to_owned())
+            .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NotNameOwner(_)))
+        ));
+        assert_eq!(owner_of(&pool, "alpha").await, "kother0000");
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_bulk_transfer(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let transferred = Model::transfer_many(
+            &pool,
+            &server,
+            &["gamma", "alpha"],
+            "kowner0000",
+            "kother0000",
+        )
+        .await?;
+
+        let names: Vec<&str> = transferred.iter().map(|name| name.name.as_str()).collect();
+        assert_eq!(names, vec!["gamma", "alpha"]);
+        assert_eq!(owner_of(&pool, "alpha").await, "kother0000");
+        assert_eq!(owner_of(&pool, "gamma").await, "kother0000");
+        assert_eq!(owner_of(&pool, "beta").await, "kowner0000");
+        assert_eq!(name_transfers(&pool).await, 2);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_bulk_transfer_is_all_or_nothing(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let result = Model::transfer_many(
+            &pool,
+            &server,
+            &["alpha", "delta", "beta"],
+            "kowner0000",
+            "kother0000",
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NotNameOwner(name))) if name == "delta"
+        ));
+
+        let result = Model::transfer_many(
+            &pool,
+            &server,
+            &["alpha", "nope"],
+            "kowner0000",
+            "kother0000",
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NameNotFound(name))) if name == "nope"
+        ));
+
+        assert_eq!(owner_of(&pool, "alpha").await, "kowner0000");
+        assert_eq!(owner_of(&pool, "beta").await, "kowner0000");
+        assert_eq!(name_transfers(&pool).await, 0);
+
+        Ok(())
+    }
+
+    async fn pending_events(pool: &Pool<Postgres>) -> Vec<WebSocketEvent> {
+        Outbox::fetch_pending(pool, 100)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.event().unwrap())
+            .collect()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_a_record_update_creates_transaction(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let alpha = Model::fetch_by_name(&pool, "alpha").await?.unwrap();
+        let updated = alpha
+            .update_a_record(&pool, &server, Some("example.com".to_owned()))
+            .await?;
+        assert_eq!(updated.metadata.as_deref(), Some("example.com"));
+        assert!(updated.last_updated.is_some());
+
+        let history = Transaction::fetch_all(&pool, 10, 0).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].transaction_type, TransactionType::NameARecord);
+        assert_eq!(history[0].from.as_deref(), Some("kowner0000"));
+        assert_eq!(history[0].to, "a");
+        assert_eq!(history[0].amount, dec!(0));
+        assert_eq!(history[0].metadata.as_deref(), Some("example.com"));
+
+        let events = pending_events(&pool).await;
+        assert!(matches!(
+            &events[..],
+            [WebSocketEvent::Transaction { transaction }, WebSocketEvent::Name { name }]
+                if transaction.transaction_type == TransactionType::NameARecord
+                    && name.a.as_deref() == Some("example.com")
+        ));
+
+        // Clearing the record is a change as well.
+        let cleared = updated.update_a_record(&pool, &server, None).await?;
+        assert_eq!(cleared.metadata, None);
+        assert_eq!(Transaction::total_count(&pool).await?, 2);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_transfer_sends_name_event(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let alpha = Model::fetch_by_name(&pool, "alpha").await?.unwrap();
+        alpha
+            .transfer_ownership(&pool, &server, "kother0000".to_owned())
+            .await?;
+
+        let events = pending_events(&pool).await;
+        assert!(matches!(
+            &events[..],
+            [WebSocketEvent::Transaction { transaction }, WebSocketEvent::Name { name }]
+                if transaction.transaction_type == TransactionType::NameTransfer
+                    && name.name == "alpha"
+                    && name.owner == "kother0000"
+                    && name.transfered.is_some()
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_revoke(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        let beta = Model::fetch_by_name(&pool, "beta").await?.unwrap();
+        let revoked = beta
+            .clone()
+            .revoke(&pool, &server, Some("abuse".to_owned()))
+            .await?;
+        assert_eq!(revoked.owner, "kowner0000");
+        assert!(Model::fetch_by_name(&pool, "beta").await?.is_none());
+
+        let history = Transaction::fetch_all(&pool, 10, 0).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].transaction_type, TransactionType::NameRelease);
+        assert_eq!(history[0].metadata.as_deref(), Some("abuse"));
+
+        let result = beta.revoke(&pool, &server, None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NameNotFound(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_unicode_name_resolves(pool: Pool<Postgres>) -> Result<()> {
+        setup(&pool).await?;
+
+        let name = validation::normalize_name("名前").unwrap();
+        Model::create(&pool, name, "kowner0000".to_owned()).await?;
+
+        let data = TransactionNameData::parse("shop@名前.kro");
+        let resolved = Model::fetch_by_name(&pool, data.name.unwrap())
+            .await?
+            .unwrap();
+        assert_eq!(resolved.name, "xn--ldr85b");
+
+        let json = NameJson::from(resolved);
+        assert_eq!(json.name, "xn--ldr85b");
+        assert_eq!(json.unicode_name, "名前");
+
+        Ok(())
+    }
 }
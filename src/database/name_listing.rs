@@ -0,0 +1,303 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{Acquire, Encode, Executor, Postgres, Type};
+
+use crate::database::name::Model as Name;
+use crate::database::outbox::Model as Outbox;
+use crate::database::transaction::Model as Transaction;
+use crate::database::transaction::{TransactionCreateData, TransactionType};
+use crate::database::{DatabaseError, ModelExt, Result};
+use crate::errors::name::NameError;
+use crate::models::krist::websockets::WebSocketEvent;
+use crate::websockets::WebSocketServer;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i32,
+    pub name_id: i32,
+    pub name: String,
+    pub seller: String,
+    pub price: Decimal,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT name_listings.*, names.name FROM name_listings JOIN names ON names.id = name_listings.name_id WHERE name_listings.id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT name_listings.*, names.name FROM name_listings JOIN names ON names.id = name_listings.name_id ORDER BY name_listings.created_at DESC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM name_listings";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    pub async fn fetch_by_name<S, E>(executor: E, name: S) -> Result<Option<Model>>
+    where
+        S: AsRef<str>,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT name_listings.*, names.name FROM name_listings JOIN names ON names.id = name_listings.name_id WHERE names.name = $1";
+
+        sqlx::query_as(q)
+            .bind(name.as_ref())
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Like [`Model::fetch_by_name`], but locks the listing and its name until the surrounding
+    /// transaction ends. Taking the name before paying keeps the lock order the same as transfers.
+    pub async fn lock_by_name<S, E>(executor: E, name: S) -> Result<Option<Model>>
+    where
+        S: AsRef<str>,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT name_listings.*, names.name FROM name_listings JOIN names ON names.id = name_listings.name_id WHERE names.name = $1 FOR UPDATE OF name_listings, names";
+
+        sqlx::query_as(q)
+            .bind(name.as_ref())
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// List a name for sale, replacing the price of an existing listing.
+    pub async fn upsert<E>(executor: E, name: &Name, price: Decimal) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"
+        WITH listing AS (
+            INSERT INTO name_listings(name_id, seller, price) VALUES ($1, $2, $3)
+            ON CONFLICT (name_id) DO UPDATE SET seller = $2, price = $3, created_at = NOW()
+            RETURNING *
+        )
+        SELECT listing.*, $4::VARCHAR AS name FROM listing
+        "#;
+
+        sqlx::query_as(q)
+            .bind(name.id)
+            .bind(&name.owner)
+            .bind(price)
+            .bind(&name.name)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Remove the listing of a name, returns whether there was one.
+    pub async fn delete_for_name<E>(executor: E, name_id: i32) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "DELETE FROM name_listings WHERE name_id = $1";
+        let result = sqlx::query(q).bind(name_id).execute(executor).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Buy a listed name: pays the seller and transfers the name to the buyer, all or nothing.
+    ///
+    /// `price` is what the buyer agreed to pay, the purchase fails if the listing changed since.
+    pub async fn purchase<A>(
+        conn: A,
+        server: &WebSocketServer,
+        name: &str,
+        buyer: &str,
+        price: Decimal,
+    ) -> Result<Name>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        // Locking the listing makes concurrent purchases of the same name wait for each other.
+        let listing = Self::lock_by_name(&mut *tx, name)
+            .await?
+            .ok_or_else(|| DatabaseError::Name(NameError::NotForSale(name.to_owned())))?;
+        if listing.price != price {
+            return Err(DatabaseError::Name(NameError::ListingPriceChanged(
+                listing.name,
+            )));
+        }
+
+        let name = Name::fetch_by_name(&mut *tx, &listing.name)
+            .await?
+            .ok_or_else(|| DatabaseError::Name(NameError::NameNotFound(listing.name.clone())))?;
+        if name.owner != listing.seller {
+            return Err(DatabaseError::Name(NameError::NotForSale(name.name)));
+        }
+        if name.owner == buyer {
+            return Err(DatabaseError::Name(NameError::AlreadyOwned(name.name)));
+        }
+
+        let creation_data = TransactionCreateData {
+            from: buyer.to_owned(),
+            to: listing.seller,
+            amount: listing.price,
+            name: Some(name.name.clone()),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        let payment = Transaction::create(&mut *tx, creation_data).await?;
+        tracing::info!("Created payment {} for name {}", payment.id, name.name);
+
+        let event = WebSocketEvent::Transaction {
+            transaction: payment.into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+
+        let updated_name = name
+            .transfer_ownership(&mut *tx, server, buyer.to_owned())
+            .await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        Ok(updated_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::wallet::Model as Wallet;
+    use crate::errors::transaction::TransactionError;
+    use rust_decimal::dec;
+    use sqlx::Pool;
+
+    async fn setup(pool: &Pool<Postgres>) -> Result<Name> {
+        Wallet::create_wallet(pool, "kseller000", "hash", None).await?;
+        Wallet::create_wallet(pool, "kbuyer0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(pool, "kpoor00000", "hash", Some(dec!(1))).await?;
+
+        Name::create(pool, "shop".to_owned(), "kseller000".to_owned()).await
+    }
+
+    async fn balance(pool: &Pool<Postgres>, address: &str) -> Decimal {
+        Wallet::fetch_by_address(pool, address)
+            .await
+            .unwrap()
+            .unwrap()
+            .balance
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_purchase_moves_name_and_funds(pool: Pool<Postgres>) -> Result<()> {
+        let name = setup(&pool).await?;
+        let server = WebSocketServer::new();
+        Model::upsert(&pool, &name, dec!(25)).await?;
+
+        let bought = Model::purchase(&pool, &server, "shop", "kbuyer0000", dec!(25)).await?;
+        assert_eq!(bought.owner, "kbuyer0000");
+        assert_eq!(balance(&pool, "kbuyer0000").await, dec!(75));
+        assert_eq!(balance(&pool, "kseller000").await, dec!(25));
+        assert_eq!(Model::fetch_by_name(&pool, "shop").await?, None);
+
+        let transfers: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM transactions WHERE name = 'shop' AND transaction_type = 'name_transfer'",
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(transfers, 1);
+
+        let result = Model::purchase(&pool, &server, "shop", "kbuyer0000", dec!(25)).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NotForSale(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_failed_purchase_changes_nothing(pool: Pool<Postgres>) -> Result<()> {
+        let name = setup(&pool).await?;
+        let server = WebSocketServer::new();
+        Model::upsert(&pool, &name, dec!(25)).await?;
+
+        let result = Model::purchase(&pool, &server, "shop", "kbuyer0000", dec!(20)).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::ListingPriceChanged(_)))
+        ));
+
+        let result = Model::purchase(&pool, &server, "shop", "kpoor00000", dec!(25)).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::InsufficientFunds
+            ))
+        ));
+
+        let result = Model::purchase(&pool, &server, "shop", "kseller000", dec!(25)).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::AlreadyOwned(_)))
+        ));
+
+        let name = Name::fetch_by_name(&pool, "shop").await?.unwrap();
+        assert_eq!(name.owner, "kseller000");
+        assert_eq!(balance(&pool, "kpoor00000").await, dec!(1));
+        assert!(Model::fetch_by_name(&pool, "shop").await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_transfer_clears_listing(pool: Pool<Postgres>) -> Result<()> {
+        let name = setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        Model::upsert(&pool, &name, dec!(25)).await?;
+        let listing = Model::upsert(&pool, &name, dec!(30)).await?;
+        assert_eq!(listing.price, dec!(30));
+        assert_eq!(listing.name, "shop");
+        assert_eq!(Model::total_count(&pool).await?, 1);
+
+        name.transfer_ownership(&pool, &server, "kbuyer0000".to_owned())
+            .await?;
+        assert_eq!(Model::fetch_by_name(&pool, "shop").await?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::config::get_config;
+use crate::database::{DatabaseError, ModelExt, Result};
+use crate::utils::name_pricing::NamePrice;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub struct Model {
+    pub id: i32,
+    pub name: String,
+    pub cost: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM name_price_overrides WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM name_price_overrides ORDER BY name ASC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM name_price_overrides";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    pub async fn fetch_by_name<S, E>(executor: E, name: S) -> Result<Option<Model>>
+    where
+        S: AsRef<str>,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM name_price_overrides WHERE name = $1";
+
+        sqlx::query_as(q)
+            .bind(name.as_ref())
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Set the price of a name, replacing an existing override.
+    pub async fn upsert<E>(executor: E, name: &str, cost: i64) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "INSERT INTO name_price_overrides(name, cost) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET cost = $2, created_at = NOW() RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(name)
+            .bind(cost)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Remove the override for a name, returns whether there was one.
+    pub async fn delete_by_name<E>(executor: E, name: &str) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "DELETE FROM name_price_overrides WHERE name = $1";
+        let result = sqlx::query(q).bind(name).execute(executor).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// What registering `name` costs right now, taking overrides into account.
+    pub async fn quote<E>(executor: E, name: &str) -> Result<NamePrice>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let override_cost = Self::fetch_by_name(executor, name)
+            .await?
+            .map(|price_override| price_override.cost);
+
+        Ok(NamePrice::compute(&get_config().names, name, override_cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Pool;
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_override_takes_precedence(pool: Pool<Postgres>) -> Result<()> {
+        let price = Model::quote(&pool, "ab").await?;
+        assert_eq!(price.override_cost, None);
+        assert_eq!(price.total, price.base_cost);
+
+        Model::upsert(&pool, "ab", 42).await?;
+        Model::upsert(&pool, "ab", 7).await?;
+        let price = Model::quote(&pool, "ab").await?;
+        assert_eq!(price.override_cost, Some(7));
+        assert_eq!(price.total, 7);
+
+        assert!(Model::delete_by_name(&pool, "ab").await?);
+        assert!(!Model::delete_by_name(&pool, "ab").await?);
+        assert_eq!(Model::quote(&pool, "ab").await?.override_cost, None);
+
+        Ok(())
+    }
+}
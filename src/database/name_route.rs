@@ -0,0 +1,242 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::database::name::Model as Name;
+use crate::database::{DatabaseError, ModelExt, Result};
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i32,
+    pub name_id: i32,
+    /// `None` for the fallback route, used for metanames without a route of their own.
+    pub metaname: Option<String>,
+    pub address: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM name_routes WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM name_routes ORDER BY id ASC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM name_routes";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    /// All routes of a name, the fallback route first.
+    pub async fn fetch_for_name<E>(executor: E, name_id: i32) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM name_routes WHERE name_id = $1 ORDER BY metaname ASC NULLS FIRST";
+
+        sqlx::query_as(q)
+            .bind(name_id)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Route `metaname` (or the fallback if `None`) to `address`, replacing an existing route.
+    pub async fn set<E>(
+        executor: E,
+        name_id: i32,
+        metaname: Option<&str>,
+        address: &str,
+    ) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"
+        INSERT INTO name_routes(name_id, metaname, address) VALUES ($1, $2, $3)
+        ON CONFLICT (name_id, COALESCE(metaname, ''))
+        DO UPDATE SET address = EXCLUDED.address, created_at = NOW()
+        RETURNING *;
+        "#;
+
+        sqlx::query_as(q)
+            .bind(name_id)
+            .bind(metaname)
+            .bind(address)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Remove the route for `metaname` (or the fallback if `None`), returns whether there was one.
+    pub async fn delete<E>(executor: E, name_id: i32, metaname: Option<&str>) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "DELETE FROM name_routes WHERE name_id = $1 AND metaname IS NOT DISTINCT FROM $2";
+        let result = sqlx::query(q)
+            .bind(name_id)
+            .bind(metaname)
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove every route of a name, returns how many there were.
+    pub async fn delete_for_name<E>(executor: E, name_id: i32) -> Result<u64>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "DELETE FROM name_routes WHERE name_id = $1";
+        let result = sqlx::query(q).bind(name_id).execute(executor).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// The address a payment to `metaname@name.kro` goes to.
+    ///
+    /// The route for the metaname wins, then the fallback route, then the owner of the name.
+    pub async fn resolve<E>(executor: E, name: &Name, metaname: Option<&str>) -> Result<String>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"
+        SELECT address FROM name_routes
+        WHERE name_id = $1 AND (metaname = $2 OR metaname IS NULL)
+        ORDER BY metaname IS NULL
+        LIMIT 1;
+        "#;
+
+        let address: Option<String> = sqlx::query_scalar(q)
+            .bind(name.id)
+            .bind(metaname)
+            .fetch_optional(executor)
+            .await?;
+
+        Ok(address.unwrap_or_else(|| name.owner.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::wallet::Model as Wallet;
+    use crate::websockets::WebSocketServer;
+    use sqlx::Pool;
+
+    async fn setup(pool: &Pool<Postgres>) -> Result<Name> {
+        for address in ["kowner0000", "krefunds00", "ksales0000", "kfallback0"] {
+            Wallet::create_wallet(pool, address, "hash", None).await?;
+        }
+
+        Name::create(pool, "shop".to_owned(), "kowner0000".to_owned()).await
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_resolve(pool: Pool<Postgres>) -> Result<()> {
+        let shop = setup(&pool).await?;
+
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("sales")).await?,
+            "kowner0000"
+        );
+        assert_eq!(Model::resolve(&pool, &shop, None).await?, "kowner0000");
+
+        Model::set(&pool, shop.id, Some("refunds"), "krefunds00").await?;
+        Model::set(&pool, shop.id, Some("sales"), "kowner0000").await?;
+        Model::set(&pool, shop.id, Some("sales"), "ksales0000").await?;
+        assert_eq!(Model::fetch_for_name(&pool, shop.id).await?.len(), 2);
+
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("refunds")).await?,
+            "krefunds00"
+        );
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("sales")).await?,
+            "ksales0000"
+        );
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("other")).await?,
+            "kowner0000"
+        );
+
+        Model::set(&pool, shop.id, None, "kfallback0").await?;
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("other")).await?,
+            "kfallback0"
+        );
+        assert_eq!(Model::resolve(&pool, &shop, None).await?, "kfallback0");
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("sales")).await?,
+            "ksales0000"
+        );
+
+        let routes = Model::fetch_for_name(&pool, shop.id).await?;
+        assert_eq!(routes[0].metaname, None);
+
+        assert!(Model::delete(&pool, shop.id, None).await?);
+        assert!(!Model::delete(&pool, shop.id, None).await?);
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("other")).await?,
+            "kowner0000"
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_transfer_clears_routes(pool: Pool<Postgres>) -> Result<()> {
+        let shop = setup(&pool).await?;
+        let server = WebSocketServer::new();
+
+        Model::set(&pool, shop.id, Some("sales"), "ksales0000").await?;
+        Model::set(&pool, shop.id, None, "kfallback0").await?;
+
+        let shop = shop
+            .transfer_ownership(&pool, &server, "krefunds00".to_owned())
+            .await?;
+
+        assert!(Model::fetch_for_name(&pool, shop.id).await?.is_empty());
+        assert_eq!(
+            Model::resolve(&pool, &shop, Some("sales")).await?,
+            "krefunds00"
+        );
+
+        Ok(())
+    }
+}
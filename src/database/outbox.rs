@@ -0,0 +1,246 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Acquire, Encode, Executor, Postgres, Type};
+
+use crate::database::locks::{self, AdvisoryLock};
+use crate::database::{DatabaseError, ModelExt, Result};
+use crate::models::krist::websockets::WebSocketEvent;
+
+/// Channel that carries the ids of published events to every kromer instance.
+pub const EVENT_CHANNEL: &str = "kromer_events";
+/// Channel used to wake up the dispatchers of all instances once new events were committed.
+pub const OUTBOX_CHANNEL: &str = "kromer_outbox";
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i64,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub dispatched_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM event_outbox WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM event_outbox ORDER BY id ASC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM event_outbox";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    /// Queue an event, it is sent to websocket clients once the surrounding transaction commits.
+    pub async fn enqueue<A>(conn: A, event: &WebSocketEvent) -> Result<Model>
+    where
+        A: 'q + Acquire<'q, Database = Postgres>,
+    {
+        let mut conn = conn.acquire().await?;
+        let payload =
+            serde_json::to_value(event).map_err(|err| sqlx::Error::Encode(Box::new(err)))?;
+
+        // Outbox ids are handed out on insert, but receivers treat every id at or below the last
+        // one they delivered as already seen (see `WebSocketServer::deliver_event` and the
+        // catch-up in `websockets::bus`), so an event that commits after a higher id went out
+        // would be dropped. Holding this lock until commit makes commit order match id order.
+        locks::lock_xact(&mut *conn, AdvisoryLock::Outbox).await?;
+
+        let q = "INSERT INTO event_outbox(payload) VALUES ($1) RETURNING *";
+        let model = sqlx::query_as(q)
+            .bind(payload)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        // Only delivered on commit, so dispatchers never wake up for events that were rolled back.
+        sqlx::query("SELECT pg_notify($1, '')")
+            .bind(OUTBOX_CHANNEL)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(model)
+    }
+
+    /// Try to become the instance that publishes events until the surrounding transaction ends.
+    ///
+    /// Returns `false` if another instance is already publishing.
+    pub async fn try_lock_dispatcher<E>(executor: E) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        locks::try_lock_xact(executor, AdvisoryLock::Dispatcher).await
+    }
+
+    /// Announce the event on [`EVENT_CHANNEL`] and mark it as dispatched.
+    ///
+    /// Both happen when the surrounding transaction commits, or not at all.
+    pub async fn publish<A>(conn: A, id: i64) -> Result<()>
+    where
+        A: 'q + Acquire<'q, Database = Postgres>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(EVENT_CHANNEL)
+            .bind(id.to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        Self::mark_dispatched(&mut *conn, id).await
+    }
+
+    /// Fetch events that have not been dispatched yet, oldest first.
+    pub async fn fetch_pending<E>(executor: E, limit: i64) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM event_outbox WHERE dispatched_at IS NULL ORDER BY id ASC LIMIT $1";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Fetch committed events newer than `after_id`, dispatched or not, oldest first.
+    pub async fn fetch_after<E>(executor: E, after_id: i64, limit: i64) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM event_outbox WHERE id > $1 ORDER BY id ASC LIMIT $2";
+
+        sqlx::query_as(q)
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Id of the newest committed event, or 0 if there is none.
+    pub async fn latest_id<E>(executor: E) -> Result<i64>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COALESCE(MAX(id), 0) FROM event_outbox";
+
+        sqlx::query_scalar(q)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    pub async fn mark_dispatched<E>(executor: E, id: i64) -> Result<()>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "UPDATE event_outbox SET dispatched_at = NOW() WHERE id = $1";
+
+        sqlx::query(q).bind(id).execute(executor).await?;
+
+        Ok(())
+    }
+
+    /// Delete dispatched events older than a day, returning how many were removed.
+    pub async fn prune_dispatched<E>(executor: E) -> Result<u64>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "DELETE FROM event_outbox WHERE dispatched_at < NOW() - INTERVAL '1 day'";
+        let result = sqlx::query(q).execute(executor).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Deserialize the stored payload back into the event it was created from.
+    pub fn event(&self) -> serde_json::Result<WebSocketEvent> {
+        serde_json::from_value(self.payload.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::krist::names::NameJson;
+    use sqlx::Pool;
+
+    fn name_event(name: &str) -> WebSocketEvent {
+        WebSocketEvent::Name {
+            name: NameJson {
+                name: name.to_owned(),
+                unicode_name: name.to_owned(),
+                owner: "kaaaaaaaaa".to_owned(),
+                original_owner: None,
+                registered: "2025-01-01T00:00:00+00:00".to_owned(),
+                updated: None,
+                transfered: None,
+                a: None,
+                unpaid: 0,
+                expires: "2025-02-01T00:00:00+00:00".to_owned(),
+                expired: false,
+            },
+        }
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_rolled_back_events_are_never_pending(pool: Pool<Postgres>) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        Model::enqueue(&mut *tx, &name_event("rolledback")).await?;
+        tx.rollback().await?;
+
+        let mut tx = pool.begin().await?;
+        let first = Model::enqueue(&mut *tx, &name_event("first")).await?;
+        let second = Model::enqueue(&mut *tx, &name_event("second")).await?;
+        tx.commit().await?;
+
+        let pending = Model::fetch_pending(&pool, 10).await?;
+        let ids: Vec<i64> = pending.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![first.id, second.id]);
+        assert!(first.id < second.id);
+
+        let event = pending[0].event().expect("payload should round-trip");
+        assert!(matches!(event, WebSocketEvent::Name { name } if name.name == "first"));
+
+        Model::mark_dispatched(&pool, first.id).await?;
+        let pending = Model::fetch_pending(&pool, 10).await?;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, second.id);
+
+        Ok(())
+    }
+}
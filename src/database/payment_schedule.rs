@@ -0,0 +1,247 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::database::locks::{self, AdvisoryLock};
+use crate::database::{DatabaseError, ModelExt, Result};
+use crate::errors::transaction::TransactionError;
+use crate::utils::recurrence::Recurrence;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i32,
+    pub from: String,
+    pub to: String,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+    pub cron: Option<String>,
+    pub interval_secs: Option<i64>,
+    /// When the next payment is due, `None` once the schedule finished or was cancelled.
+    pub next_run_at: Option<DateTime<Utc>>,
+    /// No payments are due after this.
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleCreateData {
+    pub from: String,
+    pub to: String,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+    pub cron: Option<String>,
+    pub interval_secs: Option<i64>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM payment_schedules WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM payment_schedules ORDER BY id DESC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM payment_schedules";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    /// Store a schedule whose first payment is due at `next_run_at`.
+    ///
+    /// A first payment after `ends_at` leaves the schedule finished straight away.
+    pub async fn create<E>(
+        executor: E,
+        data: ScheduleCreateData,
+        next_run_at: DateTime<Utc>,
+    ) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let next_run_at =
+            Some(next_run_at).filter(|next| data.ends_at.is_none_or(|ends_at| *next <= ends_at));
+        let q = r#"
+        INSERT INTO payment_schedules("from", "to", amount, metadata, cron, interval_secs, next_run_at, ends_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#;
+
+        sqlx::query_as(q)
+            .bind(data.from)
+            .bind(data.to)
+            .bind(data.amount)
+            .bind(data.metadata)
+            .bind(data.cron)
+            .bind(data.interval_secs)
+            .bind(next_run_at)
+            .bind(data.ends_at)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    pub fn recurrence(&self) -> Option<Recurrence> {
+        Recurrence::from_parts(self.cron.as_deref(), self.interval_secs)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.next_run_at.is_some()
+    }
+
+    /// The schedules an address pays from, newest first.
+    pub async fn fetch_for_address<E>(
+        executor: E,
+        address: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = r#"SELECT * FROM payment_schedules WHERE "from" = $1 ORDER BY id DESC LIMIT $2 OFFSET $3"#;
+
+        sqlx::query_as(q)
+            .bind(address)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    pub async fn count_for_address<E>(executor: E, address: &str) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"SELECT COUNT(*) FROM payment_schedules WHERE "from" = $1"#;
+        let result: i64 = sqlx::query_scalar(q)
+            .bind(address)
+            .fetch_one(executor)
+            .await?;
+
+        Ok(result as usize)
+    }
+
+    pub async fn count_active_for_address<E>(executor: E, address: &str) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"SELECT COUNT(*) FROM payment_schedules WHERE "from" = $1 AND next_run_at IS NOT NULL"#;
+        let result: i64 = sqlx::query_scalar(q)
+            .bind(address)
+            .fetch_one(executor)
+            .await?;
+
+        Ok(result as usize)
+    }
+
+    /// Stop the schedule, no further payments are made.
+    pub async fn cancel<E>(self, executor: E) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "UPDATE payment_schedules SET next_run_at = NULL, cancelled_at = NOW() WHERE id = $1 AND next_run_at IS NOT NULL RETURNING *";
+
+        let model: Option<Model> = sqlx::query_as(q)
+            .bind(self.id)
+            .fetch_optional(executor)
+            .await?;
+
+        model.ok_or(DatabaseError::Transaction(
+            TransactionError::ScheduleInactive(self.id),
+        ))
+    }
+
+    /// Take the scheduled payments worker lock for the current transaction, if no other instance
+    /// holds it.
+    pub async fn try_lock_worker<E>(executor: E) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        locks::try_lock_xact(executor, AdvisoryLock::PaymentsWorker).await
+    }
+
+    /// Schedules with a payment due at `now`, longest overdue first.
+    pub async fn fetch_due<E>(executor: E, now: DateTime<Utc>, limit: i64) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM payment_schedules WHERE next_run_at <= $1 ORDER BY next_run_at, id LIMIT $2";
+
+        sqlx::query_as(q)
+            .bind(now)
+            .bind(limit)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Locks schedule `id` with `FOR UPDATE` until the surrounding transaction ends, if it still
+    /// has a payment due at `now`.
+    pub async fn lock_if_due<E>(executor: E, id: i32, now: DateTime<Utc>) -> Result<Option<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM payment_schedules WHERE id = $1 AND next_run_at <= $2 FOR UPDATE";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .bind(now)
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Move on to the payment due at `next_run_at`, or finish the schedule with `None` or a time
+    /// past its end.
+    pub async fn advance<E>(self, executor: E, next_run_at: Option<DateTime<Utc>>) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let next_run_at = next_run_at
+            .filter(|next_run_at| self.ends_at.is_none_or(|ends_at| *next_run_at <= ends_at));
+        let q = "UPDATE payment_schedules SET next_run_at = $2 WHERE id = $1 RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(self.id)
+            .bind(next_run_at)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
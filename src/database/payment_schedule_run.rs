@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::database::{DatabaseError, ModelExt, Result};
+
+/// A payment a schedule attempted.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Model {
+    pub id: i32,
+    pub schedule_id: i32,
+    pub due_at: DateTime<Utc>,
+    /// The transaction made, `None` if the payment failed.
+    pub transaction_id: Option<i32>,
+    /// Why the payment failed.
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM payment_schedule_runs WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM payment_schedule_runs ORDER BY id DESC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM payment_schedule_runs";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    /// Record the outcome of the payment due at `due_at`, the transaction made or the error.
+    pub async fn create<E>(
+        executor: E,
+        schedule_id: i32,
+        due_at: DateTime<Utc>,
+        outcome: std::result::Result<i32, String>,
+    ) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let (transaction_id, error) = match outcome {
+            Ok(transaction_id) => (Some(transaction_id), None),
+            Err(error) => (None, Some(error)),
+        };
+        let q = "INSERT INTO payment_schedule_runs(schedule_id, due_at, transaction_id, error) VALUES ($1, $2, $3, $4) RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(schedule_id)
+            .bind(due_at)
+            .bind(transaction_id)
+            .bind(error)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// The latest payments of a schedule, newest first.
+    pub async fn fetch_for_schedule<E>(
+        executor: E,
+        schedule_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM payment_schedule_runs WHERE schedule_id = $1 ORDER BY id DESC LIMIT $2 OFFSET $3";
+
+        sqlx::query_as(q)
+            .bind(schedule_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
@@ -0,0 +1,168 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::database::{DatabaseError, ModelExt, Result};
+
+/// Like a name, but may also contain the `*` and `?` wildcards.
+static PATTERN_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z0-9_\-*?]{1,64}$").unwrap());
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub struct Model {
+    pub id: i32,
+    pub pattern: String,
+    #[serde(skip)]
+    pub like_pattern: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM reserved_names WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM reserved_names ORDER BY pattern ASC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM reserved_names";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    /// Whether `pattern` is something [`Model::create`] accepts.
+    pub fn is_valid_pattern(pattern: &str) -> bool {
+        PATTERN_RE.is_match(pattern)
+    }
+
+    /// Translate a `*`/`?` wildcard pattern into a `LIKE` pattern, escaping everything else.
+    pub fn to_like_pattern(pattern: &str) -> String {
+        let mut like_pattern = String::with_capacity(pattern.len());
+        for c in pattern.chars() {
+            match c {
+                '*' => like_pattern.push('%'),
+                '?' => like_pattern.push('_'),
+                '%' | '_' | '\\' => {
+                    like_pattern.push('\\');
+                    like_pattern.push(c);
+                }
+                c => like_pattern.push(c),
+            }
+        }
+
+        like_pattern
+    }
+
+    pub async fn create<E>(executor: E, pattern: &str, reason: Option<&str>) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "INSERT INTO reserved_names(pattern, like_pattern, reason) VALUES ($1, $2, $3) RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(pattern)
+            .bind(Self::to_like_pattern(pattern))
+            .bind(reason)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Remove a reserved pattern, returns whether it existed.
+    pub async fn delete<E>(executor: E, id: i32) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "DELETE FROM reserved_names WHERE id = $1";
+        let result = sqlx::query(q).bind(id).execute(executor).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The first pattern reserving `name`, if any.
+    pub async fn find_match<E>(executor: E, name: &str) -> Result<Option<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r"SELECT * FROM reserved_names WHERE $1 LIKE like_pattern ESCAPE '\' ORDER BY id LIMIT 1";
+
+        sqlx::query_as(q)
+            .bind(name)
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Pool;
+
+    #[test]
+    fn test_patterns() {
+        assert!(Model::is_valid_pattern("admin*"));
+        assert!(Model::is_valid_pattern("sh?p"));
+        assert!(!Model::is_valid_pattern("Admin"));
+        assert!(!Model::is_valid_pattern("a%"));
+        assert!(!Model::is_valid_pattern(""));
+
+        assert_eq!(Model::to_like_pattern("admin*"), "admin%");
+        assert_eq!(Model::to_like_pattern("my_sh?p"), r"my\_sh_p");
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_find_match(pool: Pool<Postgres>) -> Result<()> {
+        Model::create(&pool, "shop", None).await?;
+        Model::create(&pool, "admin*", Some("impersonation")).await?;
+        Model::create(&pool, "a_b", None).await?;
+
+        assert!(Model::find_match(&pool, "shop").await?.is_some());
+        assert!(Model::find_match(&pool, "shops").await?.is_none());
+
+        let matched = Model::find_match(&pool, "administrator").await?.unwrap();
+        assert_eq!(matched.reason.as_deref(), Some("impersonation"));
+        assert!(Model::find_match(&pool, "myadmin").await?.is_none());
+
+        // `_` is a plain character in patterns, not a wildcard.
+        assert!(Model::find_match(&pool, "a_b").await?.is_some());
+        assert!(Model::find_match(&pool, "axb").await?.is_none());
+
+        Ok(())
+    }
+}
@@ -1,19 +1,30 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::TryStreamExt;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::{Acquire, Encode, Executor, Pool, Postgres, Type};
+use sqlx::{Acquire, Encode, Executor, PgConnection, Pool, Postgres, Type};
 
+use crate::config::get_config;
+use crate::database::locks::{self, AdvisoryLock};
 use crate::database::{DatabaseError, Result};
 use crate::{database::ModelExt, routes::PaginationParams};
 
+use crate::database::name::{self, Model as Name};
+use crate::database::name_route::Model as NameRoute;
 use crate::database::wallet::Model as Wallet;
+use crate::errors::krist::generic::GenericError;
+use crate::errors::name::NameError;
+use crate::errors::transaction::TransactionError;
 use crate::errors::wallet::WalletError;
+use crate::models::krist::webserver::lookup::{LookupParams, TransactionLookupFields};
+use crate::utils::common_meta::{CommonMeta, MetaRecipient};
+use crate::utils::{crypto, validation};
 
 static KRO_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([a-z0-9]{1,64})\.kro").unwrap());
+    Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([^\s@.]{1,64})\.kro").unwrap());
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
 pub struct Model {
@@ -27,6 +38,53 @@ pub struct Model {
     pub sent_name: Option<String>,
     pub transaction_type: TransactionType,
     pub date: DateTime<Utc>,
+    pub idempotency_key: Option<String>,
+    pub request_hash: Option<String>,
+    /// The transaction this one refunds or reverses.
+    pub original_id: Option<i32>,
+    /// The hash of the transaction before this one in the chain.
+    pub prev_hash: Option<String>,
+    /// See [`Model::chain_hash`], `None` only if the row was never linked into the chain.
+    pub hash: Option<String>,
+}
+
+/// The `prev_hash` of the first transaction in the chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The newest transaction in the chain, which outside observers can pin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChainHead {
+    pub id: i32,
+    pub hash: String,
+}
+
+/// Why a link in the chain is broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainBreak {
+    /// The row was never hashed, it was inserted behind the server's back.
+    Unhashed,
+    /// The row doesn't point at the row before it, so something in between was removed.
+    PrevHashMismatch,
+    /// The row was changed after it was hashed.
+    HashMismatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokenLink {
+    pub id: i32,
+    pub reason: ChainBreak,
+}
+
+/// The result of walking the chain from the start.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainVerification {
+    /// How many transactions were found intact.
+    pub checked: usize,
+    /// The last intact transaction.
+    pub head: Option<ChainHead>,
+    /// The first broken link, everything from here on can't be trusted.
+    pub broken: Option<BrokenLink>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, PartialOrd, Serialize, Deserialize, sqlx::Type)]
@@ -40,6 +98,36 @@ pub enum TransactionType {
     NameARecord,
     NameTransfer,
     Transfer,
+    NameRenewal,
+    NameExpiry,
+    NameRelease,
+    Refund,
+    Reversal,
+    Adjustment,
+}
+
+/// One transfer of a batch, see [`Model::create_batch`].
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct BatchTransfer {
+    /// An address or `meta@name.kro`.
+    pub to: String,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+}
+
+/// The transactions a batch made, in the order of the transfers, and any refunds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchResult {
+    pub transactions: Vec<Model>,
+    pub refunds: Vec<Model>,
+}
+
+/// Where a refund goes, see [`Model::refund`].
+#[derive(Debug, Clone, PartialEq)]
+struct RefundRecipient {
+    address: String,
+    sent_metaname: Option<String>,
+    sent_name: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -52,6 +140,19 @@ pub struct TransactionCreateData {
     pub sent_metaname: Option<String>,
     pub sent_name: Option<String>,
     pub transaction_type: TransactionType,
+    pub idempotency_key: Option<String>,
+    pub request_hash: Option<String>,
+    pub original_id: Option<i32>,
+}
+
+/// What to do when reversing a transaction whose recipient already spent (some of) the funds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReversalPolicy {
+    /// Only take back what the recipient still has.
+    Partial,
+    /// Take back the full amount, leaving the recipient with a negative balance if need be.
+    Debt,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -68,6 +169,12 @@ impl From<String> for TransactionType {
             "name_a_record" => TransactionType::NameARecord,
             "name_transfer" => TransactionType::NameTransfer,
             "transfer" => TransactionType::Transfer,
+            "name_renewal" => TransactionType::NameRenewal,
+            "name_expiry" => TransactionType::NameExpiry,
+            "name_release" => TransactionType::NameRelease,
+            "refund" => TransactionType::Refund,
+            "reversal" => TransactionType::Reversal,
+            "adjustment" => TransactionType::Adjustment,
             _ => TransactionType::Unknown,
         }
     }
@@ -82,6 +189,12 @@ impl From<TransactionType> for &str {
             TransactionType::NameARecord => "name_a_record",
             TransactionType::NameTransfer => "name_transfer",
             TransactionType::Transfer => "transfer",
+            TransactionType::NameRenewal => "name_renewal",
+            TransactionType::NameExpiry => "name_expiry",
+            TransactionType::NameRelease => "name_release",
+            TransactionType::Refund => "refund",
+            TransactionType::Reversal => "reversal",
+            TransactionType::Adjustment => "adjustment",
         }
     }
 }
@@ -154,57 +267,225 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
-    pub async fn create_no_update<E>(
-        executor: E,
-        creation_data: TransactionCreateData,
-    ) -> Result<Model>
+    pub async fn create_no_update<A>(conn: A, creation_data: TransactionCreateData) -> Result<Model>
     where
-        E: 'q + Executor<'q, Database = Postgres>,
+        A: Acquire<'q, Database = Postgres>,
     {
-        let metadata = creation_data.metadata.unwrap_or_default();
-        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date) VALUES ($1, $2, $3, $4, $5, NOW()) RETURNING *"#;
+        let mut tx = conn.begin().await?;
+        let model = Self::insert(&mut tx, creation_data).await?;
+        tx.commit().await?;
 
-        sqlx::query_as(q)
-            .bind(creation_data.amount)
-            .bind(&creation_data.from)
-            .bind(&creation_data.to)
-            .bind(metadata)
-            .bind(creation_data.transaction_type)
-            .fetch_one(executor)
-            .await
-            .map_err(DatabaseError::Sqlx)
+        Ok(model)
     }
 
     pub async fn create<A>(conn: A, creation_data: TransactionCreateData) -> Result<Model>
     where
         A: Acquire<'q, Database = Postgres>,
     {
-        let metadata = creation_data.metadata.unwrap_or_default();
-
         let mut tx = conn.begin().await?;
 
-        let sender = Wallet::fetch_by_address(&mut *tx, &creation_data.from)
-            .await?
+        // Lock both rows up front, the balance check below is only meaningful while we hold them.
+        let wallets =
+            Wallet::lock_for_update(&mut *tx, &[&creation_data.from, &creation_data.to]).await?;
+
+        let sender = wallets
+            .iter()
+            .find(|wallet| wallet.address == creation_data.from)
             .ok_or_else(|| {
                 DatabaseError::Wallet(WalletError::NotFound(creation_data.from.clone()))
             })?;
 
-        let recipient = Wallet::fetch_by_address(&mut *tx, &creation_data.to)
-            .await?
+        let recipient = wallets
+            .iter()
+            .find(|wallet| wallet.address == creation_data.to)
             .ok_or_else(|| {
                 DatabaseError::Wallet(WalletError::NotFound(creation_data.to.clone()))
             })?;
 
-        let _ = sender
-            .update_balance(&mut *tx, -creation_data.amount)
-            .await?;
+        // A locked wallet can't spend, register names or be handed one.
+        if sender.locked {
+            return Err(DatabaseError::Wallet(WalletError::Locked(
+                sender.address.clone(),
+            )));
+        }
+        if recipient.locked && creation_data.transaction_type == TransactionType::NameTransfer {
+            return Err(DatabaseError::Wallet(WalletError::Locked(
+                recipient.address.clone(),
+            )));
+        }
+
+        let _ = sender.debit(&mut *tx, creation_data.amount).await?;
         let _ = recipient
             .update_balance(&mut *tx, creation_data.amount)
             .await?;
 
-        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date, name, sent_metaname, sent_name) VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7, $8) RETURNING *"#;
+        let model = Self::insert(&mut tx, creation_data).await?;
+        tx.commit().await?;
+
+        Ok(model)
+    }
+
+    /// Send every transfer in `transfers` from `from`, or none of them.
+    ///
+    /// Recipients can be addresses or `meta@name.kro`, and are resolved like a single transfer.
+    /// The sender's balance is checked once against the total. Anything wrong with a single
+    /// transfer is reported as [`DatabaseError::BatchTransfer`] with its index. Transfers to a
+    /// metaname the name doesn't accept are refunded straight away, like single transfers.
+    pub async fn create_batch<A>(
+        conn: A,
+        from: &str,
+        transfers: &[BatchTransfer],
+    ) -> Result<BatchResult>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let at = |index: usize| move |error| DatabaseError::BatchTransfer(index, Box::new(error));
+        let invalid = |index: usize, parameter: &str| {
+            at(index)(DatabaseError::Generic(GenericError::InvalidParameter(
+                parameter.to_owned(),
+            )))
+        };
+
+        if transfers.is_empty() || transfers.len() > get_config().limits.max_batch_transfers {
+            return Err(DatabaseError::Generic(GenericError::InvalidParameter(
+                "transfers".to_owned(),
+            )));
+        }
+
+        let mut tx = conn.begin().await?;
+
+        let mut resolved = Vec::with_capacity(transfers.len());
+        for (index, transfer) in transfers.iter().enumerate() {
+            if transfer.to.is_empty() {
+                return Err(invalid(index, "to"));
+            }
+            let amount = transfer.amount.round_dp(2);
+            if amount <= Decimal::ZERO {
+                return Err(invalid(index, "amount"));
+            }
+            if transfer
+                .metadata
+                .as_deref()
+                .is_some_and(|metadata| !validation::is_valid_metadata(metadata))
+            {
+                return Err(invalid(index, "metadata"));
+            }
+
+            let is_name = validation::NAME_META_RE.is_match(&transfer.to);
+            let name_data = is_name.then(|| TransactionNameData::parse(&transfer.to));
+            let (sent_metaname, sent_name) = match name_data {
+                Some(name_data) => (name_data.metaname, name_data.name),
+                None => (None, None),
+            };
+
+            let (to, auto_refund) = match &sent_name {
+                Some(sent_name) => {
+                    let name = Name::fetch_by_name(&mut *tx, sent_name)
+                        .await?
+                        .ok_or_else(|| NameError::NameNotFound(transfer.to.clone()))
+                        .map_err(|error| at(index)(error.into()))?;
+                    let address =
+                        NameRoute::resolve(&mut *tx, &name, sent_metaname.as_deref()).await?;
+
+                    (address, name.should_refund(sent_metaname.as_deref()))
+                }
+                None => (transfer.to.clone(), false),
+            };
+
+            if to == from {
+                return Err(at(index)(DatabaseError::Transaction(
+                    TransactionError::SameWalletTransfer,
+                )));
+            }
+
+            let creation_data = TransactionCreateData {
+                from: from.to_owned(),
+                to,
+                amount,
+                metadata: transfer.metadata.clone(),
+                sent_metaname,
+                sent_name,
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            resolved.push((creation_data, auto_refund));
+        }
+
+        // Refunds go out after their transfer was inserted, so their recipients are locked now.
+        let mut refund_addresses = Vec::new();
+        for (data, auto_refund) in &resolved {
+            if *auto_refund {
+                let refund =
+                    Self::refund_recipient(&mut tx, data.metadata.as_deref(), from, &data.to)
+                        .await?;
+                refund_addresses.push(refund.address);
+            }
+        }
+
+        let mut addresses: Vec<&str> = vec![from];
+        addresses.extend(resolved.iter().map(|(data, _)| data.to.as_str()));
+        addresses.extend(refund_addresses.iter().map(String::as_str));
+        let wallets = Wallet::lock_for_update(&mut *tx, &addresses).await?;
+
+        let sender = wallets
+            .iter()
+            .find(|wallet| wallet.address == from)
+            .ok_or_else(|| DatabaseError::Wallet(WalletError::NotFound(from.to_owned())))?;
+        if sender.locked {
+            return Err(DatabaseError::Wallet(WalletError::Locked(
+                sender.address.clone(),
+            )));
+        }
+
+        let mut recipients = Vec::with_capacity(resolved.len());
+        for (index, (data, _)) in resolved.iter().enumerate() {
+            let recipient = wallets
+                .iter()
+                .find(|wallet| wallet.address == data.to)
+                .ok_or_else(|| WalletError::NotFound(data.to.clone()))
+                .map_err(|error| at(index)(error.into()))?;
+            recipients.push(recipient);
+        }
+
+        let total: Decimal = resolved.iter().map(|(data, _)| data.amount).sum();
+        sender.debit(&mut *tx, total).await?;
+
+        let mut result = BatchResult::default();
+        for ((creation_data, auto_refund), recipient) in resolved.into_iter().zip(recipients) {
+            recipient
+                .update_balance(&mut *tx, creation_data.amount)
+                .await?;
+
+            let transaction = Self::insert(&mut tx, creation_data).await?;
+            if auto_refund {
+                let refund = Self::refund(
+                    &mut *tx,
+                    transaction.id,
+                    transaction.amount,
+                    Some(name::AUTO_REFUND_MESSAGE),
+                )
+                .await?;
+                result.refunds.push(refund);
+            }
+            result.transactions.push(transaction);
+        }
 
-        let model = sqlx::query_as(q)
+        tx.commit().await?;
+
+        Ok(result)
+    }
+
+    /// Append a transaction to the chain, the caller has already moved the funds.
+    async fn insert(
+        conn: &mut PgConnection,
+        creation_data: TransactionCreateData,
+    ) -> Result<Model> {
+        let metadata = creation_data.metadata.unwrap_or_default();
+
+        let prev_hash = Self::lock_chain_head(&mut *conn).await?;
+        let q = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date, name, sent_metaname, sent_name, idempotency_key, request_hash, original_id, prev_hash) VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7, $8, $9, $10, $11, $12) RETURNING *"#;
+
+        let model: Model = sqlx::query_as(q)
             .bind(creation_data.amount)
             .bind(&creation_data.from)
             .bind(&creation_data.to)
@@ -213,11 +494,504 @@ impl<'q> Model {
             .bind(creation_data.name)
             .bind(creation_data.sent_metaname)
             .bind(creation_data.sent_name)
-            .fetch_one(&mut *tx)
+            .bind(creation_data.idempotency_key)
+            .bind(creation_data.request_hash)
+            .bind(creation_data.original_id)
+            .bind(prev_hash)
+            .fetch_one(&mut *conn)
             .await?;
-        tx.commit().await?; // I'm not sure this is how it should be done? `Wallet::update_balance` also creates a transaction..
 
-        Ok(model)
+        model.seal(conn).await
+    }
+
+    /// The hash of this transaction, linking it to the one before it.
+    ///
+    /// It is the hex SHA-256 of a JSON array of the id, amount (without trailing zeros), sender,
+    /// recipient, metadata, name, sent metaname, sent name, type, date (RFC 3339 in UTC with
+    /// microseconds), original id and `prev_hash`.
+    pub fn chain_hash(&self) -> String {
+        let canonical = serde_json::json!([
+            self.id,
+            self.amount.normalize().to_string(),
+            self.from,
+            self.to,
+            self.metadata,
+            self.name,
+            self.sent_metaname,
+            self.sent_name,
+            <&str>::from(self.transaction_type.clone()),
+            self.date.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.original_id,
+            self.prev_hash,
+        ]);
+
+        crypto::sha256(&canonical.to_string())
+    }
+
+    /// Take the chain lock and return the hash the next transaction has to link to.
+    ///
+    /// Holding it until commit serializes inserts, so every transaction links to the one
+    /// committed right before it and the chain follows id order. Every wallet the caller touches
+    /// has to be locked before this, see [`crate::database::locks`].
+    async fn lock_chain_head(conn: &mut PgConnection) -> Result<String> {
+        locks::lock_xact(&mut *conn, AdvisoryLock::Chain).await?;
+
+        let head = Self::chain_head(&mut *conn).await?;
+
+        Ok(head.map_or_else(|| GENESIS_HASH.to_owned(), |head| head.hash))
+    }
+
+    /// Store the hash of a freshly inserted row, whose `prev_hash` is already set.
+    async fn seal(mut self, conn: &mut PgConnection) -> Result<Model> {
+        let hash = self.chain_hash();
+
+        sqlx::query("UPDATE transactions SET hash = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(&hash)
+            .execute(conn)
+            .await?;
+        self.hash = Some(hash);
+
+        Ok(self)
+    }
+
+    /// The newest transaction in the chain.
+    pub async fn chain_head<E>(executor: E) -> Result<Option<ChainHead>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT id, hash FROM transactions WHERE hash IS NOT NULL ORDER BY id DESC LIMIT 1";
+
+        sqlx::query_as(q)
+            .fetch_optional(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Link every transaction into the chain, if the chain hasn't been started yet.
+    ///
+    /// This is for transactions made before the chain existed, it does nothing once any row has
+    /// a hash, so rows slipped in later stay unhashed and show up in [`Model::verify_chain`].
+    /// Returns how many transactions were linked.
+    pub async fn chain_existing(pool: &Pool<Postgres>) -> Result<u64> {
+        const BATCH_SIZE: i64 = 1000;
+
+        let mut tx = pool.begin().await?;
+        let mut prev_hash = Self::lock_chain_head(&mut tx).await?;
+        if prev_hash != GENESIS_HASH {
+            return Ok(0);
+        }
+
+        let mut linked = 0;
+        let mut last_id = 0;
+        loop {
+            let q = "SELECT * FROM transactions WHERE id > $1 ORDER BY id LIMIT $2";
+            let batch: Vec<Model> = sqlx::query_as(q)
+                .bind(last_id)
+                .bind(BATCH_SIZE)
+                .fetch_all(&mut *tx)
+                .await?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for mut model in batch {
+                last_id = model.id;
+                model.prev_hash = Some(prev_hash);
+                let hash = model.chain_hash();
+
+                sqlx::query("UPDATE transactions SET prev_hash = $2, hash = $3 WHERE id = $1")
+                    .bind(model.id)
+                    .bind(&model.prev_hash)
+                    .bind(&hash)
+                    .execute(&mut *tx)
+                    .await?;
+                prev_hash = hash;
+                linked += 1;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(linked)
+    }
+
+    /// Walk the chain from the first transaction and stop at the first broken link.
+    pub async fn verify_chain<E>(executor: E) -> Result<ChainVerification>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let mut verification = ChainVerification::default();
+        let mut prev_hash = GENESIS_HASH.to_owned();
+
+        let mut rows =
+            sqlx::query_as::<_, Model>("SELECT * FROM transactions ORDER BY id").fetch(executor);
+        while let Some(model) = rows.try_next().await? {
+            let reason = match &model.hash {
+                None => Some(ChainBreak::Unhashed),
+                Some(_) if model.prev_hash.as_deref() != Some(prev_hash.as_str()) => {
+                    Some(ChainBreak::PrevHashMismatch)
+                }
+                Some(hash) if *hash != model.chain_hash() => Some(ChainBreak::HashMismatch),
+                Some(_) => None,
+            };
+
+            if let Some(reason) = reason {
+                verification.broken = Some(BrokenLink {
+                    id: model.id,
+                    reason,
+                });
+                break;
+            }
+
+            prev_hash = model.hash.clone().expect("hash was checked above");
+            verification.checked += 1;
+            verification.head = Some(ChainHead {
+                id: model.id,
+                hash: prev_hash.clone(),
+            });
+        }
+
+        Ok(verification)
+    }
+
+    /// Looks up an earlier transaction made by `from` with the same idempotency key.
+    ///
+    /// The key is locked first, so concurrent retries with the same key are serialized for as
+    /// long as the caller's transaction is open. Returns an error if the key was used for a request
+    /// with a different body.
+    pub async fn find_replay<A>(
+        conn: A,
+        from: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+    ) -> Result<Option<Model>>
+    where
+        A: 'q + Acquire<'q, Database = Postgres>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        // Not the sender row: the wallets of the transfer are locked together later on.
+        locks::lock_idempotency_key(&mut *conn, from, idempotency_key).await?;
+
+        let q = r#"SELECT * FROM transactions WHERE "from" = $1 AND idempotency_key = $2"#;
+        let existing: Option<Model> = sqlx::query_as(q)
+            .bind(from)
+            .bind(idempotency_key)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+        match existing {
+            Some(model) if model.request_hash.as_deref() != Some(request_hash) => {
+                Err(DatabaseError::Transaction(
+                    TransactionError::IdempotencyKeyReused(idempotency_key.to_owned()),
+                ))
+            }
+            existing => Ok(existing),
+        }
+    }
+
+    /// How much of transaction `id` has been refunded so far.
+    pub async fn refunded_amount<E>(executor: E, id: i32) -> Result<Decimal>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE original_id = $1 AND transaction_type = 'refund'";
+
+        sqlx::query_scalar(q)
+            .bind(id)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Credit `amount` to `wallet` from nowhere, recorded as a welfare (`mined`) transaction so
+    /// the ledger explains the new balance.
+    pub async fn grant<A>(conn: A, wallet: &Wallet, amount: Decimal) -> Result<(Wallet, Model)>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        let updated_wallet = wallet.update_balance(&mut *tx, amount).await?;
+        let creation_data = TransactionCreateData {
+            from: name::NAME_LEDGER_ADDRESS.to_owned(),
+            to: wallet.address.clone(),
+            amount,
+            transaction_type: TransactionType::Mined,
+            ..Default::default()
+        };
+        let transaction = Self::create_no_update(&mut *tx, creation_data).await?;
+
+        tx.commit().await?;
+
+        Ok((updated_wallet, transaction))
+    }
+
+    /// Whether transaction `id` has been reversed.
+    pub async fn is_reversed<E>(executor: E, id: i32) -> Result<bool>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT EXISTS(SELECT 1 FROM transactions WHERE original_id = $1 AND transaction_type = 'reversal')";
+
+        sqlx::query_scalar(q)
+            .bind(id)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Send `amount` of a transfer back, recorded as a `refund` transaction pointing at it.
+    ///
+    /// The refund goes to the `return` field of the original's CommonMeta if that is a wallet
+    /// that exists, otherwise to the sender. `message` must not contain `;`.
+    pub async fn refund<A>(
+        conn: A,
+        original_id: i32,
+        amount: Decimal,
+        message: Option<&str>,
+    ) -> Result<Model>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        // Lock the original so concurrent refunds can't both pass the check below.
+        let q = "SELECT * FROM transactions WHERE id = $1 FOR UPDATE";
+        let original: Model = sqlx::query_as(q)
+            .bind(original_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(DatabaseError::Transaction(TransactionError::NotFound))?;
+
+        let sender = match (&original.transaction_type, &original.from) {
+            (TransactionType::Transfer, Some(from)) => from.clone(),
+            _ => {
+                return Err(DatabaseError::Transaction(TransactionError::NotRefundable(
+                    original.id,
+                )));
+            }
+        };
+
+        // A reversal already sent everything back that was left.
+        if Self::is_reversed(&mut *tx, original.id).await? {
+            return Err(DatabaseError::Transaction(
+                TransactionError::AlreadyReversed(original.id),
+            ));
+        }
+
+        let refunded = Self::refunded_amount(&mut *tx, original.id).await?;
+        if amount > original.amount - refunded {
+            return Err(DatabaseError::Transaction(
+                TransactionError::RefundTooLarge(original.id),
+            ));
+        }
+
+        let recipient =
+            Self::refund_recipient(&mut tx, original.metadata.as_deref(), &sender, &original.to)
+                .await?;
+
+        let mut metadata = CommonMeta::new();
+        metadata.set("ref", &original.id.to_string());
+        metadata.set("type", "refund");
+        if let Some(message) = message {
+            metadata.set("message", message);
+        }
+
+        let creation_data = TransactionCreateData {
+            from: original.to.clone(),
+            to: recipient.address,
+            amount,
+            metadata: Some(metadata.to_string()),
+            sent_metaname: recipient.sent_metaname,
+            sent_name: recipient.sent_name,
+            transaction_type: TransactionType::Refund,
+            original_id: Some(original.id),
+            ..Default::default()
+        };
+        let refund = Self::create(&mut *tx, creation_data).await?;
+
+        tx.commit().await?;
+
+        Ok(refund)
+    }
+
+    /// Where a refund of a transfer from `sender` to `recipient` with `metadata` goes.
+    ///
+    /// That is the `return` field of the CommonMeta if it is a wallet that exists, otherwise the
+    /// sender.
+    async fn refund_recipient(
+        conn: &mut PgConnection,
+        metadata: Option<&str>,
+        sender: &str,
+        recipient: &str,
+    ) -> Result<RefundRecipient> {
+        let return_to = metadata.and_then(|metadata| CommonMeta::parse(metadata).return_to());
+        let (mut sent_metaname, mut sent_name) = (None, None);
+        let return_address = match return_to {
+            Some(MetaRecipient::Address(address)) => Some(address),
+            Some(MetaRecipient::Name { metaname, name }) => {
+                match Name::fetch_by_name(&mut *conn, &name).await? {
+                    Some(model) => {
+                        let address =
+                            NameRoute::resolve(&mut *conn, &model, metaname.as_deref()).await?;
+                        (sent_metaname, sent_name) = (metaname, Some(name));
+                        Some(address)
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
+        let address = match return_address {
+            Some(address)
+                if address != recipient
+                    && Wallet::fetch_by_address(&mut *conn, &address)
+                        .await?
+                        .is_some() =>
+            {
+                address
+            }
+            _ => {
+                (sent_metaname, sent_name) = (None, None);
+                sender.to_owned()
+            }
+        };
+
+        Ok(RefundRecipient {
+            address,
+            sent_metaname,
+            sent_name,
+        })
+    }
+
+    /// Lock the wallets of a transfer that is refunded right after it is made, including the one
+    /// the refund goes to.
+    ///
+    /// [`Model::refund`] would otherwise lock that wallet after the transfer was inserted, against
+    /// the lock order in [`crate::database::locks`].
+    pub async fn lock_for_refund<A>(conn: A, creation_data: &TransactionCreateData) -> Result<()>
+    where
+        A: 'q + Acquire<'q, Database = Postgres>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        let refund = Self::refund_recipient(
+            &mut conn,
+            creation_data.metadata.as_deref(),
+            &creation_data.from,
+            &creation_data.to,
+        )
+        .await?;
+        Wallet::lock_for_update(
+            &mut *conn,
+            &[&creation_data.from, &creation_data.to, &refund.address],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Undo a transaction, recorded as a `reversal` transaction pointing at it.
+    ///
+    /// The recipient's `total_in` and the sender's `total_out` are wound back rather than counting
+    /// the reversal as a new payment. Welfare (`mined`) transactions never debited anyone, so
+    /// reversing them only takes the funds back from the recipient. `reason` must not contain `;`.
+    pub async fn reverse<A>(
+        conn: A,
+        original_id: i32,
+        policy: ReversalPolicy,
+        reason: Option<&str>,
+    ) -> Result<Model>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        // Lock the original so two staff members can't reverse it at the same time.
+        let q = "SELECT * FROM transactions WHERE id = $1 FOR UPDATE";
+        let original: Model = sqlx::query_as(q)
+            .bind(original_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(DatabaseError::Transaction(TransactionError::NotFound))?;
+
+        let credits_sender = match original.transaction_type {
+            TransactionType::Transfer | TransactionType::Refund => true,
+            TransactionType::Mined => false,
+            _ => {
+                return Err(DatabaseError::Transaction(TransactionError::NotReversible(
+                    original.id,
+                )));
+            }
+        };
+        let sender = original.from.clone().ok_or(DatabaseError::Transaction(
+            TransactionError::NotReversible(original.id),
+        ))?;
+
+        if Self::is_reversed(&mut *tx, original.id).await? {
+            return Err(DatabaseError::Transaction(
+                TransactionError::AlreadyReversed(original.id),
+            ));
+        }
+
+        // Whatever was refunded already went back, only the rest can be reversed.
+        let remaining = original.amount - Self::refunded_amount(&mut *tx, original.id).await?;
+        if remaining <= Decimal::ZERO {
+            return Err(DatabaseError::Transaction(
+                TransactionError::AlreadyRefunded(original.id),
+            ));
+        }
+
+        let wallets = Wallet::lock_for_update(&mut *tx, &[&original.to, &sender]).await?;
+        let find_wallet = |address: &str| {
+            wallets
+                .iter()
+                .find(|wallet| wallet.address == address)
+                .ok_or_else(|| DatabaseError::Wallet(WalletError::NotFound(address.to_owned())))
+        };
+
+        let recipient = find_wallet(&original.to)?;
+        let amount = match policy {
+            ReversalPolicy::Partial => remaining.min(recipient.balance),
+            ReversalPolicy::Debt => remaining,
+        };
+        if amount <= Decimal::ZERO {
+            return Err(DatabaseError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        recipient.unwind_incoming(&mut *tx, amount).await?;
+        if credits_sender {
+            find_wallet(&sender)?
+                .unwind_outgoing(&mut *tx, amount)
+                .await?;
+        }
+
+        let mut metadata = CommonMeta::new();
+        metadata.set("ref", &original.id.to_string());
+        metadata.set("type", "reversal");
+        if let Some(reason) = reason {
+            metadata.set("message", reason);
+        }
+
+        let creation_data = TransactionCreateData {
+            from: original.to.clone(),
+            to: sender,
+            amount,
+            metadata: Some(metadata.to_string()),
+            transaction_type: TransactionType::Reversal,
+            original_id: Some(original.id),
+            ..Default::default()
+        };
+        let reversal = Self::create_no_update(&mut *tx, creation_data).await?;
+
+        tx.commit().await?;
+
+        Ok(reversal)
     }
 
     // Implemented both of the "no_mined" functions here rather than simply modifying the existing total count function because I
@@ -257,7 +1031,175 @@ impl<'q> Model {
             .await
             .map_err(DatabaseError::Sqlx)
     }
-}
+
+    /// Transactions sent from or to any of `addresses`, or every transaction if none are given.
+    pub async fn lookup_addresses<E>(
+        executor: E,
+        addresses: Option<&'q [&'q str]>,
+        params: &LookupParams<TransactionLookupFields>,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let filter = address_filter(params.include_mined);
+        Self::lookup_where(executor, &filter, addresses, params).await
+    }
+
+    pub async fn count_lookup_addresses<E>(
+        executor: E,
+        addresses: Option<&'q [&'q str]>,
+        include_mined: bool,
+    ) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let filter = address_filter(include_mined);
+        Self::count_where(executor, &filter, addresses).await
+    }
+
+    /// Transactions that changed the given names: their purchase, transfers and A record updates.
+    pub async fn name_history<E>(
+        executor: E,
+        names: &'q [&'q str],
+        params: &LookupParams<TransactionLookupFields>,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        Self::lookup_where(executor, NAME_HISTORY_FILTER, Some(names), params).await
+    }
+
+    pub async fn count_name_history<E>(executor: E, names: &'q [&'q str]) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        Self::count_where(executor, NAME_HISTORY_FILTER, Some(names)).await
+    }
+
+    /// Transactions sent to the given names, e.g. to `meta@name.kro`.
+    pub async fn sent_to_name<E>(
+        executor: E,
+        names: &'q [&'q str],
+        params: &LookupParams<TransactionLookupFields>,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        Self::lookup_where(executor, SENT_TO_NAME_FILTER, Some(names), params).await
+    }
+
+    pub async fn count_sent_to_name<E>(executor: E, names: &'q [&'q str]) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        Self::count_where(executor, SENT_TO_NAME_FILTER, Some(names)).await
+    }
+
+    /// Count transactions that bought, changed, transferred or were sent to the given name.
+    pub async fn count_involving_name<E>(executor: E, name: &str) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM transactions WHERE name = $1 OR sent_name = $1";
+        let result: i64 = sqlx::query_scalar(q).bind(name).fetch_one(executor).await?;
+
+        Ok(result as usize)
+    }
+
+    /// Count transactions whose metadata contains `query`, ignoring case.
+    pub async fn count_by_metadata<E>(
+        executor: E,
+        query: &str,
+        include_mined: bool,
+    ) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = match include_mined {
+            true => r#"SELECT COUNT(*) FROM transactions WHERE metadata ILIKE $1"#,
+            false => {
+                r#"SELECT COUNT(*) FROM transactions WHERE metadata ILIKE $1 AND transaction_type != 'mined'"#
+            }
+        };
+        let pattern = format!("%{}%", escape_like(query));
+        let result: i64 = sqlx::query_scalar(q)
+            .bind(pattern)
+            .fetch_one(executor)
+            .await?;
+
+        Ok(result as usize)
+    }
+
+    /// Run a lookup query, `filter` is a trusted SQL condition on the `$1` array.
+    async fn lookup_where<E>(
+        executor: E,
+        filter: &str,
+        values: Option<&'q [&'q str]>,
+        params: &LookupParams<TransactionLookupFields>,
+    ) -> Result<Vec<Self>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let order = params.order.as_sql();
+        let q = format!(
+            "SELECT * FROM transactions WHERE {filter} ORDER BY {} {order}, id {order} LIMIT $2 OFFSET $3",
+            params.order_by.column()
+        );
+
+        sqlx::query_as(&q)
+            .bind(values)
+            .bind(params.limit)
+            .bind(params.offset)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn count_where<E>(
+        executor: E,
+        filter: &str,
+        values: Option<&'q [&'q str]>,
+    ) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = format!("SELECT COUNT(*) FROM transactions WHERE {filter}");
+        let result: i64 = sqlx::query_scalar(&q)
+            .bind(values)
+            .fetch_one(executor)
+            .await?;
+
+        Ok(result as usize)
+    }
+}
+
+const NAME_HISTORY_FILTER: &str =
+    "name = ANY($1) AND transaction_type IN ('name_purchase', 'name_a_record', 'name_transfer')";
+const SENT_TO_NAME_FILTER: &str = "sent_name = ANY($1)";
+
+/// Escape the wildcards of a `LIKE` pattern so user input is matched literally.
+fn escape_like(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn address_filter(include_mined: bool) -> String {
+    let filter = r#"($1::text[] IS NULL OR "from" = ANY($1) OR "to" = ANY($1))"#;
+    match include_mined {
+        true => filter.to_string(),
+        false => format!("{filter} AND transaction_type != 'mined'"),
+    }
+}
+
+/// Fingerprint of a transfer request, stored next to its idempotency key so replays with a
+/// different body can be told apart from genuine retries.
+pub fn request_hash(to: &str, amount: Decimal, metadata: Option<&str>) -> String {
+    let metadata = metadata.unwrap_or_default();
+
+    crypto::sha256(&format!("{to}\n{}\n{metadata}", amount.normalize()))
+}
 
 impl TransactionNameData {
     /// Parse a transaction name from a string-like type according to CommonMeta format.
@@ -266,6 +1208,8 @@ impl TransactionNameData {
     /// If the input is empty, returns a default `TransactionNameData`.
     /// Otherwise parses according to the pattern: `meta@name.kro`
     ///
+    /// Unicode names are returned in their punycode form, invalid names are left out.
+    ///
     /// # Examples
     /// ```
     /// use kromer::database::transaction::TransactionNameData;
@@ -273,6 +1217,10 @@ impl TransactionNameData {
     /// assert_eq!(data.metaname, Some("meta".to_string()));
     /// assert_eq!(data.name, Some("name".to_string()));
     ///
+    /// let unicode = TransactionNameData::parse("meta@名前.kro");
+    /// assert_eq!(unicode.metaname, Some("meta".to_string()));
+    /// assert_eq!(unicode.name, Some("xn--ldr85b".to_string()));
+    ///
     /// let empty = TransactionNameData::parse("");
     /// assert_eq!(empty, TransactionNameData::default());
     /// ```
@@ -285,7 +1233,9 @@ impl TransactionNameData {
         match KRO_REGEX.captures(input) {
             Some(captures) => {
                 let metaname = captures.get(1).map(|m| m.as_str().to_string()); // TODO: Less allocating, should maybe use `&str` on the transaction models
-                let name = captures.get(2).map(|m| m.as_str().to_string());
+                let name = captures
+                    .get(2)
+                    .and_then(|m| validation::normalize_name(m.as_str()));
 
                 Self { metaname, name }
             }
@@ -356,3 +1306,803 @@ impl TransactionNameData {
         metaname_ref.map(|metaname| metaname.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::join_all;
+    use rust_decimal::dec;
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_concurrent_transfers_never_overdraw(
+        pool_opts: PgPoolOptions,
+        connect_opts: PgConnectOptions,
+    ) -> sqlx::Result<()> {
+        let pool = pool_opts
+            .max_connections(16)
+            .connect_with(connect_opts)
+            .await?;
+
+        let starting_balance = dec!(100);
+        let sender =
+            Wallet::create_wallet(&pool, "ksender000", "hash", Some(starting_balance)).await?;
+        let mut recipients = Vec::new();
+        for i in 0..4 {
+            let address = format!("krecv{i:05}");
+            recipients.push(Wallet::create_wallet(&pool, &address, "hash", None).await?);
+        }
+
+        // 300 transfers of 1 KRO from a wallet holding 100, so exactly 100 of them may succeed.
+        let attempts = (0..300).map(|i| {
+            let pool = pool.clone();
+            let creation_data = TransactionCreateData {
+                from: sender.address.clone(),
+                to: recipients[i % recipients.len()].address.clone(),
+                amount: dec!(1),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+
+            tokio::spawn(async move { Model::create(&pool, creation_data).await })
+        });
+
+        let mut succeeded = 0;
+        for result in join_all(attempts).await {
+            match result.expect("transfer task panicked") {
+                Ok(_) => succeeded += 1,
+                Err(DatabaseError::Transaction(TransactionError::InsufficientFunds)) => {}
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        }
+
+        let negative: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM wallets WHERE balance < 0")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(negative, 0);
+
+        let sender = Wallet::fetch_by_address(&pool, &sender.address)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(succeeded, 100);
+        assert_eq!(sender.balance, dec!(0));
+        assert_eq!(sender.total_out, starting_balance);
+
+        let received: Decimal =
+            sqlx::query_scalar("SELECT SUM(balance) FROM wallets WHERE address LIKE 'krecv%'")
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(received, starting_balance);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_hash_ignores_amount_scale() {
+        let a = request_hash("kaaaaaaaaa", dec!(1), Some("hi"));
+        let b = request_hash("kaaaaaaaaa", dec!(1.00), Some("hi"));
+        let c = request_hash("kaaaaaaaaa", dec!(1.01), Some("hi"));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_idempotency_key_replay(pool: Pool<Postgres>) -> sqlx::Result<()> {
+        let sender = Wallet::create_wallet(&pool, "ksender000", "hash", Some(dec!(10))).await?;
+        Wallet::create_wallet(&pool, "krecv00000", "hash", None).await?;
+
+        let hash = request_hash("krecv00000", dec!(5), None);
+        let creation_data = TransactionCreateData {
+            from: sender.address.clone(),
+            to: "krecv00000".to_owned(),
+            amount: dec!(5),
+            transaction_type: TransactionType::Transfer,
+            idempotency_key: Some("retry-me".to_owned()),
+            request_hash: Some(hash.clone()),
+            ..Default::default()
+        };
+        let original = Model::create(&pool, creation_data).await.unwrap();
+
+        let replay = Model::find_replay(&pool, &sender.address, "retry-me", &hash)
+            .await
+            .unwrap();
+        assert_eq!(replay, Some(original));
+
+        let fresh = Model::find_replay(&pool, &sender.address, "other-key", &hash)
+            .await
+            .unwrap();
+        assert_eq!(fresh, None);
+
+        let other_hash = request_hash("krecv00000", dec!(6), None);
+        let conflict = Model::find_replay(&pool, &sender.address, "retry-me", &other_hash).await;
+        assert!(matches!(
+            conflict,
+            Err(DatabaseError::Transaction(
+                TransactionError::IdempotencyKeyReused(_)
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_opposite_idempotent_transfers(
+        pool_opts: PgPoolOptions,
+        connect_opts: PgConnectOptions,
+    ) -> Result<()> {
+        let pool = pool_opts
+            .max_connections(16)
+            .connect_with(connect_opts)
+            .await?;
+        Wallet::create_wallet(&pool, "kaaaaaaaaa", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kbbbbbbbbb", "hash", Some(dec!(100))).await?;
+
+        // Each one checks for a replay before it locks both wallets, like the routes do.
+        let transfers = (0..100).map(|i| {
+            let pool = pool.clone();
+            let (from, to) = match i % 2 {
+                0 => ("kaaaaaaaaa", "kbbbbbbbbb"),
+                _ => ("kbbbbbbbbb", "kaaaaaaaaa"),
+            };
+            let key = format!("key-{i}");
+            let hash = request_hash(to, dec!(1), None);
+
+            async move {
+                let mut tx = pool.begin().await?;
+                Model::find_replay(&mut *tx, from, &key, &hash).await?;
+                let creation_data = TransactionCreateData {
+                    from: from.to_owned(),
+                    to: to.to_owned(),
+                    amount: dec!(1),
+                    transaction_type: TransactionType::Transfer,
+                    idempotency_key: Some(key),
+                    request_hash: Some(hash),
+                    ..Default::default()
+                };
+                Model::create(&mut *tx, creation_data).await?;
+                tx.commit().await?;
+
+                Ok::<_, DatabaseError>(())
+            }
+        });
+
+        for result in join_all(transfers).await {
+            result?;
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_lookup_orders_and_filters(pool: Pool<Postgres>) -> Result<()> {
+        use crate::models::krist::webserver::lookup::{LookupOrder, LookupQuery};
+
+        let alice = Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        let bob = Wallet::create_wallet(&pool, "kbob000000", "hash", None).await?;
+        Wallet::create_wallet(&pool, "kcarol0000", "hash", Some(dec!(100))).await?;
+
+        let transfers = [
+            ("kalice0000", "kbob000000", dec!(5), None),
+            ("kalice0000", "kbob000000", dec!(20), Some("shop")),
+            ("kcarol0000", "kalice0000", dec!(1), None),
+            ("kcarol0000", "kcarol0000", dec!(3), Some("shop")),
+        ];
+        for (from, to, amount, sent_name) in transfers {
+            let creation_data = TransactionCreateData {
+                from: from.to_string(),
+                to: to.to_string(),
+                amount,
+                sent_name: sent_name.map(str::to_string),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            Model::create(&pool, creation_data).await?;
+        }
+
+        let purchase = TransactionCreateData {
+            from: alice.address.clone(),
+            to: "kcarol0000".to_string(),
+            amount: dec!(10),
+            name: Some("shop".to_string()),
+            transaction_type: TransactionType::NamePurchase,
+            ..Default::default()
+        };
+        Model::create(&pool, purchase).await?;
+
+        let query = LookupQuery {
+            order_by: Some("value".to_string()),
+            order: Some("DESC".to_string()),
+            limit: Some("2".to_string()),
+            ..Default::default()
+        };
+        let params = query.parse::<TransactionLookupFields>().unwrap();
+        assert_eq!(params.order, LookupOrder::Desc);
+
+        let addresses = [bob.address.as_str()];
+        let found = Model::lookup_addresses(&pool, Some(&addresses), &params).await?;
+        let amounts: Vec<Decimal> = found.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, vec![dec!(20), dec!(5)]);
+        assert_eq!(
+            Model::count_lookup_addresses(&pool, Some(&addresses), false).await?,
+            2
+        );
+        assert_eq!(Model::count_lookup_addresses(&pool, None, false).await?, 5);
+
+        let params = LookupQuery::default()
+            .parse::<TransactionLookupFields>()
+            .unwrap();
+        let names = ["shop"];
+
+        let sent = Model::sent_to_name(&pool, &names, &params).await?;
+        let amounts: Vec<Decimal> = sent.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, vec![dec!(20), dec!(3)]);
+
+        let history = Model::name_history(&pool, &names, &params).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].transaction_type, TransactionType::NamePurchase);
+        assert_eq!(Model::count_name_history(&pool, &names).await?, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_count_by_metadata_matches_literally(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kbob000000", "hash", None).await?;
+
+        for metadata in ["Order 100% done", "order 1000 done", "nothing here"] {
+            let creation_data = TransactionCreateData {
+                from: "kalice0000".to_string(),
+                to: "kbob000000".to_string(),
+                amount: dec!(1),
+                metadata: Some(metadata.to_string()),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            Model::create(&pool, creation_data).await?;
+        }
+
+        assert_eq!(Model::count_by_metadata(&pool, "ORDER", false).await?, 2);
+        assert_eq!(Model::count_by_metadata(&pool, "100%", false).await?, 1);
+        assert_eq!(Model::count_by_metadata(&pool, "order_1", false).await?, 0);
+
+        Ok(())
+    }
+
+    async fn pay(pool: &Pool<Postgres>, to: &str, amount: Decimal, metadata: &str) -> Model {
+        let creation_data = TransactionCreateData {
+            from: "kalice0000".to_string(),
+            to: to.to_string(),
+            amount,
+            metadata: Some(metadata.to_string()),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+
+        Model::create(pool, creation_data).await.unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_refund(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kshop00000", "hash", None).await?;
+        Wallet::create_wallet(&pool, "kreturn000", "hash", None).await?;
+
+        let original = pay(&pool, "kshop00000", dec!(10), "return=kreturn000").await;
+
+        let refund = Model::refund(&pool, original.id, dec!(4), Some("out of stock")).await?;
+        assert_eq!(refund.transaction_type, TransactionType::Refund);
+        assert_eq!(refund.original_id, Some(original.id));
+        assert_eq!(refund.from.as_deref(), Some("kshop00000"));
+        assert_eq!(refund.to, "kreturn000");
+        assert_eq!(
+            refund.metadata.as_deref(),
+            Some(format!("ref={};type=refund;message=out of stock", original.id).as_str())
+        );
+
+        let result = Model::refund(&pool, original.id, dec!(6.01), None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::RefundTooLarge(_)
+            ))
+        ));
+
+        Model::refund(&pool, original.id, dec!(6), None).await?;
+        assert_eq!(Model::refunded_amount(&pool, original.id).await?, dec!(10));
+
+        let shop = Wallet::fetch_by_address(&pool, "kshop00000")
+            .await?
+            .unwrap();
+        assert_eq!(shop.balance, dec!(0));
+
+        // A refund is not a transfer, so it can't be refunded in turn.
+        let result = Model::refund(&pool, refund.id, dec!(1), None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(TransactionError::NotRefundable(
+                _
+            )))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_refund_falls_back_to_sender(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kshop00000", "hash", None).await?;
+
+        for metadata in [
+            "return=kmissing00",
+            "return=kshop00000",
+            "return=nowhere.kro",
+            "",
+        ] {
+            let original = pay(&pool, "kshop00000", dec!(1), metadata).await;
+            let refund = Model::refund(&pool, original.id, dec!(1), None).await?;
+            assert_eq!(refund.to, "kalice0000", "refund for {metadata:?}");
+        }
+
+        Ok(())
+    }
+
+    /// Alice pays Bob 30, who passes 20 of it on to Carol.
+    async fn setup_spent_transfer(pool: &Pool<Postgres>) -> Model {
+        Wallet::create_wallet(pool, "kalice0000", "hash", Some(dec!(100)))
+            .await
+            .unwrap();
+        Wallet::create_wallet(pool, "kbob000000", "hash", None)
+            .await
+            .unwrap();
+        Wallet::create_wallet(pool, "kcarol0000", "hash", None)
+            .await
+            .unwrap();
+
+        let original = pay(pool, "kbob000000", dec!(30), "").await;
+        let creation_data = TransactionCreateData {
+            from: "kbob000000".to_string(),
+            to: "kcarol0000".to_string(),
+            amount: dec!(20),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        Model::create(pool, creation_data).await.unwrap();
+
+        original
+    }
+
+    async fn wallet(pool: &Pool<Postgres>, address: &str) -> Wallet {
+        Wallet::fetch_by_address(pool, address)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_partial_reversal(pool: Pool<Postgres>) -> Result<()> {
+        let original = setup_spent_transfer(&pool).await;
+
+        let reversal =
+            Model::reverse(&pool, original.id, ReversalPolicy::Partial, Some("scam")).await?;
+        assert_eq!(reversal.transaction_type, TransactionType::Reversal);
+        assert_eq!(reversal.original_id, Some(original.id));
+        assert_eq!(reversal.amount, dec!(10));
+        assert_eq!(reversal.from.as_deref(), Some("kbob000000"));
+        assert_eq!(reversal.to, "kalice0000");
+
+        let bob = wallet(&pool, "kbob000000").await;
+        assert_eq!(
+            (bob.balance, bob.total_in, bob.total_out),
+            (dec!(0), dec!(20), dec!(20))
+        );
+        let alice = wallet(&pool, "kalice0000").await;
+        assert_eq!((alice.balance, alice.total_out), (dec!(80), dec!(20)));
+
+        let result = Model::reverse(&pool, original.id, ReversalPolicy::Debt, None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::AlreadyReversed(_)
+            ))
+        ));
+
+        let result = Model::reverse(&pool, reversal.id, ReversalPolicy::Debt, None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(TransactionError::NotReversible(
+                _
+            )))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_reversal_with_debt(pool: Pool<Postgres>) -> Result<()> {
+        let original = setup_spent_transfer(&pool).await;
+
+        let reversal = Model::reverse(&pool, original.id, ReversalPolicy::Debt, None).await?;
+        assert_eq!(reversal.amount, dec!(30));
+
+        let bob = wallet(&pool, "kbob000000").await;
+        assert_eq!((bob.balance, bob.total_in), (dec!(-20), dec!(0)));
+        let alice = wallet(&pool, "kalice0000").await;
+        assert_eq!((alice.balance, alice.total_out), (dec!(100), dec!(0)));
+
+        // A wallet in debt can't spend anything until it is paid off.
+        let creation_data = TransactionCreateData {
+            from: "kbob000000".to_string(),
+            to: "kcarol0000".to_string(),
+            amount: dec!(1),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        let result = Model::create(&pool, creation_data).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::InsufficientFunds
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_reversal_after_refund(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kshop00000", "hash", None).await?;
+
+        // Only what was not refunded yet is taken back.
+        let original = pay(&pool, "kshop00000", dec!(10), "").await;
+        Model::refund(&pool, original.id, dec!(4), None).await?;
+        let reversal = Model::reverse(&pool, original.id, ReversalPolicy::Debt, None).await?;
+        assert_eq!(reversal.amount, dec!(6));
+        assert_eq!(wallet(&pool, "kshop00000").await.balance, dec!(0));
+        assert_eq!(wallet(&pool, "kalice0000").await.balance, dec!(100));
+
+        // Nothing is left of a transfer that was refunded in full.
+        let original = pay(&pool, "kshop00000", dec!(10), "").await;
+        Model::refund(&pool, original.id, dec!(10), None).await?;
+        let result = Model::reverse(&pool, original.id, ReversalPolicy::Debt, None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::AlreadyRefunded(_)
+            ))
+        ));
+        assert_eq!(wallet(&pool, "kshop00000").await.balance, dec!(0));
+        assert_eq!(wallet(&pool, "kalice0000").await.balance, dec!(100));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_refund_after_reversal(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kshop00000", "hash", None).await?;
+
+        let original = pay(&pool, "kshop00000", dec!(10), "").await;
+        Model::reverse(&pool, original.id, ReversalPolicy::Partial, None).await?;
+
+        let result = Model::refund(&pool, original.id, dec!(1), None).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::AlreadyReversed(_)
+            ))
+        ));
+        assert_eq!(Model::refunded_amount(&pool, original.id).await?, dec!(0));
+        assert_eq!(wallet(&pool, "kalice0000").await.balance, dec!(100));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_reverse_welfare(pool: Pool<Postgres>) -> Result<()> {
+        let bob = Wallet::create_wallet(&pool, "kbob000000", "hash", None).await?;
+        bob.update_balance(&pool, dec!(50)).await?;
+        let creation_data = TransactionCreateData {
+            from: "serverwelf".into(),
+            to: "kbob000000".into(),
+            amount: dec!(50),
+            transaction_type: TransactionType::Mined,
+            ..Default::default()
+        };
+        let original = Model::create_no_update(&pool, creation_data).await?;
+        let welfare_before = wallet(&pool, "serverwelf").await;
+
+        Model::reverse(&pool, original.id, ReversalPolicy::Partial, None).await?;
+
+        let bob = wallet(&pool, "kbob000000").await;
+        assert_eq!((bob.balance, bob.total_in), (dec!(0), dec!(0)));
+        assert_eq!(wallet(&pool, "serverwelf").await, welfare_before);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_locked_wallet(pool: Pool<Postgres>) -> Result<()> {
+        use crate::database::wallet_lock_event::Model as WalletLockEvent;
+
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        let bob = Wallet::create_wallet(&pool, "kbob000000", "hash", Some(dec!(100))).await?;
+        bob.set_locked(&pool, true, "stolen key", "admin").await?;
+
+        let transfer = |from: &str, to: &str, transaction_type| TransactionCreateData {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            amount: dec!(0),
+            name: Some("shop".to_owned()),
+            transaction_type,
+            ..Default::default()
+        };
+
+        // Bob can't spend or be given a name, but can still be paid.
+        let result = Model::create(
+            &pool,
+            transfer("kbob000000", "kalice0000", TransactionType::Transfer),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Wallet(WalletError::Locked(address))) if address == "kbob000000"
+        ));
+        let result = Model::create(
+            &pool,
+            transfer("kalice0000", "kbob000000", TransactionType::NameTransfer),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Wallet(WalletError::Locked(_)))
+        ));
+        pay(&pool, "kbob000000", dec!(5), "").await;
+
+        bob.set_locked(&pool, false, "key recovered", "admin")
+            .await?;
+        Model::create(
+            &pool,
+            transfer("kbob000000", "kalice0000", TransactionType::Transfer),
+        )
+        .await?;
+
+        let events = WalletLockEvent::fetch_for_wallet(&pool, bob.id, 50, 0).await?;
+        let history: Vec<_> = events
+            .iter()
+            .map(|e| (e.locked, e.reason.as_str()))
+            .collect();
+        assert_eq!(history, [(false, "key recovered"), (true, "stolen key")]);
+
+        Ok(())
+    }
+
+    fn batch(transfers: &[(&str, Decimal)]) -> Vec<BatchTransfer> {
+        transfers
+            .iter()
+            .map(|(to, amount)| BatchTransfer {
+                to: to.to_string(),
+                amount: *amount,
+                metadata: Some("payroll".to_owned()),
+            })
+            .collect()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_batch_transfer(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kbob000000", "hash", None).await?;
+        Wallet::create_wallet(&pool, "kcarol0000", "hash", None).await?;
+        Name::create(&pool, "shop".to_owned(), "kcarol0000".to_owned()).await?;
+
+        let transfers = batch(&[
+            ("kbob000000", dec!(10)),
+            ("sales@shop.kro", dec!(5)),
+            ("kbob000000", dec!(2.5)),
+        ]);
+        let result = Model::create_batch(&pool, "kalice0000", &transfers).await?;
+
+        let sent: Vec<_> = result
+            .transactions
+            .iter()
+            .map(|t| (t.to.as_str(), t.amount, t.sent_name.as_deref()))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                ("kbob000000", dec!(10), None),
+                ("kcarol0000", dec!(5), Some("shop")),
+                ("kbob000000", dec!(2.5), None),
+            ]
+        );
+        assert_eq!(result.refunds, []);
+
+        let alice = wallet(&pool, "kalice0000").await;
+        assert_eq!((alice.balance, alice.total_out), (dec!(82.5), dec!(17.5)));
+        assert_eq!(wallet(&pool, "kbob000000").await.balance, dec!(12.5));
+        assert_eq!(wallet(&pool, "kcarol0000").await.balance, dec!(5));
+        assert!(Model::verify_chain(&pool).await?.broken.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_batch_transfer_is_atomic(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "kalice0000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "kbob000000", "hash", None).await?;
+        let before = wallet(&pool, "kalice0000").await;
+
+        // Each transfer is covered on its own, but not the total.
+        let transfers = batch(&[("kbob000000", dec!(60)), ("kbob000000", dec!(40.01))]);
+        let result = Model::create_batch(&pool, "kalice0000", &transfers).await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Transaction(
+                TransactionError::InsufficientFunds
+            ))
+        ));
+
+        let cases = [
+            (
+                batch(&[("kbob000000", dec!(1)), ("kmissing00", dec!(1))]),
+                1,
+            ),
+            (
+                batch(&[("kbob000000", dec!(1)), ("missing.kro", dec!(1))]),
+                1,
+            ),
+            (batch(&[("kbob000000", dec!(0.001))]), 0),
+            (
+                batch(&[("kbob000000", dec!(1)), ("kalice0000", dec!(1))]),
+                1,
+            ),
+        ];
+        for (transfers, index) in cases {
+            let result = Model::create_batch(&pool, "kalice0000", &transfers).await;
+            assert!(
+                matches!(result, Err(DatabaseError::BatchTransfer(i, _)) if i == index),
+                "{transfers:?} failed with {result:?}"
+            );
+        }
+
+        let result = Model::create_batch(&pool, "kalice0000", &[]).await;
+        assert!(matches!(result, Err(DatabaseError::Generic(_))));
+
+        assert_eq!(wallet(&pool, "kalice0000").await, before);
+        assert_eq!(Model::total_count(&pool).await?, 0);
+
+        Ok(())
+    }
+
+    async fn setup_chain(pool: &Pool<Postgres>) -> Vec<Model> {
+        Wallet::create_wallet(pool, "kalice0000", "hash", Some(dec!(100)))
+            .await
+            .unwrap();
+        Wallet::create_wallet(pool, "kbob000000", "hash", None)
+            .await
+            .unwrap();
+
+        let mut chain = vec![
+            pay(pool, "kbob000000", dec!(1.5), "first").await,
+            pay(pool, "kbob000000", dec!(10), "").await,
+        ];
+        let creation_data = TransactionCreateData {
+            from: "serverwelf".into(),
+            to: "kbob000000".into(),
+            amount: dec!(3),
+            transaction_type: TransactionType::Mined,
+            ..Default::default()
+        };
+        chain.push(Model::create_no_update(pool, creation_data).await.unwrap());
+
+        chain
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_hash_chain(pool: Pool<Postgres>) -> Result<()> {
+        let chain = setup_chain(&pool).await;
+
+        assert_eq!(chain[0].prev_hash.as_deref(), Some(GENESIS_HASH));
+        for pair in chain.windows(2) {
+            assert_eq!(pair[1].prev_hash, pair[0].hash);
+        }
+
+        // The stored row hashes the same as the one returned on insert.
+        let stored = Model::fetch_by_id(&pool, chain[1].id).await?.unwrap();
+        assert_eq!(stored, chain[1]);
+        assert_eq!(stored.hash, Some(stored.chain_hash()));
+
+        let head = Model::chain_head(&pool).await?.unwrap();
+        assert_eq!(
+            (head.id, Some(head.hash.clone())),
+            (chain[2].id, chain[2].hash.clone())
+        );
+
+        let verification = Model::verify_chain(&pool).await?;
+        assert_eq!(verification.checked, 3);
+        assert_eq!(verification.head, Some(head));
+        assert_eq!(verification.broken, None);
+
+        sqlx::query("UPDATE transactions SET amount = 100 WHERE id = $1")
+            .bind(chain[1].id)
+            .execute(&pool)
+            .await?;
+        let verification = Model::verify_chain(&pool).await?;
+        assert_eq!(verification.checked, 1);
+        assert_eq!(
+            verification.broken,
+            Some(BrokenLink {
+                id: chain[1].id,
+                reason: ChainBreak::HashMismatch,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_hash_chain_detects_removed_rows(pool: Pool<Postgres>) -> Result<()> {
+        let chain = setup_chain(&pool).await;
+
+        sqlx::query("DELETE FROM transactions WHERE id = $1")
+            .bind(chain[1].id)
+            .execute(&pool)
+            .await?;
+
+        let verification = Model::verify_chain(&pool).await?;
+        assert_eq!(
+            verification.broken,
+            Some(BrokenLink {
+                id: chain[2].id,
+                reason: ChainBreak::PrevHashMismatch,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_chain_existing(pool: Pool<Postgres>) -> Result<()> {
+        let insert = r#"INSERT INTO transactions(amount, "from", "to", metadata, transaction_type, date) VALUES (5, 'serverwelf', 'kbob000000', '', 'mined', NOW())"#;
+        for _ in 0..3 {
+            sqlx::query(insert).execute(&pool).await?;
+        }
+
+        assert_eq!(Model::chain_existing(&pool).await?, 3);
+        assert_eq!(Model::chain_existing(&pool).await?, 0);
+        let verification = Model::verify_chain(&pool).await?;
+        assert_eq!((verification.checked, verification.broken), (3, None));
+
+        // Once the chain exists, rows inserted behind its back are not linked.
+        sqlx::query(insert).execute(&pool).await?;
+        assert_eq!(Model::chain_existing(&pool).await?, 0);
+        let verification = Model::verify_chain(&pool).await?;
+        assert_eq!(
+            verification.broken.map(|broken| broken.reason),
+            Some(ChainBreak::Unhashed)
+        );
+
+        Ok(())
+    }
+}
@@ -3,8 +3,11 @@ use chrono::{DateTime, Utc};
 use rust_decimal::{Decimal, dec};
 use sqlx::{Acquire, Encode, Executor, Postgres, Type};
 
+use crate::config::get_config;
+use crate::database::wallet_lock_event::Model as WalletLockEvent;
 use crate::database::{DatabaseError, ModelExt, Result, name, transaction};
 use crate::errors::KromerError;
+use crate::errors::transaction::TransactionError;
 use crate::routes::PaginationParams;
 use crate::utils::crypto;
 
@@ -105,6 +108,11 @@ impl<'q> Model {
             .map_err(DatabaseError::Sqlx)
     }
 
+    /// Authenticate `private_key`, creating its wallet if it doesn't exist yet.
+    ///
+    /// A new wallet is granted the initial balance, which inserts a transaction. Call this before
+    /// locking any other wallet, outside of the transaction that moves funds, so the grant doesn't
+    /// hold the chain lock while other wallets are locked (see [`crate::database::locks`]).
     #[tracing::instrument(skip(pool))]
     pub async fn verify_address<A, S>(pool: A, private_key: S) -> Result<VerifyResponse>
     where
@@ -124,7 +132,24 @@ impl<'q> Model {
 
         let wallet = match result {
             Some(w) => w,
-            None => Self::create_wallet(&mut *tx, &address, &hash, None).await?,
+            None => {
+                let mut creation = (&mut *tx).begin().await?;
+                let wallet = Self::create_wallet(&mut *creation, &address, &hash, None).await?;
+
+                // Granted as welfare, so the ledger accounts for the starting balance.
+                let initial_balance = get_config().wallets.initial_balance;
+                let wallet = match initial_balance.is_zero() {
+                    true => wallet,
+                    false => {
+                        transaction::Model::grant(&mut *creation, &wallet, initial_balance)
+                            .await?
+                            .0
+                    }
+                };
+
+                creation.commit().await?;
+                wallet
+            }
         };
         let pkey = &wallet.private_key;
 
@@ -262,7 +287,7 @@ impl<'q> Model {
         SET
             balance = balance + $1,
             total_in = total_in + CASE WHEN $1 > 0 THEN $1 ELSE 0 END,
-            total_out = abs(total_out) + CASE WHEN $1 < 0 THEN abs($1) ELSE 0 END
+            total_out = total_out + CASE WHEN $1 < 0 THEN abs($1) ELSE 0 END
         WHERE address = $2
         RETURNING *;
         "#;
@@ -274,6 +299,131 @@ impl<'q> Model {
             .await
     }
 
+    /// Subtracts `amount` from the balance, but only if the wallet can cover it.
+    ///
+    /// The check and the debit happen in a single statement, so concurrent debits on the same
+    /// wallet can never take the balance below zero.
+    pub async fn debit<E>(&self, executor: E, amount: Decimal) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = r#"
+        UPDATE wallets
+        SET
+            balance = balance - $1,
+            total_out = total_out + $1
+        WHERE address = $2 AND balance >= $1
+        RETURNING *;
+        "#;
+
+        let model: Option<Model> = sqlx::query_as(q)
+            .bind(amount)
+            .bind(&self.address)
+            .fetch_optional(executor)
+            .await?;
+
+        model.ok_or(DatabaseError::Transaction(
+            TransactionError::InsufficientFunds,
+        ))
+    }
+
+    /// Takes back `amount` the wallet received, as if it never came in.
+    ///
+    /// This does not check the balance, a wallet that already spent the funds ends up in debt.
+    pub async fn unwind_incoming<E>(&self, executor: E, amount: Decimal) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "UPDATE wallets SET balance = balance - $1, total_in = total_in - $1 WHERE address = $2 RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(amount)
+            .bind(&self.address)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Gives back `amount` the wallet sent, as if it never went out.
+    pub async fn unwind_outgoing<E>(&self, executor: E, amount: Decimal) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "UPDATE wallets SET balance = balance + $1, total_out = total_out - $1 WHERE address = $2 RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(amount)
+            .bind(&self.address)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Overwrite the stored totals, used by the ledger audit to correct drift.
+    pub async fn set_totals<E>(
+        &self,
+        executor: E,
+        total_in: Decimal,
+        total_out: Decimal,
+    ) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "UPDATE wallets SET total_in = $2, total_out = $3 WHERE id = $1 RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(self.id)
+            .bind(total_in)
+            .bind(total_out)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// Lock or unlock the wallet, recording who did it and why.
+    ///
+    /// A locked wallet can't send, register names or receive them.
+    pub async fn set_locked<A>(
+        &self,
+        conn: A,
+        locked: bool,
+        reason: &str,
+        actor: &str,
+    ) -> Result<Model>
+    where
+        A: Acquire<'q, Database = Postgres>,
+    {
+        let mut tx = conn.begin().await?;
+
+        let q = "UPDATE wallets SET locked = $2 WHERE id = $1 RETURNING *";
+        let wallet: Model = sqlx::query_as(q)
+            .bind(self.id)
+            .bind(locked)
+            .fetch_one(&mut *tx)
+            .await?;
+        WalletLockEvent::create(&mut *tx, self.id, locked, reason, actor).await?;
+
+        tx.commit().await?;
+
+        Ok(wallet)
+    }
+
+    /// Locks the given wallets with `FOR UPDATE` until the surrounding transaction ends.
+    ///
+    /// Rows are always locked in id order so two transfers going opposite ways can't deadlock.
+    pub async fn lock_for_update<E>(executor: E, addresses: &[&str]) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM wallets WHERE address = ANY($1) ORDER BY id FOR UPDATE";
+
+        sqlx::query_as(q)
+            .bind(addresses)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
     pub async fn lookup_addresses<A>(
         conn: A,
         addresses: Vec<&str>,
@@ -0,0 +1,107 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Encode, Executor, Postgres, Type};
+
+use crate::database::{DatabaseError, ModelExt, Result};
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub struct Model {
+    pub id: i32,
+    pub wallet_id: i32,
+    /// Whether the wallet was locked or unlocked.
+    pub locked: bool,
+    pub reason: String,
+    /// Who made the change, as given by the caller of the internal API. It isn't verified.
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl<'q> ModelExt<'q> for Model {
+    async fn fetch_by_id<T, E>(pool: E, id: T) -> Result<Option<Self>>
+    where
+        Self: Sized,
+        T: 'q + Encode<'q, Postgres> + Type<Postgres> + Send,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT * FROM wallet_lock_events WHERE id = $1";
+
+        sqlx::query_as(q)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn fetch_all<E>(pool: E, limit: i64, offset: i64) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM wallet_lock_events ORDER BY id DESC LIMIT $1 OFFSET $2";
+
+        sqlx::query_as(q)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    async fn total_count<E>(pool: E) -> Result<usize>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "SELECT COUNT(*) FROM wallet_lock_events";
+        let result: i64 = sqlx::query_scalar(q).fetch_one(pool).await?;
+
+        Ok(result as usize)
+    }
+}
+
+impl<'q> Model {
+    pub async fn create<E>(
+        executor: E,
+        wallet_id: i32,
+        locked: bool,
+        reason: &str,
+        actor: &str,
+    ) -> Result<Model>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let q = "INSERT INTO wallet_lock_events(wallet_id, locked, reason, actor) VALUES ($1, $2, $3, $4) RETURNING *";
+
+        sqlx::query_as(q)
+            .bind(wallet_id)
+            .bind(locked)
+            .bind(reason)
+            .bind(actor)
+            .fetch_one(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+
+    /// The lock history of a wallet, newest first.
+    pub async fn fetch_for_wallet<E>(
+        executor: E,
+        wallet_id: i32,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Model>>
+    where
+        E: 'q + Executor<'q, Database = Postgres>,
+    {
+        let limit = limit.clamp(1, 1000);
+        let q = "SELECT * FROM wallet_lock_events WHERE wallet_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2 OFFSET $3";
+
+        sqlx::query_as(q)
+            .bind(wallet_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(executor)
+            .await
+            .map_err(DatabaseError::Sqlx)
+    }
+}
@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Could not read config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("Could not parse config file {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+
+    #[error("Invalid config value for {0}: {1}")]
+    Invalid(&'static str, String),
+}
@@ -49,6 +49,10 @@ pub enum KristError {
 
     #[error("{0}")]
     Custom(&'static str),
+
+    /// Something wrong with a single transfer of a batch, at the given index.
+    #[error("Transfer {0}: {1}")]
+    BatchTransfer(usize, Box<KristError>),
 }
 
 pub trait KristErrorExt {
@@ -67,6 +71,7 @@ impl KristErrorExt for KristError {
             KristError::Database(_) => "internal_server_error",
             KristError::JsonPayload(_) => "internal_server_error",
             KristError::Path(_) => "internal_server_error",
+            KristError::BatchTransfer(_, e) => e.error_type(),
             KristError::Custom(e) => e, // Same way as krist, where message is the error type when no message type is given
         }
     }
@@ -87,6 +92,7 @@ impl error::ResponseError for KristError {
             KristError::JsonPayload(_) => StatusCode::INTERNAL_SERVER_ERROR,
             KristError::Path(_) => StatusCode::INTERNAL_SERVER_ERROR,
             KristError::Custom(_) => StatusCode::BAD_REQUEST,
+            KristError::BatchTransfer(_, e) => e.status_code(),
         }
     }
 
@@ -12,6 +12,9 @@ pub enum AddressError {
 
     #[error("Authentication failed")]
     AuthFailed,
+
+    #[error("Address {0} is locked")]
+    Locked(String),
 }
 
 impl KristErrorExt for AddressError {
@@ -19,6 +22,7 @@ impl KristErrorExt for AddressError {
         match self {
             AddressError::NotFound(_) => "address_not_found",
             AddressError::AuthFailed => "auth_failed",
+            AddressError::Locked(_) => "address_locked",
         }
     }
 }
@@ -31,6 +35,7 @@ impl error::ResponseError for AddressError {
         match self {
             AddressError::NotFound(_) => StatusCode::NOT_FOUND,
             AddressError::AuthFailed => StatusCode::UNAUTHORIZED,
+            AddressError::Locked(_) => StatusCode::FORBIDDEN,
         }
     }
 
@@ -51,6 +56,7 @@ impl From<wallet::WalletError> for AddressError {
         match value {
             wallet::WalletError::NotFound(address) => AddressError::NotFound(address),
             wallet::WalletError::AuthFailed => AddressError::AuthFailed,
+            wallet::WalletError::Locked(address) => AddressError::Locked(address),
         }
     }
 }
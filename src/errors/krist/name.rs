@@ -18,6 +18,24 @@ pub enum NameError {
 
     #[error("Insufficient balance to purchase name")]
     InsufficientBalance,
+
+    #[error("Name {0} is not for sale")]
+    NotForSale(String),
+
+    #[error("The price of name {0} has changed")]
+    ListingPriceChanged(String),
+
+    #[error("You already own name {0}")]
+    AlreadyOwned(String),
+
+    #[error("Name {0} has expired and must be renewed first")]
+    NameExpired(String),
+
+    #[error("Name {0} is reserved")]
+    NameReserved(String),
+
+    #[error("There is no route for {0}")]
+    RouteNotFound(String),
 }
 
 impl error::ResponseError for NameError {
@@ -27,6 +45,12 @@ impl error::ResponseError for NameError {
             NameError::NameTaken(_) => StatusCode::CONFLICT,
             NameError::NotNameOwner(_) => StatusCode::FORBIDDEN,
             NameError::InsufficientBalance => StatusCode::IM_A_TEAPOT, // Really dont know what to put here instead of 418.
+            NameError::NotForSale(_) => StatusCode::NOT_FOUND,
+            NameError::ListingPriceChanged(_) => StatusCode::CONFLICT,
+            NameError::AlreadyOwned(_) => StatusCode::BAD_REQUEST,
+            NameError::NameExpired(_) => StatusCode::BAD_REQUEST,
+            NameError::NameReserved(_) => StatusCode::FORBIDDEN,
+            NameError::RouteNotFound(_) => StatusCode::NOT_FOUND,
         }
     }
 
@@ -49,6 +73,12 @@ impl KristErrorExt for NameError {
             NameError::NameTaken(_) => "name_taken",
             NameError::NotNameOwner(_) => "not_name_owner",
             NameError::InsufficientBalance => "insufficient_balance",
+            NameError::NotForSale(_) => "name_not_for_sale",
+            NameError::ListingPriceChanged(_) => "listing_price_changed",
+            NameError::AlreadyOwned(_) => "name_already_owned",
+            NameError::NameExpired(_) => "name_expired",
+            NameError::NameReserved(_) => "name_reserved",
+            NameError::RouteNotFound(_) => "name_route_not_found",
         }
     }
 }
@@ -60,6 +90,12 @@ impl From<name::NameError> for NameError {
             name::NameError::NameTaken(name) => Self::NameTaken(name),
             name::NameError::NotNameOwner(name) => Self::NotNameOwner(name),
             name::NameError::InsufficientBalance => Self::InsufficientBalance,
+            name::NameError::NotForSale(name) => Self::NotForSale(name),
+            name::NameError::ListingPriceChanged(name) => Self::ListingPriceChanged(name),
+            name::NameError::AlreadyOwned(name) => Self::AlreadyOwned(name),
+            name::NameError::NameExpired(name) => Self::NameExpired(name),
+            name::NameError::NameReserved(name) => Self::NameReserved(name),
+            name::NameError::RouteNotFound(route) => Self::RouteNotFound(route),
         }
     }
 }
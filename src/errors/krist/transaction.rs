@@ -21,6 +21,36 @@ pub enum TransactionError {
 
     #[error("Transaction conflict for parameter {0}")]
     Conflict(String),
+
+    #[error("Idempotency key {0} was already used for a different transaction")]
+    IdempotencyKeyReused(String),
+
+    #[error("Transaction {0} can not be refunded")]
+    NotRefundable(i32),
+
+    #[error("Refund is larger than what is left to refund of transaction {0}")]
+    RefundTooLarge(i32),
+
+    #[error("Transaction {0} can not be reversed")]
+    NotReversible(i32),
+
+    #[error("Transaction {0} was already reversed")]
+    AlreadyReversed(i32),
+
+    #[error("Transaction {0} was already fully refunded")]
+    AlreadyRefunded(i32),
+
+    #[error("Payment schedule {0} not found")]
+    ScheduleNotFound(i32),
+
+    #[error("Payment schedule {0} belongs to another address")]
+    NotScheduleOwner(i32),
+
+    #[error("Payment schedule {0} already finished or was cancelled")]
+    ScheduleInactive(i32),
+
+    #[error("Address {0} has too many active payment schedules")]
+    TooManySchedules(String),
 }
 
 impl KristErrorExt for TransactionError {
@@ -31,6 +61,16 @@ impl KristErrorExt for TransactionError {
             TransactionError::Disabled => "transactions_disabled",
             TransactionError::SameWalletTransfer => "same_wallet_transfer",
             TransactionError::Conflict(_) => "transaction_conflict",
+            TransactionError::IdempotencyKeyReused(_) => "idempotency_key_reused",
+            TransactionError::NotRefundable(_) => "transaction_not_refundable",
+            TransactionError::RefundTooLarge(_) => "refund_too_large",
+            TransactionError::NotReversible(_) => "transaction_not_reversible",
+            TransactionError::AlreadyReversed(_) => "transaction_already_reversed",
+            TransactionError::AlreadyRefunded(_) => "transaction_already_refunded",
+            TransactionError::ScheduleNotFound(_) => "schedule_not_found",
+            TransactionError::NotScheduleOwner(_) => "not_schedule_owner",
+            TransactionError::ScheduleInactive(_) => "schedule_inactive",
+            TransactionError::TooManySchedules(_) => "too_many_schedules",
         }
     }
 }
@@ -43,6 +83,16 @@ impl error::ResponseError for TransactionError {
             TransactionError::Disabled => StatusCode::LOCKED,
             TransactionError::SameWalletTransfer => StatusCode::BAD_REQUEST,
             TransactionError::Conflict(_) => StatusCode::CONFLICT,
+            TransactionError::IdempotencyKeyReused(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            TransactionError::NotRefundable(_) => StatusCode::BAD_REQUEST,
+            TransactionError::RefundTooLarge(_) => StatusCode::BAD_REQUEST,
+            TransactionError::NotReversible(_) => StatusCode::BAD_REQUEST,
+            TransactionError::AlreadyReversed(_) => StatusCode::CONFLICT,
+            TransactionError::AlreadyRefunded(_) => StatusCode::CONFLICT,
+            TransactionError::ScheduleNotFound(_) => StatusCode::NOT_FOUND,
+            TransactionError::NotScheduleOwner(_) => StatusCode::FORBIDDEN,
+            TransactionError::ScheduleInactive(_) => StatusCode::CONFLICT,
+            TransactionError::TooManySchedules(_) => StatusCode::BAD_REQUEST,
         }
     }
 
@@ -66,6 +116,20 @@ impl From<transaction::TransactionError> for TransactionError {
             transaction::TransactionError::Disabled => Self::Disabled,
             transaction::TransactionError::SameWalletTransfer => Self::SameWalletTransfer,
             transaction::TransactionError::Conflict(param) => Self::Conflict(param),
+            transaction::TransactionError::IdempotencyKeyReused(key) => {
+                Self::IdempotencyKeyReused(key)
+            }
+            transaction::TransactionError::NotRefundable(id) => Self::NotRefundable(id),
+            transaction::TransactionError::RefundTooLarge(id) => Self::RefundTooLarge(id),
+            transaction::TransactionError::NotReversible(id) => Self::NotReversible(id),
+            transaction::TransactionError::AlreadyReversed(id) => Self::AlreadyReversed(id),
+            transaction::TransactionError::AlreadyRefunded(id) => Self::AlreadyRefunded(id),
+            transaction::TransactionError::ScheduleNotFound(id) => Self::ScheduleNotFound(id),
+            transaction::TransactionError::NotScheduleOwner(id) => Self::NotScheduleOwner(id),
+            transaction::TransactionError::ScheduleInactive(id) => Self::ScheduleInactive(id),
+            transaction::TransactionError::TooManySchedules(address) => {
+                Self::TooManySchedules(address)
+            }
         }
     }
 }
@@ -1,3 +1,4 @@
+pub mod config;
 pub mod krist;
 pub mod name;
 pub mod player;
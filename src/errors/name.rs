@@ -13,6 +13,24 @@ pub enum NameError {
 
     #[error("Insufficient balance to purchase name")]
     InsufficientBalance,
+
+    #[error("Name {0} is not for sale")]
+    NotForSale(String),
+
+    #[error("The price of name {0} has changed")]
+    ListingPriceChanged(String),
+
+    #[error("You already own name {0}")]
+    AlreadyOwned(String),
+
+    #[error("Name {0} has expired and must be renewed first")]
+    NameExpired(String),
+
+    #[error("Name {0} is reserved")]
+    NameReserved(String),
+
+    #[error("There is no route for {0}")]
+    RouteNotFound(String),
 }
 
 impl error::ResponseError for NameError {
@@ -22,6 +40,12 @@ impl error::ResponseError for NameError {
             NameError::NotNameOwner(_) => StatusCode::UNAUTHORIZED,
             NameError::NameTaken(_) => StatusCode::CONFLICT,
             NameError::InsufficientBalance => StatusCode::PAYMENT_REQUIRED,
+            NameError::NotForSale(_) => StatusCode::NOT_FOUND,
+            NameError::ListingPriceChanged(_) => StatusCode::CONFLICT,
+            NameError::AlreadyOwned(_) => StatusCode::BAD_REQUEST,
+            NameError::NameExpired(_) => StatusCode::BAD_REQUEST,
+            NameError::NameReserved(_) => StatusCode::FORBIDDEN,
+            NameError::RouteNotFound(_) => StatusCode::NOT_FOUND,
         }
     }
 }
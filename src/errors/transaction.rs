@@ -16,6 +16,36 @@ pub enum TransactionError {
 
     #[error("Transaction conflict for parameter {0}")]
     Conflict(String),
+
+    #[error("Idempotency key {0} was already used for a different transaction")]
+    IdempotencyKeyReused(String),
+
+    #[error("Transaction {0} can not be refunded")]
+    NotRefundable(i32),
+
+    #[error("Refund is larger than what is left to refund of transaction {0}")]
+    RefundTooLarge(i32),
+
+    #[error("Transaction {0} can not be reversed")]
+    NotReversible(i32),
+
+    #[error("Transaction {0} was already reversed")]
+    AlreadyReversed(i32),
+
+    #[error("Transaction {0} was already fully refunded")]
+    AlreadyRefunded(i32),
+
+    #[error("Payment schedule {0} not found")]
+    ScheduleNotFound(i32),
+
+    #[error("Payment schedule {0} belongs to another address")]
+    NotScheduleOwner(i32),
+
+    #[error("Payment schedule {0} already finished or was cancelled")]
+    ScheduleInactive(i32),
+
+    #[error("Address {0} has too many active payment schedules")]
+    TooManySchedules(String),
 }
 
 impl error::ResponseError for TransactionError {
@@ -26,6 +56,16 @@ impl error::ResponseError for TransactionError {
             TransactionError::Disabled => StatusCode::FORBIDDEN,
             TransactionError::SameWalletTransfer => StatusCode::FORBIDDEN,
             TransactionError::Conflict(_) => StatusCode::CONFLICT,
+            TransactionError::IdempotencyKeyReused(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            TransactionError::NotRefundable(_) => StatusCode::BAD_REQUEST,
+            TransactionError::RefundTooLarge(_) => StatusCode::BAD_REQUEST,
+            TransactionError::NotReversible(_) => StatusCode::BAD_REQUEST,
+            TransactionError::AlreadyReversed(_) => StatusCode::CONFLICT,
+            TransactionError::AlreadyRefunded(_) => StatusCode::CONFLICT,
+            TransactionError::ScheduleNotFound(_) => StatusCode::NOT_FOUND,
+            TransactionError::NotScheduleOwner(_) => StatusCode::FORBIDDEN,
+            TransactionError::ScheduleInactive(_) => StatusCode::CONFLICT,
+            TransactionError::TooManySchedules(_) => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -7,6 +7,9 @@ pub enum WalletError {
 
     #[error("Authentication failed")]
     AuthFailed,
+
+    #[error("Wallet {0} is locked")]
+    Locked(String),
 }
 
 impl error::ResponseError for WalletError {
@@ -14,6 +17,7 @@ impl error::ResponseError for WalletError {
         match self {
             WalletError::NotFound(_) => actix_web::http::StatusCode::NOT_FOUND,
             WalletError::AuthFailed => actix_web::http::StatusCode::BAD_REQUEST,
+            WalletError::Locked(_) => actix_web::http::StatusCode::FORBIDDEN,
         }
     }
 }
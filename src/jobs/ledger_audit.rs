@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+
+use futures_util::TryStreamExt;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+
+use crate::database::Result;
+use crate::database::name::NAME_LEDGER_ADDRESS;
+use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
+use crate::database::wallet::Model as Wallet;
+
+/// A wallet whose stored numbers don't match a replay of the transaction log.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WalletDiscrepancy {
+    pub address: String,
+    pub balance: Decimal,
+    pub expected_balance: Decimal,
+    pub total_in: Decimal,
+    pub expected_total_in: Decimal,
+    pub total_out: Decimal,
+    pub expected_total_out: Decimal,
+    /// The adjustment transaction written for the balance difference, when fixing.
+    pub adjustment_id: Option<i32>,
+}
+
+/// What a single audit found, and fixed if asked to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AuditReport {
+    pub wallets: usize,
+    pub transactions: usize,
+    pub discrepancies: Vec<WalletDiscrepancy>,
+    /// The supply `/supply` reports, from the stored balances.
+    pub supply: Decimal,
+    /// The supply according to the transaction log.
+    pub expected_supply: Decimal,
+    /// Whether adjustments were written, after which the stored numbers match the log.
+    pub fixed: bool,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty() && self.supply == self.expected_supply
+    }
+}
+
+/// A transaction as far as the replay cares, with the type of the transaction it points at.
+#[derive(Debug, sqlx::FromRow)]
+struct LedgerEntry {
+    amount: Decimal,
+    from: Option<String>,
+    to: String,
+    transaction_type: TransactionType,
+    original_type: Option<TransactionType>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Totals {
+    balance: Decimal,
+    total_in: Decimal,
+    total_out: Decimal,
+}
+
+impl Totals {
+    fn receive(&mut self, amount: Decimal) {
+        self.balance += amount;
+        self.total_in += amount;
+    }
+
+    fn send(&mut self, amount: Decimal) {
+        self.balance -= amount;
+        self.total_out += amount;
+    }
+
+    /// A signed change that isn't part of a transfer, like `update_balance` does it.
+    fn adjust(&mut self, amount: Decimal) {
+        match amount.is_sign_negative() {
+            true => self.send(-amount),
+            false => self.receive(amount),
+        }
+    }
+}
+
+/// Apply a single transaction to the replayed wallets, the same way it was applied when made.
+fn apply(ledger: &mut HashMap<String, Totals>, entry: &LedgerEntry) {
+    let amount = entry.amount;
+    if amount.is_zero() {
+        return;
+    }
+
+    match entry.transaction_type {
+        // Welfare only credits the recipient, `serverwelf` is never debited for it.
+        TransactionType::Mined => ledger.entry(entry.to.clone()).or_default().receive(amount),
+        TransactionType::Adjustment => ledger.entry(entry.to.clone()).or_default().adjust(amount),
+        // A reversal goes from whoever received the original back to its sender, undoing totals
+        // instead of adding to them. Reversed welfare only takes the funds away again.
+        TransactionType::Reversal => {
+            if let Some(from) = &entry.from {
+                let recipient = ledger.entry(from.clone()).or_default();
+                recipient.balance -= amount;
+                recipient.total_in -= amount;
+            }
+            if entry.original_type != Some(TransactionType::Mined) {
+                let sender = ledger.entry(entry.to.clone()).or_default();
+                sender.balance += amount;
+                sender.total_out -= amount;
+            }
+        }
+        _ => {
+            if let Some(from) = &entry.from {
+                ledger.entry(from.clone()).or_default().send(amount);
+            }
+            ledger.entry(entry.to.clone()).or_default().receive(amount);
+        }
+    }
+}
+
+/// Replay the transaction log for every wallet and compare the result with the stored balances
+/// and totals.
+///
+/// With `fix`, every balance difference is recorded as an `adjustment` transaction so the log
+/// explains the balance as it is, and the totals are overwritten with the replayed ones. Wallets
+/// are locked for the duration of a fix; a plain audit only reads a consistent snapshot.
+pub async fn run(pool: &Pool<Postgres>, fix: bool) -> Result<AuditReport> {
+    let mut tx = pool.begin().await?;
+
+    // Nothing can move funds while every wallet is locked, so the log can't change under us.
+    let q = match fix {
+        true => "SELECT * FROM wallets ORDER BY id FOR UPDATE",
+        false => {
+            sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                .execute(&mut *tx)
+                .await?;
+            "SELECT * FROM wallets ORDER BY id"
+        }
+    };
+    let wallets: Vec<Wallet> = sqlx::query_as(q).fetch_all(&mut *tx).await?;
+
+    let mut report = AuditReport {
+        wallets: wallets.len(),
+        fixed: fix,
+        ..Default::default()
+    };
+
+    let q = r#"
+    SELECT t.amount, t."from", t."to", t.transaction_type, o.transaction_type AS original_type
+    FROM transactions t
+    LEFT JOIN transactions o ON o.id = t.original_id
+    ORDER BY t.id
+    "#;
+    let mut ledger: HashMap<String, Totals> = HashMap::new();
+    {
+        let mut entries = sqlx::query_as::<_, LedgerEntry>(q).fetch(&mut *tx);
+        while let Some(entry) = entries.try_next().await? {
+            apply(&mut ledger, &entry);
+            report.transactions += 1;
+        }
+    }
+
+    for wallet in &wallets {
+        let mut expected = ledger.get(&wallet.address).copied().unwrap_or_default();
+        if wallet.address != NAME_LEDGER_ADDRESS {
+            report.supply += wallet.balance;
+            report.expected_supply += expected.balance;
+        }
+
+        let stored = Totals {
+            balance: wallet.balance,
+            total_in: wallet.total_in,
+            total_out: wallet.total_out,
+        };
+        if stored == expected {
+            continue;
+        }
+
+        let mut discrepancy = WalletDiscrepancy {
+            address: wallet.address.clone(),
+            balance: stored.balance,
+            expected_balance: expected.balance,
+            total_in: stored.total_in,
+            expected_total_in: expected.total_in,
+            total_out: stored.total_out,
+            expected_total_out: expected.total_out,
+            adjustment_id: None,
+        };
+
+        if fix {
+            let difference = stored.balance - expected.balance;
+            if !difference.is_zero() {
+                let creation_data = TransactionCreateData {
+                    from: NAME_LEDGER_ADDRESS.to_owned(),
+                    to: wallet.address.clone(),
+                    amount: difference,
+                    metadata: Some("type=adjustment".to_owned()),
+                    transaction_type: TransactionType::Adjustment,
+                    ..Default::default()
+                };
+                let adjustment = Transaction::create_no_update(&mut *tx, creation_data).await?;
+                discrepancy.adjustment_id = Some(adjustment.id);
+                expected.adjust(difference);
+            }
+
+            wallet
+                .set_totals(&mut *tx, expected.total_in, expected.total_out)
+                .await?;
+        }
+
+        report.discrepancies.push(discrepancy);
+    }
+
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::transaction::ReversalPolicy;
+    use rust_decimal::dec;
+
+    async fn transfer(pool: &Pool<Postgres>, from: &str, to: &str, amount: Decimal) -> Transaction {
+        let creation_data = TransactionCreateData {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            amount,
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+        Transaction::create(pool, creation_data).await.unwrap()
+    }
+
+    async fn welfare(pool: &Pool<Postgres>, wallet: &Wallet, amount: Decimal) -> Transaction {
+        wallet.update_balance(pool, amount).await.unwrap();
+        let creation_data = TransactionCreateData {
+            from: NAME_LEDGER_ADDRESS.to_owned(),
+            to: wallet.address.clone(),
+            amount,
+            transaction_type: TransactionType::Mined,
+            ..Default::default()
+        };
+        Transaction::create_no_update(pool, creation_data)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_clean_ledger(pool: Pool<Postgres>) -> Result<()> {
+        let alice = Wallet::create_wallet(&pool, "kalice0000", "hash", None).await?;
+        let bob = Wallet::create_wallet(&pool, "kbob000000", "hash", None).await?;
+
+        welfare(&pool, &alice, dec!(100)).await;
+        let mined = welfare(&pool, &bob, dec!(10)).await;
+        let payment = transfer(&pool, "kalice0000", "kbob000000", dec!(40)).await;
+        transfer(&pool, "kbob000000", "kalice0000", dec!(5)).await;
+        Transaction::reverse(&pool, payment.id, ReversalPolicy::Partial, None).await?;
+        Transaction::reverse(&pool, mined.id, ReversalPolicy::Debt, None).await?;
+
+        let report = run(&pool, false).await?;
+        assert_eq!(report.discrepancies, []);
+        assert!(report.is_clean());
+        assert_eq!(report.supply, dec!(100));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_fix_drift(pool: Pool<Postgres>) -> Result<()> {
+        let alice = Wallet::create_wallet(&pool, "kalice0000", "hash", None).await?;
+        Wallet::create_wallet(&pool, "kbob000000", "hash", Some(dec!(25))).await?;
+
+        // Granted without a transaction, like `/_internal/wallet/create` used to.
+        alice.set_balance(&pool, dec!(100)).await.unwrap();
+        transfer(&pool, "kalice0000", "kbob000000", dec!(30)).await;
+
+        let report = run(&pool, false).await?;
+        assert!(!report.is_clean());
+        assert_eq!(report.supply, dec!(125));
+        assert_eq!(report.expected_supply, dec!(0));
+
+        let alice_report = &report.discrepancies[0];
+        assert_eq!(alice_report.address, "kalice0000");
+        assert_eq!(alice_report.balance, dec!(70));
+        assert_eq!(alice_report.expected_balance, dec!(-30));
+        assert_eq!(alice_report.adjustment_id, None);
+
+        let report = run(&pool, true).await?;
+        assert_eq!(report.discrepancies.len(), 2);
+        assert!(
+            report
+                .discrepancies
+                .iter()
+                .all(|d| d.adjustment_id.is_some())
+        );
+
+        let alice = Wallet::fetch_by_address(&pool, "kalice0000")
+            .await?
+            .unwrap();
+        assert_eq!(
+            (alice.balance, alice.total_in, alice.total_out),
+            (dec!(70), dec!(100), dec!(30))
+        );
+
+        let report = run(&pool, false).await?;
+        assert_eq!(report.discrepancies, []);
+        assert!(report.is_clean());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+//! Jobs that run next to the web server on every instance, or on demand.
+
+pub mod ledger_audit;
+pub mod name_lifecycle;
+pub mod scheduled_payments;
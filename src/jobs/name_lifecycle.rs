@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+use actix_web::rt::time;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::config::get_config;
+use crate::database::Result;
+use crate::database::name::Model as Name;
+use crate::utils::clock::Clock;
+use crate::websockets::WebSocketServer;
+
+/// What a single lifecycle run did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LifecycleReport {
+    /// How many names had their `unpaid` bonus counted down.
+    pub decremented: u64,
+    /// Names that expired and entered their grace period.
+    pub expired: Vec<String>,
+    /// Names that were released after their grace period.
+    pub released: Vec<String>,
+}
+
+/// Drive the name lifecycle forever, running every `names.lifecycle_interval_secs`.
+pub async fn run<C: Clock>(pool: Pool<Postgres>, server: WebSocketServer, clock: C) {
+    let interval_secs = get_config().names.lifecycle_interval_secs;
+    let mut interval = time::interval(Duration::from_secs(interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match run_once(&pool, &server, clock.now()).await {
+            Ok(report) if report.expired.is_empty() && report.released.is_empty() => {}
+            Ok(report) => tracing::info!(
+                "Expired names {:?}, released names {:?}",
+                report.expired,
+                report.released
+            ),
+            Err(err) => tracing::error!("Failed to run the name lifecycle: {err}"),
+        }
+    }
+}
+
+/// Count down the unpaid bonus, then expire and release every name that is due at `now`.
+///
+/// Only one instance does this at a time; the others return an empty report.
+pub async fn run_once(
+    pool: &Pool<Postgres>,
+    server: &WebSocketServer,
+    now: DateTime<Utc>,
+) -> Result<LifecycleReport> {
+    let mut report = LifecycleReport::default();
+
+    let mut tx = pool.begin().await?;
+    if !Name::try_lock_lifecycle(&mut *tx).await? {
+        return Ok(report);
+    }
+
+    report.decremented = Name::decrement_unpaid(&mut *tx, now).await?;
+
+    for name in Name::fetch_due_for_expiry(&mut *tx, now).await? {
+        if let Some(name) = name.expire(&mut *tx, server, now).await? {
+            report.expired.push(name.name);
+        }
+    }
+
+    for name in Name::fetch_due_for_release(&mut *tx, now).await? {
+        if let Some(name) = name.release(&mut *tx, server, now).await? {
+            report.released.push(name.name);
+        }
+    }
+
+    tx.commit().await?;
+    server.notify_outbox();
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::transaction::{Model as Transaction, TransactionType};
+    use crate::database::wallet::Model as Wallet;
+    use crate::database::{DatabaseError, ModelExt};
+    use crate::errors::name::NameError;
+    use crate::utils::clock::FakeClock;
+    use chrono::TimeDelta;
+    use rust_decimal::{Decimal, dec};
+
+    async fn setup(pool: &Pool<Postgres>) -> Result<Name> {
+        Wallet::create_wallet(pool, "kowner0000", "hash", Some(dec!(1000))).await?;
+
+        Name::create(pool, "example".to_owned(), "kowner0000".to_owned()).await
+    }
+
+    async fn transaction_types(pool: &Pool<Postgres>) -> Vec<TransactionType> {
+        let mut transactions = Transaction::fetch_all(pool, 100, 0).await.unwrap();
+        transactions.sort_by_key(|transaction| transaction.id);
+
+        transactions
+            .into_iter()
+            .map(|transaction| transaction.transaction_type)
+            .collect()
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_unpaid_bonus_counts_down(pool: Pool<Postgres>) -> Result<()> {
+        let name = setup(&pool).await?;
+        let server = WebSocketServer::new();
+        let config = &get_config().names;
+        let period = TimeDelta::seconds(config.unpaid_decrement_secs);
+        let unpaid = |bonus: i64| Decimal::from(config.unpaid_bonus - bonus);
+        assert_eq!(name.unpaid, unpaid(0));
+
+        let clock = FakeClock::new(name.unpaid_decremented_at + period - TimeDelta::seconds(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.decremented, 0);
+
+        clock.advance(TimeDelta::seconds(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.decremented, 1);
+
+        // Another instance running at the same time counts nothing twice.
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.decremented, 0);
+
+        let name = Name::fetch_by_name(&pool, "example").await?.unwrap();
+        assert_eq!(name.unpaid, unpaid(1));
+        assert_eq!(Name::count_unpaid(&pool).await?, 1);
+
+        // A late run catches up on every full period, however long it was.
+        clock.advance(period * 5 / 2);
+        run_once(&pool, &server, clock.now()).await?;
+        let name = Name::fetch_by_name(&pool, "example").await?.unwrap();
+        assert_eq!(name.unpaid, unpaid(3));
+
+        clock.advance(period * (config.unpaid_bonus as i32));
+        run_once(&pool, &server, clock.now()).await?;
+        let name = Name::fetch_by_name(&pool, "example").await?.unwrap();
+        assert_eq!(name.unpaid, Decimal::ZERO);
+        assert_eq!(Name::count_unpaid(&pool).await?, 0);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_expiry_grace_and_release(pool: Pool<Postgres>) -> Result<()> {
+        let name = setup(&pool).await?;
+        let server = WebSocketServer::new();
+        let grace_period = TimeDelta::days(get_config().names.grace_period_days);
+        let clock = FakeClock::new(name.expires_at - TimeDelta::seconds(1));
+
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert!(report.expired.is_empty());
+
+        clock.advance(TimeDelta::seconds(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.expired, vec!["example".to_owned()]);
+        assert!(report.released.is_empty());
+        assert!(
+            Name::fetch_by_name(&pool, "example")
+                .await?
+                .unwrap()
+                .expired
+        );
+
+        // Expired names can't change hands or records until they are renewed.
+        Wallet::create_wallet(&pool, "kother0000", "hash", None).await?;
+        let expired = Name::fetch_by_name(&pool, "example").await?.unwrap();
+        let result = expired
+            .clone()
+            .transfer_ownership(&pool, &server, "kother0000".to_owned())
+            .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NameExpired(_)))
+        ));
+        let result = expired
+            .update_a_record(&pool, &server, Some("example.com".to_owned()))
+            .await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NameExpired(_)))
+        ));
+        let result =
+            Name::transfer_many(&pool, &server, &["example"], "kowner0000", "kother0000").await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::Name(NameError::NameExpired(_)))
+        ));
+
+        // Still in the grace period, and the expiry is only recorded once.
+        clock.advance(grace_period - TimeDelta::seconds(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert!(report.expired.is_empty());
+        assert!(report.released.is_empty());
+
+        clock.advance(TimeDelta::seconds(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.released, vec!["example".to_owned()]);
+        assert!(Name::fetch_by_name(&pool, "example").await?.is_none());
+
+        assert_eq!(
+            transaction_types(&pool).await,
+            vec![TransactionType::NameExpiry, TransactionType::NameRelease]
+        );
+
+        // Released names can be registered again.
+        Name::create(&pool, "example".to_owned(), "kowner0000".to_owned()).await?;
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_renewal_during_grace_period(pool: Pool<Postgres>) -> Result<()> {
+        let name = setup(&pool).await?;
+        let server = WebSocketServer::new();
+        let config = &get_config().names;
+        let clock = FakeClock::new(name.expires_at + TimeDelta::hours(1));
+
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.expired, vec!["example".to_owned()]);
+
+        let expired = Name::fetch_by_name(&pool, "example").await?.unwrap();
+        let renewed = expired.renew(&pool, &server).await?;
+        assert!(!renewed.expired);
+        assert_eq!(
+            renewed.expires_at,
+            name.expires_at + TimeDelta::days(config.term_days)
+        );
+
+        let owner = Wallet::fetch_by_address(&pool, "kowner0000")
+            .await?
+            .unwrap();
+        assert_eq!(
+            owner.balance,
+            dec!(1000) - Decimal::from(config.renewal_cost)
+        );
+
+        // Well past the old grace period, but the renewed term has not run out yet.
+        clock.advance(TimeDelta::days(config.grace_period_days + 1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert!(report.expired.is_empty());
+        assert!(report.released.is_empty());
+
+        assert_eq!(
+            transaction_types(&pool).await,
+            vec![TransactionType::NameExpiry, TransactionType::NameRenewal]
+        );
+
+        Ok(())
+    }
+}
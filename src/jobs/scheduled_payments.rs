@@ -0,0 +1,370 @@
+use std::time::Duration;
+
+use actix_web::rt::time;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::config::get_config;
+use crate::database::outbox::Model as Outbox;
+use crate::database::payment_schedule::Model as PaymentSchedule;
+use crate::database::payment_schedule_run::Model as PaymentScheduleRun;
+use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
+use crate::database::{DatabaseError, Result};
+use crate::models::krist::websockets::WebSocketEvent;
+use crate::utils::clock::Clock;
+use crate::websockets::WebSocketServer;
+
+/// Most payments made in a single run, the rest wait for the next one.
+const MAX_PAYMENTS_PER_RUN: i64 = 100;
+
+/// What a single run did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaymentsReport {
+    /// The transactions made.
+    pub paid: Vec<i32>,
+    /// Schedules whose payment failed, with the reason.
+    pub failed: Vec<(i32, String)>,
+}
+
+/// Make due scheduled payments forever, checking every `schedules.worker_interval_secs`.
+pub async fn run<C: Clock>(pool: Pool<Postgres>, server: WebSocketServer, clock: C) {
+    let interval_secs = get_config().schedules.worker_interval_secs;
+    let mut interval = time::interval(Duration::from_secs(interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match run_once(&pool, &server, clock.now()).await {
+            Ok(report) if report.paid.is_empty() && report.failed.is_empty() => {}
+            Ok(report) => tracing::info!(
+                "Made {} scheduled payments, {} failed",
+                report.paid.len(),
+                report.failed.len()
+            ),
+            Err(err) => tracing::error!("Failed to make scheduled payments: {err}"),
+        }
+    }
+}
+
+/// Make every scheduled payment that is due at `now` and move each schedule on to its next one.
+///
+/// A payment that fails, e.g. for insufficient funds or a locked wallet, is recorded and not
+/// retried; the schedule carries on with its next payment. Each payment is made and committed in
+/// a transaction of its own. A database error stops the run, and the payment it was making stays
+/// due for the next one. Only one instance does this at a time; the others return an empty
+/// report.
+pub async fn run_once(
+    pool: &Pool<Postgres>,
+    server: &WebSocketServer,
+    now: DateTime<Utc>,
+) -> Result<PaymentsReport> {
+    let mut report = PaymentsReport::default();
+
+    // Held on a connection of its own until the run is over, so payments don't have to share a
+    // transaction with it.
+    let mut worker = pool.begin().await?;
+    if !PaymentSchedule::try_lock_worker(&mut *worker).await? {
+        return Ok(report);
+    }
+
+    for schedule in PaymentSchedule::fetch_due(pool, now, MAX_PAYMENTS_PER_RUN).await? {
+        let mut tx = pool.begin().await?;
+
+        // It may have been cancelled since it was fetched.
+        let Some(schedule) = PaymentSchedule::lock_if_due(&mut *tx, schedule.id, now).await? else {
+            continue;
+        };
+        // Can't be `None` while the schedule is active, it is set from a valid recurrence.
+        let Some(due_at) = schedule.next_run_at else {
+            continue;
+        };
+
+        let creation_data = TransactionCreateData {
+            from: schedule.from.clone(),
+            to: schedule.to.clone(),
+            amount: schedule.amount,
+            metadata: schedule.metadata.clone(),
+            transaction_type: TransactionType::Transfer,
+            ..Default::default()
+        };
+
+        let outcome = match Transaction::create(&mut *tx, creation_data).await {
+            Ok(transaction) => {
+                let event = WebSocketEvent::Transaction {
+                    transaction: transaction.clone().into(),
+                };
+                Outbox::enqueue(&mut *tx, &event).await?;
+
+                Ok(transaction.id)
+            }
+            // Not the schedule's fault, e.g. a deadlock, so it isn't recorded as a failed payment.
+            Err(err @ DatabaseError::Sqlx(_)) => return Err(err),
+            Err(err) => {
+                tracing::debug!("Scheduled payment {} failed: {err}", schedule.id);
+
+                Err(err.to_string())
+            }
+        };
+        PaymentScheduleRun::create(&mut *tx, schedule.id, due_at, outcome.clone()).await?;
+
+        let next_run_at = schedule
+            .recurrence()
+            .and_then(|recurrence| recurrence.next_after(due_at, now));
+        let schedule = schedule.advance(&mut *tx, next_run_at).await?;
+
+        tx.commit().await?;
+        server.notify_outbox();
+
+        match outcome {
+            Ok(transaction_id) => report.paid.push(transaction_id),
+            Err(error) => report.failed.push((schedule.id, error)),
+        }
+    }
+
+    worker.commit().await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::ModelExt;
+    use crate::database::payment_schedule::ScheduleCreateData;
+    use crate::database::wallet::Model as Wallet;
+    use crate::utils::clock::FakeClock;
+    use crate::utils::recurrence::Recurrence;
+    use chrono::{TimeDelta, TimeZone};
+    use futures_util::future::join_all;
+    use rust_decimal::dec;
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+
+    async fn schedule(
+        pool: &Pool<Postgres>,
+        clock: &FakeClock,
+        recurrence: (Option<&str>, Option<i64>),
+        ends_at: Option<DateTime<Utc>>,
+    ) -> PaymentSchedule {
+        let (cron, interval_secs) = recurrence;
+        let next_run_at = Recurrence::from_parts(cron, interval_secs)
+            .unwrap()
+            .next_after(clock.now(), clock.now())
+            .unwrap();
+        let data = ScheduleCreateData {
+            from: "ktenant000".to_owned(),
+            to: "klandlord0".to_owned(),
+            amount: dec!(40),
+            metadata: Some("rent".to_owned()),
+            cron: cron.map(str::to_owned),
+            interval_secs,
+            ends_at,
+        };
+
+        PaymentSchedule::create(pool, data, next_run_at)
+            .await
+            .unwrap()
+    }
+
+    async fn balance(pool: &Pool<Postgres>, address: &str) -> rust_decimal::Decimal {
+        Wallet::fetch_by_address(pool, address)
+            .await
+            .unwrap()
+            .unwrap()
+            .balance
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_interval_payments(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "ktenant000", "hash", Some(dec!(100))).await?;
+        Wallet::create_wallet(&pool, "klandlord0", "hash", None).await?;
+        let server = WebSocketServer::new();
+        let clock = FakeClock::new(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap());
+        let start = clock.now();
+
+        let rent = schedule(&pool, &clock, (None, Some(3600)), None).await;
+        assert_eq!(rent.next_run_at, Some(start + TimeDelta::hours(1)));
+
+        // Nothing is due yet.
+        clock.advance(TimeDelta::minutes(59));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report, PaymentsReport::default());
+
+        clock.advance(TimeDelta::minutes(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.paid.len(), 1);
+        assert_eq!(balance(&pool, "klandlord0").await, dec!(40));
+
+        // Paid once per due time, however often the worker runs.
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert!(report.paid.is_empty());
+
+        clock.advance(TimeDelta::hours(1));
+        run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(balance(&pool, "klandlord0").await, dec!(80));
+
+        // 20 left, so the third payment fails and is recorded, and the schedule carries on.
+        clock.advance(TimeDelta::hours(1));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.failed, [(rent.id, "Insufficient funds".to_owned())]);
+
+        let runs = PaymentScheduleRun::fetch_for_schedule(&pool, rent.id, 10, 0).await?;
+        let outcomes: Vec<_> = runs
+            .iter()
+            .map(|run| {
+                (
+                    run.due_at,
+                    run.transaction_id.is_some(),
+                    run.error.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                (
+                    start + TimeDelta::hours(3),
+                    false,
+                    Some("Insufficient funds")
+                ),
+                (start + TimeDelta::hours(2), true, None),
+                (start + TimeDelta::hours(1), true, None),
+            ]
+        );
+
+        let rent = PaymentSchedule::fetch_by_id(&pool, rent.id).await?.unwrap();
+        assert_eq!(rent.next_run_at, Some(start + TimeDelta::hours(4)));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_cron_payments_end_and_cancel(pool: Pool<Postgres>) -> Result<()> {
+        Wallet::create_wallet(&pool, "ktenant000", "hash", Some(dec!(1000))).await?;
+        let landlord = Wallet::create_wallet(&pool, "klandlord0", "hash", None).await?;
+        let server = WebSocketServer::new();
+        // A Sunday.
+        let clock = FakeClock::new(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap());
+
+        // Every Monday at noon, for two weeks.
+        let ends_at = Utc.with_ymd_and_hms(2026, 10, 26, 12, 0, 0).unwrap();
+        let weekly = schedule(&pool, &clock, (Some("0 12 * * MON"), None), Some(ends_at)).await;
+        let hourly = schedule(&pool, &clock, (None, Some(3600)), None).await;
+
+        // Only the hourly schedule is due, and a locked wallet can't pay.
+        clock.advance(TimeDelta::hours(1));
+        let tenant = Wallet::fetch_by_address(&pool, "ktenant000")
+            .await?
+            .unwrap();
+        tenant
+            .set_locked(&pool, true, "stolen key", "admin")
+            .await?;
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert!(report.paid.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, hourly.id);
+        tenant
+            .set_locked(&pool, false, "key recovered", "admin")
+            .await?;
+
+        let hourly = PaymentSchedule::fetch_by_id(&pool, hourly.id)
+            .await?
+            .unwrap();
+        let cancelled = hourly.cancel(&pool).await?;
+        assert!(!cancelled.is_active());
+        assert!(cancelled.cancelled_at.is_some());
+        assert!(cancelled.clone().cancel(&pool).await.is_err());
+
+        clock.set(Utc.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap());
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.paid.len(), 1);
+
+        clock.set(ends_at);
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report.paid.len(), 1);
+
+        // The next Monday is past the end, so the schedule is finished.
+        let weekly = PaymentSchedule::fetch_by_id(&pool, weekly.id)
+            .await?
+            .unwrap();
+        assert_eq!(weekly.next_run_at, None);
+        assert_eq!(weekly.cancelled_at, None);
+
+        clock.advance(TimeDelta::weeks(4));
+        let report = run_once(&pool, &server, clock.now()).await?;
+        assert_eq!(report, PaymentsReport::default());
+        assert_eq!(balance(&pool, &landlord.address).await, dec!(80));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_payments_alongside_transfers(
+        pool_opts: PgPoolOptions,
+        connect_opts: PgConnectOptions,
+    ) -> Result<()> {
+        let pool = pool_opts
+            .max_connections(16)
+            .connect_with(connect_opts)
+            .await?;
+        let server = WebSocketServer::new();
+        let start = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+
+        // Every run pays between several pairs of wallets, while transfers go the other way.
+        let mut pairs = Vec::new();
+        for i in 0..4 {
+            let payer = format!("kpayer{i:04}");
+            let payee = format!("kpayee{i:04}");
+            Wallet::create_wallet(&pool, &payer, "hash", Some(dec!(1000))).await?;
+            Wallet::create_wallet(&pool, &payee, "hash", Some(dec!(1000))).await?;
+
+            let data = ScheduleCreateData {
+                from: payer.clone(),
+                to: payee.clone(),
+                amount: dec!(1),
+                metadata: None,
+                cron: None,
+                interval_secs: Some(60),
+                ends_at: None,
+            };
+            PaymentSchedule::create(&pool, data, start).await?;
+            pairs.push((payer, payee));
+        }
+
+        let worker = async {
+            let mut reports = Vec::new();
+            for minute in 0..20 {
+                let now = start + TimeDelta::minutes(minute);
+                reports.push(run_once(&pool, &server, now).await?);
+            }
+
+            Ok::<_, DatabaseError>(reports)
+        };
+        let transfers = (0..200).map(|i| {
+            let pool = pool.clone();
+            let (payer, payee) = &pairs[i % pairs.len()];
+            let creation_data = TransactionCreateData {
+                from: payee.clone(),
+                to: payer.clone(),
+                amount: dec!(1),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+
+            tokio::spawn(async move { Transaction::create(&pool, creation_data).await })
+        });
+
+        let (reports, transfers) = tokio::join!(worker, join_all(transfers));
+        for result in transfers {
+            result.expect("transfer task panicked")?;
+        }
+        for report in reports? {
+            assert_eq!(report.failed, []);
+            assert_eq!(report.paid.len(), pairs.len());
+        }
+
+        Ok(())
+    }
+}
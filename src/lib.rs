@@ -1,10 +1,13 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use rust_decimal::Decimal;
 use sqlx::{Pool, Postgres};
 use tokio::sync::OnceCell;
 
+pub mod config;
 pub mod database;
 pub mod errors;
 pub mod guards;
+pub mod jobs;
 pub mod models;
 pub mod routes;
 pub mod utils;
@@ -14,10 +17,8 @@ static ARGS: OnceCell<Args> = OnceCell::const_new();
 /// A mostly Krist-Compatible currency server for ComputerCraft, made by ReconnectedCC. Args override environment variables.
 #[derive(Parser, Debug)]
 #[command(about, long_about = None)]
-
 pub struct Args {
     /// Enable debug mode, prints debug messages to the console
-
     #[arg(short, long)]
     pub debug: bool,
     #[arg(long)]
@@ -32,6 +33,41 @@ pub struct Args {
     /// Force Websocket to use the insecure "ws://" protocol
     #[arg(short, long)]
     pub insecure: bool,
+    /// Path to the TOML config file, defaults to `kromer.toml` if it exists
+    #[arg(long, env = "KROMER_CONFIG")]
+    pub config: Option<std::path::PathBuf>,
+    /// Overrides `server.public_url` from the config file
+    #[arg(long, env = "KROMER_PUBLIC_URL")]
+    pub public_url: Option<String>,
+    /// Overrides `server.public_ws_url` from the config file
+    #[arg(long, env = "KROMER_PUBLIC_WS_URL")]
+    pub public_ws_url: Option<String>,
+    /// Overrides `motd.text` from the config file
+    #[arg(long, env = "KROMER_MOTD")]
+    pub motd: Option<String>,
+    /// Overrides `names.cost` from the config file
+    #[arg(long, env = "KROMER_NAME_COST")]
+    pub name_cost: Option<i64>,
+    /// Overrides `wallets.initial_balance` from the config file
+    #[arg(long, env = "KROMER_INITIAL_BALANCE")]
+    pub initial_balance: Option<Decimal>,
+    /// Overrides `receipts.signing_key` from the config file
+    #[arg(long, env = "KROMER_RECEIPT_KEY", hide_env_values = true)]
+    pub receipt_key: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Things to do instead of starting the server.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Replay the transaction log and report wallets whose balance or totals don't match it.
+    /// Exits with status 1 if anything is off and `--fix` wasn't given
+    Audit {
+        /// Write adjustment transactions for balance differences and correct the totals
+        #[arg(long)]
+        fix: bool,
+    },
 }
 
 pub fn init_args(args: Args) {
@@ -1,12 +1,19 @@
 use actix_cors::Cors;
 use actix_web::{App, HttpServer, middleware, web};
 use clap::Parser;
-use kromer::{AppState, Args, get_args, init_args, routes, websockets::WebSocketServer};
+use kromer::config::{Config, init_config};
+use kromer::database::transaction::Model as Transaction;
+use kromer::jobs::{ledger_audit, name_lifecycle, scheduled_payments};
+use kromer::utils::clock::SystemClock;
+use kromer::utils::receipts::{ReceiptSigner, init_signer};
+use kromer::websockets::{WebSocketServer, bus, outbox};
+use kromer::{AppState, Args, Command, get_args, init_args, routes};
 use sqlx::postgres::PgPool;
 use std::env;
 
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenvy::dotenv().ok();
     let parsed_args = Args::parse();
     init_args(parsed_args);
     let args = get_args();
@@ -18,12 +25,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .init();
         tracing::info!("Debug mode enabled");
     }
-    dotenvy::dotenv().ok();
 
-    let server_url = args.url.clone().unwrap_or_else(|| {
-        env::var("SERVER_URL")
-            .expect("SERVER_URL is not set in .env file or as command line argument (--url)")
-    });
+    let config = Config::load(args)?;
+    // Only the server signs receipts, the audit runs without a key.
+    if args.command.is_none() {
+        config.require_signing_key()?;
+        init_signer(ReceiptSigner::from_config(&config.receipts))?;
+    }
+    init_config(config);
+
     let database_url = args.database_url.clone().unwrap_or_else(|| {
         env::var("DATABASE_URL").expect(
             "DATABASE_URL is not set in .env file or as command line argument (--database_url)",
@@ -36,7 +46,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     sqlx::migrate!("./migrations").run(&pool).await?;
     tracing::info!("Database migrations completed successfully");
 
+    let linked = Transaction::chain_existing(&pool).await?;
+    if linked > 0 {
+        tracing::info!("Linked {linked} existing transactions into the hash chain");
+    }
+
+    if let Some(Command::Audit { fix }) = args.command {
+        let report = ledger_audit::run(&pool, fix).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        if !fix && !report.is_clean() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let server_url = args.url.clone().unwrap_or_else(|| {
+        env::var("SERVER_URL")
+            .expect("SERVER_URL is not set in .env file or as command line argument (--url)")
+    });
+
     let krist_ws_server = WebSocketServer::new();
+    let listener = bus::listen(&pool, &krist_ws_server).await?;
+    actix_web::rt::spawn(bus::run_listener(
+        listener,
+        pool.clone(),
+        krist_ws_server.clone(),
+    ));
+    actix_web::rt::spawn(outbox::run_dispatcher(
+        pool.clone(),
+        krist_ws_server.clone(),
+    ));
+    actix_web::rt::spawn(name_lifecycle::run(
+        pool.clone(),
+        krist_ws_server.clone(),
+        SystemClock,
+    ));
+    actix_web::rt::spawn(scheduled_payments::run(
+        pool.clone(),
+        krist_ws_server.clone(),
+        SystemClock,
+    ));
     let state = web::Data::new(AppState { pool });
 
     let http_server = HttpServer::new(move || {
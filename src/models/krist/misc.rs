@@ -19,3 +19,12 @@ pub struct PrivateKeyAddressResponse {
     pub ok: bool,
     pub address: String,
 }
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ReceiptKeysResponse {
+    pub ok: bool,
+    /// The hex ed25519 public key new receipts are signed with.
+    pub key: String,
+    /// Earlier keys whose receipts are still valid.
+    pub retired_keys: Vec<String>,
+}
@@ -5,6 +5,7 @@ pub mod error;
 pub mod misc;
 pub mod motd;
 pub mod names;
+pub mod schedules;
 pub mod transactions;
 pub mod webserver;
 pub mod websockets;
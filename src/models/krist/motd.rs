@@ -1,14 +1,9 @@
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-pub const MINING_CONSTANTS: Constants = Constants {
-    wallet_version: 16,
-    nonce_max_size: 24,
-    name_cost: 500,
-    min_work: 1,
-    max_work: 100000,
-    work_factor: 0.025,
-    seconds_per_block: 300,
-};
+use crate::config::{Config, CurrencyConfig};
+use crate::database::motd;
+use crate::websockets::types::convert_to_iso_string;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DetailedMotdResponse {
@@ -76,12 +71,104 @@ pub struct CurrencyInfo {
     pub currency_symbol: String,
 }
 
-#[inline]
-pub fn get_currency_info() -> CurrencyInfo {
-    CurrencyInfo {
-        address_prefix: "k".to_string(),
-        name_suffix: "kro".to_string(),
-        currency_name: "Kromer".to_string(),
-        currency_symbol: "KRO".to_string(),
+impl DetailedMotd {
+    /// Render the MOTD from the server configuration, `current` is the MOTD set through the
+    /// internal API and takes precedence over the configured text.
+    pub fn new(config: &Config, current: Option<motd::Model>) -> Self {
+        let (motd, motd_set) = match current {
+            Some(current) => (current.motd, Some(convert_to_iso_string(current.set_at))),
+            None => (config.motd.text.clone(), None),
+        };
+
+        Self {
+            server_time: convert_to_iso_string(Utc::now()),
+            motd,
+            set: motd_set.clone(),
+            motd_set,
+            public_url: config.server.public_url.clone(),
+            public_ws_url: config.server.public_ws_url.clone(),
+            mining_enabled: false,
+            transactions_enabled: true,
+            debug_mode: config.server.debug_mode,
+            work: 500,
+            last_block: None,
+            package: PackageInfo::default(),
+            constants: Constants::new(config.names.cost),
+            currency: (&config.currency).into(),
+            notice: config.motd.notice.clone(),
+        }
+    }
+}
+
+impl Motd {
+    pub fn new(config: &Config, current: motd::Model) -> Self {
+        Self {
+            motd: current.motd,
+            motd_set: convert_to_iso_string(current.set_at),
+            debug_mode: Some(config.server.debug_mode),
+        }
+    }
+}
+
+impl Default for PackageInfo {
+    fn default() -> Self {
+        Self {
+            name: crate::build_info::PKG_NAME.to_string(),
+            version: crate::build_info::PKG_VERSION.to_string(),
+            author: "ReconnectedCC Team".to_string(),
+            license: "GPL-3.0".to_string(),
+            repository: "https://github.com/ReconnectedCC/kromer/".to_string(),
+            git_hash: crate::build_info::GIT_COMMIT_HASH.map(|s| s.to_string()),
+        }
+    }
+}
+
+impl Constants {
+    /// Kromer has no mining, so everything but the name cost keeps Krist's values.
+    pub fn new(name_cost: i64) -> Self {
+        Self {
+            wallet_version: 16,
+            nonce_max_size: 24,
+            name_cost,
+            min_work: 1,
+            max_work: 100000,
+            work_factor: 0.025,
+            seconds_per_block: 300,
+        }
+    }
+}
+
+impl From<&CurrencyConfig> for CurrencyInfo {
+    fn from(currency: &CurrencyConfig) -> Self {
+        Self {
+            address_prefix: "k".to_string(),
+            name_suffix: "kro".to_string(),
+            currency_name: currency.currency_name.clone(),
+            currency_symbol: currency.currency_symbol.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_motd_overrides_config() {
+        let config = Config::default();
+
+        let motd = DetailedMotd::new(&config, None);
+        assert_eq!(motd.motd, config.motd.text);
+        assert_eq!(motd.motd_set, None);
+
+        let current = motd::Model {
+            id: 1,
+            motd: "Maintenance at noon".to_string(),
+            set_at: "2026-10-17T12:00:00Z".parse().unwrap(),
+        };
+        let motd = DetailedMotd::new(&config, Some(current));
+        assert_eq!(motd.motd, "Maintenance at noon");
+        assert_eq!(motd.motd_set.as_deref(), Some("2026-10-17T12:00:00.000Z"));
+        assert_eq!(motd.set, motd.motd_set);
     }
 }
@@ -1,6 +1,10 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 use serde::{Deserialize, Serialize};
 
-use crate::database::name;
+use crate::database::{name, name_listing, name_route};
+use crate::utils::idn;
+use crate::utils::name_pricing::NamePrice;
 // use utoipa::ToResponse;
 
 // use crate::database::models::name;
@@ -25,6 +29,9 @@ pub struct NameResponse {
 pub struct NameCostResponse {
     pub ok: bool,
     pub name_cost: i64,
+    /// How the cost of a specific name was worked out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<NamePrice>,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -60,6 +67,73 @@ pub struct TransferNameRequest {
     pub private_key: String,
 }
 
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct BulkTransferNameRequest {
+    /// The names to transfer, either all of them are transferred or none are.
+    pub names: Vec<String>,
+    pub address: String,
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct BulkTransferNameResponse {
+    pub ok: bool,
+    pub names: Vec<NameJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ListNameRequest {
+    /// The price the name is sold for.
+    pub price: Decimal,
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct UnlistNameRequest {
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct RenewNameRequest {
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct BuyNameRequest {
+    /// The price the buyer agrees to pay, the purchase fails if the listing has a different price.
+    pub price: Decimal,
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct NameListingJson {
+    pub name: String,
+    pub seller: String,
+    pub price: Decimal,
+    pub listed: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct NameListingResponse {
+    pub ok: bool,
+    pub listing: NameListingJson,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct NameListingListResponse {
+    pub ok: bool,
+    /// The count of results.
+    pub count: usize,
+    /// The total amount of listings
+    pub total: usize,
+    pub listings: Vec<NameListingJson>,
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct NameDataUpdateBody {
     /// The data you want to set for the name.
@@ -69,27 +143,120 @@ pub struct NameDataUpdateBody {
     pub private_key: String,
 }
 
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct SetNameRouteRequest {
+    /// The metaname to route, or `None` to set the fallback for metanames without a route.
+    pub metaname: Option<String>,
+    pub address: String,
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct RemoveNameRouteRequest {
+    /// The metaname to stop routing, or `None` to remove the fallback.
+    pub metaname: Option<String>,
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct NameRouteJson {
+    pub metaname: Option<String>,
+    pub address: String,
+    pub created: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct NameRoutesResponse {
+    pub ok: bool,
+    pub name: String,
+    pub routes: Vec<NameRouteJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct SetNameRefundsRequest {
+    /// Refund payments to metanames that are not in `allowed_metanames`.
+    pub auto_refund: bool,
+    #[serde(default)]
+    pub allowed_metanames: Vec<String>,
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct NameRefundsResponse {
+    pub ok: bool,
+    pub name: String,
+    pub auto_refund: bool,
+    pub allowed_metanames: Vec<String>,
+}
+
+impl From<name::Model> for NameRefundsResponse {
+    fn from(name: name::Model) -> Self {
+        Self {
+            ok: true,
+            name: name.name,
+            auto_refund: name.auto_refund,
+            allowed_metanames: name.metaname_allowlist,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct NameJson {
+    /// The name as stored, punycode-encoded if it is internationalized.
     pub name: String,
+    /// The name as it should be displayed, the same as `name` for plain ASCII names.
+    pub unicode_name: String,
     pub owner: String,
     pub original_owner: Option<String>,
     pub registered: String,
     pub updated: Option<String>,
     pub transfered: Option<String>,
+    pub a: Option<String>,
     pub unpaid: i64,
+    /// When the name has to be renewed by.
+    pub expires: String,
+    /// Whether the name is in its grace period, it is released if not renewed in time.
+    pub expired: bool,
 }
 
 impl From<name::Model> for NameJson {
     fn from(name: name::Model) -> Self {
         Self {
+            unicode_name: idn::to_unicode(&name.name).unwrap_or_else(|| name.name.clone()),
             name: name.name,
             owner: name.owner,
             original_owner: Some(name.original_owner),
             registered: name.time_registered.to_rfc3339(),
-            updated: None,    // TODO: Populate this.
-            transfered: None, // TODO: Populate this
-            unpaid: 0,
+            updated: name.last_updated.map(|time| time.to_rfc3339()),
+            transfered: name.last_transfered.map(|time| time.to_rfc3339()),
+            a: name.metadata,
+            unpaid: name.unpaid.to_i64().unwrap_or_default(),
+            expires: name.expires_at.to_rfc3339(),
+            expired: name.expired,
+        }
+    }
+}
+
+impl From<name_route::Model> for NameRouteJson {
+    fn from(route: name_route::Model) -> Self {
+        Self {
+            metaname: route.metaname,
+            address: route.address,
+            created: route.created_at.to_rfc3339(),
+        }
+    }
+}
+
+impl From<name_listing::Model> for NameListingJson {
+    fn from(listing: name_listing::Model) -> Self {
+        Self {
+            name: listing.name,
+            seller: listing.seller,
+            price: listing.price,
+            listed: listing.created_at.to_rfc3339(),
         }
     }
 }
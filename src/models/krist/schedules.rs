@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::database::{payment_schedule, payment_schedule_run};
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct CreateScheduleRequest {
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+    /// The address to pay.
+    pub to: String,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    /// A five field cron expression in UTC, see [`crate::utils::recurrence`]. Exactly one of
+    /// `cron` and `interval_secs` must be given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    /// Pay every this many seconds, at least a minute and at most a year, the first time one
+    /// interval from now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<i64>,
+    /// Stop paying after this time, as an ISO-8601 string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct CancelScheduleRequest {
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleStatus {
+    Active,
+    Finished,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ScheduleJson {
+    pub id: i32,
+    pub from: String,
+    pub to: String,
+    pub value: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<i64>,
+    pub status: ScheduleStatus,
+    /// When the next payment is due, only while the schedule is active.
+    pub next_run: Option<String>,
+    pub ends: Option<String>,
+    pub created: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancelled: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ScheduleRunJson {
+    /// When the payment was due.
+    pub due: String,
+    /// The transaction made, absent if the payment failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<i32>,
+    /// Why the payment failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ScheduleResponse {
+    pub ok: bool,
+    pub schedule: ScheduleJson,
+    /// The latest payments, newest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<ScheduleRunJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ScheduleListResponse {
+    pub ok: bool,
+    /// The count of results.
+    pub count: usize,
+    /// The total amount of schedules
+    pub total: usize,
+    pub schedules: Vec<ScheduleJson>,
+}
+
+impl From<payment_schedule::Model> for ScheduleJson {
+    fn from(schedule: payment_schedule::Model) -> Self {
+        let status = match (schedule.next_run_at, schedule.cancelled_at) {
+            (_, Some(_)) => ScheduleStatus::Cancelled,
+            (Some(_), None) => ScheduleStatus::Active,
+            (None, None) => ScheduleStatus::Finished,
+        };
+
+        Self {
+            id: schedule.id,
+            from: schedule.from,
+            to: schedule.to,
+            value: schedule.amount,
+            metadata: schedule.metadata,
+            cron: schedule.cron,
+            interval_secs: schedule.interval_secs,
+            status,
+            next_run: schedule.next_run_at.map(|time| time.to_rfc3339()),
+            ends: schedule.ends_at.map(|time| time.to_rfc3339()),
+            created: schedule.created_at.to_rfc3339(),
+            cancelled: schedule.cancelled_at.map(|time| time.to_rfc3339()),
+        }
+    }
+}
+
+impl From<payment_schedule_run::Model> for ScheduleRunJson {
+    fn from(run: payment_schedule_run::Model) -> Self {
+        Self {
+            due: run.due_at.to_rfc3339(),
+            transaction_id: run.transaction_id,
+            error: run.error,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 // use utoipa::{
@@ -6,7 +8,8 @@ use serde::{Deserialize, Serialize};
 // };
 
 use crate::database::transaction::{self, TransactionType};
-// use transaction::TransactionNameData;
+use crate::utils::common_meta::CommonMeta;
+use crate::utils::receipts::{self, Receipt, ReceiptData};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct TransactionListResponse {
@@ -31,10 +34,49 @@ pub struct TransactionDetails {
     pub metadata: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchTransactionDetails {
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+    pub transfers: Vec<transaction::BatchTransfer>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct BatchTransactionResponse {
+    pub ok: bool,
+    /// One transaction per transfer, in the order they were sent.
+    pub transactions: Vec<TransactionJson>,
+    /// Refunds made straight away for transfers to metanames their name does not accept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refunds: Vec<TransactionJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainHeadResponse {
+    pub ok: bool,
+    /// `None` until the first transaction is made.
+    pub head: Option<transaction::ChainHead>,
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct TransactionResponse {
     pub ok: bool,
     pub transaction: TransactionJson,
+    /// The refund made straight away because the name does not accept the metaname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refund: Option<TransactionJson>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct RefundRequest {
+    #[serde(rename = "privatekey")]
+    pub private_key: String,
+    /// How much to refund, everything that is left by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Decimal>,
+    /// Added to the metadata of the refund as `message=`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -46,6 +88,24 @@ pub struct AddressTransactionQuery {
     pub include_mined: Option<bool>,
 }
 
+/// Query parameter to opt in to the parsed CommonMeta on returned transactions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetaQuery {
+    #[serde(alias = "includeMeta")]
+    pub include_meta: Option<bool>,
+}
+
+impl MetaQuery {
+    pub fn apply(&self, transaction: impl Into<TransactionJson>) -> TransactionJson {
+        let transaction = transaction.into();
+
+        match self.include_meta {
+            Some(true) => transaction.with_meta(),
+            _ => transaction,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct TransactionJson {
     /// The ID of this transaction.
@@ -74,12 +134,46 @@ pub struct TransactionJson {
     pub sent_name: Option<String>,
     #[serde(rename = "type")]
     pub transaction_type: TransactionType,
+    /// The transaction this one refunds or reverses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_id: Option<i32>,
+    /// The hash of the transaction before this one, see [`transaction::Model::chain_hash`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<String>,
+    /// The hash linking this transaction into the chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+
+    /// The metadata parsed as CommonMeta, only present when the client asked for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<BTreeMap<String, String>>,
+
+    /// A receipt signed by the server, see [`receipts`] for how to verify it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Box<Receipt>>,
+}
+
+impl TransactionJson {
+    /// Fill in `meta` from the raw metadata, if there is any.
+    pub fn with_meta(mut self) -> Self {
+        self.meta = self
+            .metadata
+            .as_deref()
+            .map(|metadata| CommonMeta::parse(metadata).to_fields());
+        self
+    }
+
+    /// Sign a receipt for this transaction with the server's current key.
+    pub fn with_receipt(mut self) -> Self {
+        self.receipt = Some(Box::new(
+            receipts::get_signer().sign(&ReceiptData::from(&self)),
+        ));
+        self
+    }
 }
 
 impl From<transaction::Model> for TransactionJson {
     fn from(transaction: transaction::Model) -> Self {
-        // let name_data = TransactionNameData::parse_opt_ref(&transaction.metadata);
-
         Self {
             id: transaction.id,
             from: transaction.from,
@@ -91,6 +185,57 @@ impl From<transaction::Model> for TransactionJson {
             sent_name: transaction.sent_name,
             transaction_type: transaction.transaction_type,
             name: transaction.name,
+            original_id: transaction.original_id,
+            prev_hash: transaction.prev_hash,
+            hash: transaction.hash,
+            meta: None,
+            receipt: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use rust_decimal::dec;
+
+    fn transaction(metadata: Option<&str>) -> transaction::Model {
+        transaction::Model {
+            id: 1,
+            amount: dec!(5),
+            from: Some("kaaaaaaaaa".to_owned()),
+            to: "kbbbbbbbbb".to_owned(),
+            metadata: metadata.map(str::to_owned),
+            name: None,
+            sent_metaname: None,
+            sent_name: None,
+            transaction_type: TransactionType::Transfer,
+            date: Utc::now(),
+            idempotency_key: None,
+            request_hash: None,
+            original_id: None,
+            prev_hash: None,
+            hash: None,
         }
     }
+
+    #[test]
+    fn test_meta_is_opt_in() {
+        let model = transaction(Some("shop@store.kro;message=hi"));
+
+        let json = serde_json::to_value(MetaQuery::default().apply(model.clone())).unwrap();
+        assert!(json.get("meta").is_none());
+
+        let query = MetaQuery {
+            include_meta: Some(true),
+        };
+        let json = serde_json::to_value(query.apply(model)).unwrap();
+        assert_eq!(json["meta"]["recipient"], "shop@store.kro");
+        assert_eq!(json["meta"]["metaname"], "shop");
+        assert_eq!(json["meta"]["message"], "hi");
+
+        let json = serde_json::to_value(query.apply(transaction(None))).unwrap();
+        assert!(json.get("meta").is_none());
+    }
 }
@@ -2,8 +2,15 @@ pub mod addresses;
 pub mod names;
 pub mod transactions;
 
+use serde::de::{DeserializeOwned, IntoDeserializer, value::StrDeserializer};
 use serde::{Deserialize, Serialize};
 
+use crate::errors::krist::generic::GenericError;
+
+/// The most results a single lookup may return.
+pub const LOOKUP_MAX_LIMIT: i64 = 1000;
+const LOOKUP_DEFAULT_LIMIT: i64 = 50;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BlockLookupFields {
@@ -15,9 +22,10 @@ pub enum BlockLookupFields {
     Difficulty,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TransactionLookupFields {
+    #[default]
     Id,
     From,
     To,
@@ -27,15 +35,17 @@ pub enum TransactionLookupFields {
     SentMetaname,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum NameLookupFields {
+    #[default]
     Name,
     Owner,
     #[serde(rename = "original_owner")]
     OriginalOwner,
     Registered,
     Updated,
+    #[serde(alias = "transferred")]
     Transfered,
     #[serde(rename = "transferredOrRegistered")]
     TransferedOrRegistered,
@@ -43,11 +53,185 @@ pub enum NameLookupFields {
     Unpaid,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+impl TransactionLookupFields {
+    /// The column to sort by, KristWeb calls the `date` column `time`.
+    pub fn column(&self) -> &'static str {
+        match self {
+            TransactionLookupFields::Id => "id",
+            TransactionLookupFields::From => r#""from""#,
+            TransactionLookupFields::To => r#""to""#,
+            TransactionLookupFields::Value => "amount",
+            TransactionLookupFields::Time => "date",
+            TransactionLookupFields::SentName => "sent_name",
+            TransactionLookupFields::SentMetaname => "sent_metaname",
+        }
+    }
+}
+
+impl NameLookupFields {
+    /// The column (or expression) to sort by.
+    pub fn column(&self) -> &'static str {
+        match self {
+            NameLookupFields::Name => "name",
+            NameLookupFields::Owner => "owner",
+            NameLookupFields::OriginalOwner => "original_owner",
+            NameLookupFields::Registered => "time_registered",
+            NameLookupFields::Updated => "last_updated",
+            NameLookupFields::Transfered => "last_transfered",
+            NameLookupFields::TransferedOrRegistered => {
+                "COALESCE(last_transfered, time_registered)"
+            }
+            NameLookupFields::A => "metadata",
+            NameLookupFields::Unpaid => "unpaid",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum LookupOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl LookupOrder {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            LookupOrder::Asc => "ASC",
+            LookupOrder::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LookupQuery {
     pub limit: Option<String>,
     pub offset: Option<String>,
     pub order_by: Option<String>,
     pub order: Option<String>,
+    pub include_mined: Option<bool>,
+}
+
+/// A validated [`LookupQuery`], sorting by the fields in `F`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupParams<F> {
+    pub limit: i64,
+    pub offset: i64,
+    pub order_by: F,
+    pub order: LookupOrder,
+    pub include_mined: bool,
+}
+
+impl LookupQuery {
+    pub fn parse<F>(&self) -> Result<LookupParams<F>, GenericError>
+    where
+        F: DeserializeOwned + Default,
+    {
+        let limit = match &self.limit {
+            Some(limit) => match limit.parse::<i64>() {
+                Ok(limit) if limit > 0 => limit.min(LOOKUP_MAX_LIMIT),
+                _ => return Err(GenericError::InvalidParameter("limit".to_string())),
+            },
+            None => LOOKUP_DEFAULT_LIMIT,
+        };
+
+        let offset = match &self.offset {
+            Some(offset) => match offset.parse::<i64>() {
+                Ok(offset) if offset >= 0 => offset,
+                _ => return Err(GenericError::InvalidParameter("offset".to_string())),
+            },
+            None => 0,
+        };
+
+        let order_by = match &self.order_by {
+            Some(order_by) => parse_field(order_by)
+                .ok_or_else(|| GenericError::InvalidParameter("orderBy".to_string()))?,
+            None => F::default(),
+        };
+
+        let order = match &self.order {
+            Some(order) => parse_field(&order.to_uppercase())
+                .ok_or_else(|| GenericError::InvalidParameter("order".to_string()))?,
+            None => LookupOrder::default(),
+        };
+
+        Ok(LookupParams {
+            limit,
+            offset,
+            order_by,
+            order,
+            include_mined: self.include_mined.unwrap_or(false),
+        })
+    }
+}
+
+/// Parse a query string value with the serde names of `F`.
+fn parse_field<F: DeserializeOwned>(value: &str) -> Option<F> {
+    let deserializer: StrDeserializer<serde::de::value::Error> = value.into_deserializer();
+    F::deserialize(deserializer).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(order_by: &str, order: &str) -> LookupQuery {
+        LookupQuery {
+            order_by: Some(order_by.to_string()),
+            order: Some(order.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_lookup_query() {
+        let params = query("time", "desc")
+            .parse::<TransactionLookupFields>()
+            .unwrap();
+        assert_eq!(params.order_by, TransactionLookupFields::Time);
+        assert_eq!(params.order_by.column(), "date");
+        assert_eq!(params.order, LookupOrder::Desc);
+        assert_eq!(params.limit, 50);
+
+        let params = query("transferredOrRegistered", "ASC")
+            .parse::<NameLookupFields>()
+            .unwrap();
+        assert_eq!(params.order_by, NameLookupFields::TransferedOrRegistered);
+
+        let defaults = LookupQuery::default().parse::<NameLookupFields>().unwrap();
+        assert_eq!(defaults.order_by, NameLookupFields::Name);
+        assert_eq!(defaults.order, LookupOrder::Asc);
+    }
+
+    #[test]
+    fn test_parse_lookup_query_rejects_invalid_values() {
+        let invalid = |query: LookupQuery| query.parse::<TransactionLookupFields>().unwrap_err();
+
+        assert!(matches!(
+            invalid(query("date", "asc")),
+            GenericError::InvalidParameter(param) if param == "orderBy"
+        ));
+        assert!(matches!(
+            invalid(query("id", "sideways")),
+            GenericError::InvalidParameter(param) if param == "order"
+        ));
+
+        let bad_limit = LookupQuery {
+            limit: Some("-1".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            invalid(bad_limit),
+            GenericError::InvalidParameter(param) if param == "limit"
+        ));
+
+        let huge_limit = LookupQuery {
+            limit: Some("5000".to_string()),
+            ..Default::default()
+        };
+        let params = huge_limit.parse::<TransactionLookupFields>().unwrap();
+        assert_eq!(params.limit, LOOKUP_MAX_LIMIT);
+    }
 }
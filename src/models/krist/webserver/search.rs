@@ -2,8 +2,12 @@ use crate::models::krist::addresses::AddressJson;
 use crate::models::krist::blocks::BlockJson;
 use crate::models::krist::names::NameJson;
 use crate::models::krist::transactions::TransactionJson;
+use crate::utils::validation;
 use serde::{Deserialize, Serialize};
 
+/// Longest search query we accept.
+pub const SEARCH_QUERY_MAX_LENGTH: usize = 256;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReqSearchQuery {
@@ -26,13 +30,44 @@ pub struct SearchQueryMatch {
     pub stripped_name: String,
     #[serde(rename = "hasID")]
     pub has_id: bool,
-    #[serde(rename = "cleanID")]
+    #[serde(rename = "cleanID", skip_serializing_if = "Option::is_none")]
     pub clean_id: Option<i64>,
 }
 
+impl SearchQueryMatch {
+    /// Work out what the query could refer to, the same way Krist does.
+    pub fn parse(query: &str) -> Self {
+        let lowercase = query.to_lowercase();
+        let stripped_name = validation::strip_name_suffix(&lowercase);
+        // Unicode names are searched for in the form they are stored in.
+        let name = validation::normalize_name(&stripped_name);
+
+        // Only a query that is a number once punctuation is gone (e.g. `#1234`) is an id.
+        let word: String = query
+            .trim()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let clean_id = word.parse::<i64>().ok();
+        let has_id = clean_id.is_some();
+
+        Self {
+            original_query: query.to_string(),
+            match_address: validation::is_valid_kromer_address(&lowercase),
+            match_block: false, // Kromer has no blocks.
+            match_name: name.is_some(),
+            match_transaction: has_id,
+            stripped_name: name.unwrap_or(stripped_name),
+            has_id,
+            clean_id,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResult {
+    pub ok: bool,
     pub query: SearchQueryMatch,
     pub matches: SearchResultMatches,
 }
@@ -49,7 +84,8 @@ pub struct SearchResultMatches {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchExtendedResult {
-    pub query: SearchResult,
+    pub ok: bool,
+    pub query: SearchQueryMatch,
     pub matches: SearchExtendedResultMatches,
 }
 
@@ -62,7 +98,57 @@ pub struct SearchExtendedResultMatches {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchExtendedResultTransactions {
-    pub address_involved: Option<f64>,
-    pub name_involved: Option<f64>,
-    pub metadata: Option<f64>,
+    pub address_involved: Option<usize>,
+    pub name_involved: Option<usize>,
+    pub metadata: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search_query() {
+        let address = SearchQueryMatch::parse("kaaaaaaaaa");
+        assert!(address.match_address);
+        assert!(address.match_name);
+        assert!(!address.has_id);
+
+        let name = SearchQueryMatch::parse("Shop.kro");
+        assert!(!name.match_address);
+        assert!(name.match_name);
+        assert_eq!(name.stripped_name, "shop");
+
+        let unicode = SearchQueryMatch::parse("名前.kro");
+        assert!(unicode.match_name);
+        assert_eq!(unicode.stripped_name, "xn--ldr85b");
+
+        let id = SearchQueryMatch::parse("#1234");
+        assert!(id.match_transaction);
+        assert_eq!(id.clean_id, Some(1234));
+        assert!(!id.match_name);
+
+        let padded = SearchQueryMatch::parse(" 42 ");
+        assert_eq!(padded.clean_id, Some(42));
+
+        // Digits mixed with letters are not an id.
+        let mixed = SearchQueryMatch::parse("shop42");
+        assert!(!mixed.has_id);
+        assert!(!mixed.match_transaction);
+        assert_eq!(mixed.clean_id, None);
+        assert!(mixed.match_name);
+
+        let address = SearchQueryMatch::parse("k1234abcd5");
+        assert!(address.match_address);
+        assert!(!address.match_transaction);
+        assert_eq!(address.clean_id, None);
+
+        assert_eq!(SearchQueryMatch::parse("12_3").clean_id, None);
+
+        let metadata = SearchQueryMatch::parse("hello world");
+        assert!(!metadata.match_address);
+        assert!(!metadata.match_name);
+        assert!(!metadata.match_transaction);
+        assert_eq!(metadata.clean_id, None);
+    }
 }
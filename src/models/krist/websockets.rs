@@ -1,6 +1,8 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
+use crate::database::transaction::BatchTransfer;
+
 use super::{addresses::AddressJson, motd::DetailedMotd, transactions::TransactionJson};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -32,6 +34,9 @@ pub enum WebSocketMessageInner {
         message: String,
     },
     Event {
+        /// Monotonically increasing id of the event, taken from the outbox it was sent from.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        event_id: Option<i64>,
         #[serde(flatten)]
         event: WebSocketEvent,
     },
@@ -49,6 +54,18 @@ pub enum WebSocketMessageInner {
 
         /// Optional metadata to include in the transaction.
         metadata: Option<String>,
+
+        /// Optional client-chosen key, retrying with the same key returns the original transaction.
+        #[serde(alias = "idempotencyKey")]
+        idempotency_key: Option<String>,
+    },
+    MakeBatchTransaction {
+        /// The privatekey of your address, the logged in address is used without it.
+        #[serde(rename = "privatekey")]
+        private_key: Option<String>,
+
+        /// Every transfer to make, all of them are made or none.
+        transfers: Vec<BatchTransfer>,
     },
 
     GetValidSubscriptionLevels,
@@ -88,6 +105,16 @@ pub enum WebSocketMessageResponse {
 
     MakeTransaction {
         transaction: TransactionJson,
+        /// The refund made straight away because the name does not accept the metaname.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        refund: Option<Box<TransactionJson>>,
+    },
+
+    MakeBatchTransaction {
+        transactions: Vec<TransactionJson>,
+        /// Refunds made straight away for transfers to metanames their name does not accept.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        refunds: Vec<TransactionJson>,
     },
 
     GetValidSubscriptionLevels {
@@ -143,6 +170,9 @@ pub enum WebSocketEvent {
     Name {
         name: super::names::NameJson,
     },
+    Motd {
+        motd: super::motd::Motd,
+    },
 }
 
 impl WebSocketMessage {
@@ -150,7 +180,21 @@ impl WebSocketMessage {
         WebSocketMessage {
             ok: None,
             id: None,
-            r#type: WebSocketMessageInner::Event { event },
+            r#type: WebSocketMessageInner::Event {
+                event_id: None,
+                event,
+            },
+        }
+    }
+
+    pub fn new_outbox_event(event_id: i64, event: WebSocketEvent) -> WebSocketMessage {
+        WebSocketMessage {
+            ok: None,
+            id: None,
+            r#type: WebSocketMessageInner::Event {
+                event_id: Some(event_id),
+                event,
+            },
         }
     }
 }
@@ -216,6 +260,7 @@ impl WebSocketMessageInner {
             WebSocketMessageInner::GetValidSubscriptionLevels => "get_valid_subscription_levels",
             WebSocketMessageInner::Unsubscribe { .. } => "unsubscribe",
             WebSocketMessageInner::MakeTransaction { .. } => "make_transaction",
+            WebSocketMessageInner::MakeBatchTransaction { .. } => "make_batch_transaction",
             WebSocketMessageInner::Work => "work",
             WebSocketMessageInner::Hello { .. } => "hello",
             WebSocketMessageInner::Error { .. } => "error",
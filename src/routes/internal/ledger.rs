@@ -0,0 +1,30 @@
+use actix_web::{HttpResponse, get, post, web};
+
+use crate::jobs::ledger_audit;
+use crate::{AppState, errors::KromerError};
+
+#[get("/audit")]
+async fn audit_report(state: web::Data<AppState>) -> Result<HttpResponse, KromerError> {
+    let report = ledger_audit::run(&state.pool, false).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[post("/audit/fix")]
+async fn audit_fix(state: web::Data<AppState>) -> Result<HttpResponse, KromerError> {
+    let report = ledger_audit::run(&state.pool, true).await?;
+    tracing::info!(
+        "Ledger audit corrected {} wallets",
+        report.discrepancies.len()
+    );
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/ledger")
+            .service(audit_report)
+            .service(audit_fix),
+    );
+}
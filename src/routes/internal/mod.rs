@@ -1,9 +1,17 @@
+pub mod ledger;
+pub mod motd;
+pub mod names;
+pub mod transactions;
 pub mod wallet;
 pub mod ws;
 
 use actix_web::web;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.configure(ledger::config);
+    cfg.configure(motd::config);
+    cfg.configure(names::config);
+    cfg.configure(transactions::config);
     cfg.configure(wallet::config);
     cfg.configure(ws::config);
 }
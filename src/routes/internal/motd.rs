@@ -0,0 +1,54 @@
+use actix_web::{HttpResponse, post, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::get_config;
+use crate::database::motd::{MOTD_MAX_LENGTH, Model as Motd};
+use crate::database::outbox::Model as Outbox;
+use crate::models::krist::motd::Motd as MotdJson;
+use crate::models::krist::websockets::WebSocketEvent;
+use crate::websockets::WebSocketServer;
+use crate::{AppState, errors::KromerError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetMotdReq {
+    pub motd: String,
+}
+
+#[post("")]
+async fn motd_set(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    data: web::Json<SetMotdReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let data = data.into_inner();
+    let motd = data.motd.trim();
+
+    if motd.chars().count() > MOTD_MAX_LENGTH {
+        return Err(KromerError::Validation(format!(
+            "MOTD must not be longer than {MOTD_MAX_LENGTH} characters"
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let current = Motd::set(&mut *tx, motd).await?;
+    let motd = MotdJson::new(get_config(), current);
+
+    let event = WebSocketEvent::Motd { motd: motd.clone() };
+    Outbox::enqueue(&mut *tx, &event).await?;
+
+    tx.commit().await?;
+    server.notify_outbox();
+
+    tracing::info!("MOTD was changed to {:?}", motd.motd);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "motd": motd
+    })))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/motd").service(motd_set));
+}
@@ -0,0 +1,245 @@
+use actix_web::{HttpResponse, delete, get, post, put, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::database::ModelExt;
+use crate::database::name::Model as Name;
+use crate::database::name_price_override::Model as NamePriceOverride;
+use crate::database::reserved_name::Model as ReservedName;
+use crate::errors::name::NameError;
+use crate::models::krist::names::NameJson;
+use crate::routes::PaginationParams;
+use crate::utils::validation;
+use crate::websockets::WebSocketServer;
+use crate::{AppState, errors::KromerError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetPriceReq {
+    pub cost: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ReserveNameReq {
+    /// A name, optionally with `*` and `?` wildcards.
+    pub pattern: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ForceTransferReq {
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RevokeNameReq {
+    pub reason: Option<String>,
+}
+
+fn parse_name(name: String) -> Result<String, KromerError> {
+    validation::normalize_name(&name).ok_or_else(|| KromerError::Validation("Invalid name".into()))
+}
+
+#[get("/prices")]
+async fn price_list(
+    state: web::Data<AppState>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+
+    let overrides = NamePriceOverride::fetch_all(pool, limit, offset).await?;
+    let total = NamePriceOverride::total_count(pool).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "total": total,
+        "overrides": overrides
+    })))
+}
+
+#[put("/prices/{name}")]
+async fn price_set(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    data: web::Json<SetPriceReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let name = parse_name(name.into_inner())?;
+
+    if data.cost < 0 {
+        return Err(KromerError::Validation("Invalid cost".into()));
+    }
+
+    let price_override = NamePriceOverride::upsert(pool, &name, data.cost).await?;
+    tracing::info!("Price of name {name} was set to {}", data.cost);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "override": price_override
+    })))
+}
+
+#[delete("/prices/{name}")]
+async fn price_delete(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let name = parse_name(name.into_inner())?;
+
+    if !NamePriceOverride::delete_by_name(pool, &name).await? {
+        return Err(KromerError::NotFound);
+    }
+    tracing::info!("Price override of name {name} was removed");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "name": name
+    })))
+}
+
+#[get("/reserved")]
+async fn reserved_list(
+    state: web::Data<AppState>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+
+    let reserved = ReservedName::fetch_all(pool, limit, offset).await?;
+    let total = ReservedName::total_count(pool).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "total": total,
+        "reserved": reserved
+    })))
+}
+
+#[post("/reserved")]
+async fn reserved_create(
+    state: web::Data<AppState>,
+    data: web::Json<ReserveNameReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let data = data.into_inner();
+    let pattern = data.pattern.trim().to_lowercase();
+
+    if !ReservedName::is_valid_pattern(&pattern) {
+        return Err(KromerError::Validation("Invalid pattern".into()));
+    }
+
+    if data
+        .reason
+        .as_ref()
+        .is_some_and(|reason| reason.len() > 255)
+    {
+        return Err(KromerError::Validation(
+            "Reason must not be longer than 255 characters".into(),
+        ));
+    }
+
+    let reserved = ReservedName::create(pool, &pattern, data.reason.as_deref()).await?;
+    tracing::info!("Reserved names matching {pattern}");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "reserved": reserved
+    })))
+}
+
+#[delete("/reserved/{id}")]
+async fn reserved_delete(
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let id = id.into_inner();
+
+    if !ReservedName::delete(pool, id).await? {
+        return Err(KromerError::NotFound);
+    }
+    tracing::info!("Removed reserved name pattern {id}");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": id
+    })))
+}
+
+#[post("/{name}/transfer")]
+async fn name_force_transfer(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    name: web::Path<String>,
+    data: web::Json<ForceTransferReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let name = parse_name(name.into_inner())?;
+    let data = data.into_inner();
+
+    if !validation::is_valid_kromer_address(&data.address) {
+        return Err(KromerError::Validation("Invalid address".into()));
+    }
+
+    let name = Name::fetch_by_name(pool, &name)
+        .await?
+        .ok_or_else(|| KromerError::Name(NameError::NameNotFound(name)))?;
+
+    let name = match name.owner == data.address {
+        true => name,
+        false => {
+            tracing::info!(
+                "Force transferring name {} from {} to {}",
+                name.name,
+                name.owner,
+                data.address
+            );
+            name.transfer_ownership(pool, &server, data.address).await?
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "name": NameJson::from(name)
+    })))
+}
+
+#[post("/{name}/revoke")]
+async fn name_revoke(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    name: web::Path<String>,
+    data: Option<web::Json<RevokeNameReq>>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let name = parse_name(name.into_inner())?;
+    let reason = data.and_then(|data| data.into_inner().reason);
+
+    if reason
+        .as_ref()
+        .is_some_and(|reason| !validation::is_valid_metadata(reason))
+    {
+        return Err(KromerError::Validation("Reason is too long".into()));
+    }
+
+    let name = Name::fetch_by_name(pool, &name)
+        .await?
+        .ok_or_else(|| KromerError::Name(NameError::NameNotFound(name)))?;
+
+    let revoked = name.revoke(pool, &server, reason).await?;
+    tracing::info!("Revoked name {} from {}", revoked.name, revoked.owner);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "name": NameJson::from(revoked)
+    })))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/names")
+            .service(price_list)
+            .service(price_set)
+            .service(price_delete)
+            .service(reserved_list)
+            .service(reserved_create)
+            .service(reserved_delete)
+            .service(name_force_transfer)
+            .service(name_revoke),
+    );
+}
@@ -0,0 +1,84 @@
+use actix_web::{HttpResponse, get, post, web};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::database::outbox::Model as Outbox;
+use crate::database::transaction::{Model as Transaction, ReversalPolicy};
+use crate::models::krist::transactions::TransactionJson;
+use crate::models::krist::websockets::WebSocketEvent;
+use crate::utils::validation;
+use crate::websockets::WebSocketServer;
+use crate::{AppState, errors::KromerError};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ReverseReq {
+    /// What to do if the recipient already spent the funds.
+    pub policy: ReversalPolicy,
+    pub reason: Option<String>,
+}
+
+#[post("/{id}/reverse")]
+async fn transaction_reverse(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    id: web::Path<i32>,
+    data: web::Json<ReverseReq>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let id = id.into_inner();
+    let data = data.into_inner();
+
+    // The reason ends up in CommonMeta, so it can't contain the separator.
+    if data
+        .reason
+        .as_deref()
+        .is_some_and(|reason| reason.contains(';') || !validation::is_valid_metadata(reason))
+    {
+        return Err(KromerError::Validation("Invalid reason".into()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let reversal = Transaction::reverse(&mut *tx, id, data.policy, data.reason.as_deref()).await?;
+    let reversal: TransactionJson = reversal.into();
+
+    let event = WebSocketEvent::Transaction {
+        transaction: reversal.clone(),
+    };
+    Outbox::enqueue(&mut *tx, &event).await?;
+
+    tx.commit().await?;
+    server.notify_outbox();
+
+    tracing::info!(
+        "Reversed transaction {id} with {} ({:?})",
+        reversal.value,
+        data.policy
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "reversal": reversal
+    })))
+}
+
+#[get("/chain/verify")]
+async fn transaction_chain_verify(state: web::Data<AppState>) -> Result<HttpResponse, KromerError> {
+    let verification = Transaction::verify_chain(&state.pool).await?;
+    if let Some(broken) = &verification.broken {
+        tracing::warn!(
+            "Transaction chain is broken at {}: {:?}",
+            broken.id,
+            broken.reason
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(verification))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/transactions")
+            .service(transaction_reverse)
+            .service(transaction_chain_verify),
+    );
+}
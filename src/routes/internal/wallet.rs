@@ -5,16 +5,21 @@ use serde_json::json;
 use uuid::Uuid;
 
 use crate::database::player::Model as Player;
-use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
+use crate::database::transaction::Model as Transaction;
 use crate::database::wallet::Model as Wallet;
+use crate::database::wallet_lock_event::Model as WalletLockEvent;
 
 use crate::database::ModelExt;
 use crate::errors::player::PlayerError;
 use crate::errors::wallet::WalletError;
 use crate::models::krist::addresses::AddressCreationResponse;
+use crate::routes::PaginationParams;
 use crate::utils::crypto::generate_random_password;
 use crate::{AppState, errors::KromerError};
 
+/// The balance a newly created player wallet starts with.
+const NEW_PLAYER_BALANCE: Decimal = dec!(100);
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct MinecraftUser {
     pub name: String,
@@ -27,6 +32,15 @@ struct GiveMoneyReq {
     pub amount: Decimal,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetLockedReq {
+    pub reason: String,
+    /// Who is locking or unlocking the wallet, kept in the audit trail.
+    ///
+    /// All internal callers share one key, so this is taken on trust and not verified.
+    pub actor: String,
+}
+
 #[post("/create")]
 async fn wallet_create(
     state: web::Data<AppState>,
@@ -43,8 +57,13 @@ async fn wallet_create(
     let player = Player::create(pool, user.uuid, user.name).await?;
     let wallet_verification_response = Wallet::verify_address(pool, &private_key).await?;
 
+    // New players start with 100, granted as welfare so the ledger accounts for it.
     let wallet = wallet_verification_response.model;
-    let updated_wallet = wallet.set_balance(&mut *tx, dec!(100)).await?;
+    let top_up = NEW_PLAYER_BALANCE - wallet.balance;
+    let updated_wallet = match top_up > Decimal::ZERO {
+        true => Transaction::grant(&mut *tx, &wallet, top_up).await?.0,
+        false => wallet,
+    };
 
     let _updated_player = player
         .add_wallet_to_owned(&mut *tx, &updated_wallet)
@@ -78,16 +97,7 @@ async fn wallet_give_money(
     let wallet = Wallet::fetch_by_address(&mut *tx, &data.address)
         .await?
         .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(data.address.clone())))?;
-    let updated_wallet = wallet.update_balance(&mut *tx, amount).await?;
-
-    let creation_data = TransactionCreateData {
-        from: "serverwelf".into(),
-        to: data.address,
-        amount,
-        transaction_type: TransactionType::Mined,
-        ..Default::default()
-    };
-    let transaction = Transaction::create_no_update(&mut *tx, creation_data).await?; // bitches.
+    let (updated_wallet, transaction) = Transaction::grant(&mut *tx, &wallet, amount).await?;
     tracing::info!(
         "Created a transaction for welfare with ID {}",
         transaction.id
@@ -123,11 +133,125 @@ async fn wallet_get_by_uuid(
     })))
 }
 
+async fn set_locked(
+    state: &AppState,
+    address: &str,
+    data: SetLockedReq,
+    locked: bool,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+
+    let reason = data.reason.trim();
+    if reason.is_empty() || reason.len() > 255 {
+        return Err(KromerError::Validation("Invalid reason".into()));
+    }
+    let actor = data.actor.trim();
+    if actor.is_empty() || actor.len() > 64 {
+        return Err(KromerError::Validation("Invalid actor".into()));
+    }
+
+    let wallet = Wallet::fetch_by_address(pool, address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(address.to_owned())))?;
+    let wallet = wallet.set_locked(pool, locked, reason, actor).await?;
+    tracing::info!(
+        "Wallet {address} was {} by {actor}: {reason}",
+        if locked { "locked" } else { "unlocked" }
+    );
+
+    Ok(HttpResponse::Ok().json(json!({
+        "wallet": wallet
+    })))
+}
+
+#[post("/{address}/lock")]
+async fn wallet_lock(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    data: web::Json<SetLockedReq>,
+) -> Result<HttpResponse, KromerError> {
+    set_locked(&state, &address, data.into_inner(), true).await
+}
+
+#[post("/{address}/unlock")]
+async fn wallet_unlock(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    data: web::Json<SetLockedReq>,
+) -> Result<HttpResponse, KromerError> {
+    set_locked(&state, &address, data.into_inner(), false).await
+}
+
+#[get("/{address}/locks")]
+async fn wallet_lock_history(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let pool = &state.pool;
+    let address = address.into_inner();
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+
+    let wallet = Wallet::fetch_by_address(pool, &address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound(address.clone())))?;
+    let events = WalletLockEvent::fetch_for_wallet(pool, wallet.id, limit, offset).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "locked": wallet.locked,
+        "events": events
+    })))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/wallet")
             .service(wallet_create)
             .service(wallet_give_money)
-            .service(wallet_get_by_uuid),
+            .service(wallet_get_by_uuid)
+            .service(wallet_lock)
+            .service(wallet_unlock)
+            .service(wallet_lock_history),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Result;
+    use crate::jobs::ledger_audit;
+    use actix_web::{App, test};
+    use sqlx::{Pool, Postgres};
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_created_wallets_pass_the_audit(pool: Pool<Postgres>) -> Result<()> {
+        let state = web::Data::new(AppState { pool: pool.clone() });
+        let app = test::init_service(App::new().app_data(state).configure(config)).await;
+
+        let user = MinecraftUser {
+            name: "steve".to_owned(),
+            uuid: Uuid::new_v4(),
+        };
+        let request = test::TestRequest::post()
+            .uri("/wallet/create")
+            .set_json(&user)
+            .to_request();
+        let created: AddressCreationResponse = test::call_and_read_body_json(&app, request).await;
+
+        let wallet = Wallet::fetch_by_address(&pool, &created.address)
+            .await?
+            .unwrap();
+        assert_eq!(
+            (wallet.balance, wallet.total_in),
+            (NEW_PLAYER_BALANCE, NEW_PLAYER_BALANCE)
+        );
+
+        let report = ledger_audit::run(&pool, false).await?;
+        assert_eq!(report.discrepancies, []);
+        assert!(report.is_clean());
+
+        Ok(())
+    }
+}
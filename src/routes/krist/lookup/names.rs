@@ -0,0 +1,144 @@
+use actix_web::{HttpResponse, get, web};
+use sqlx::{Pool, Postgres};
+
+use crate::database::name::Model as Name;
+use crate::database::transaction::Model as Transaction;
+use crate::errors::krist::generic::GenericError;
+use crate::models::krist::names::NameJson;
+use crate::models::krist::transactions::TransactionJson;
+use crate::models::krist::webserver::lookup::names::{
+    HistoryLookupResponse, LookupResponse, TransactionsLookupResponse,
+};
+use crate::models::krist::webserver::lookup::{
+    LookupQuery, NameLookupFields, TransactionLookupFields,
+};
+use crate::utils::validation;
+use crate::{AppState, errors::krist::KristError};
+
+#[get("")]
+async fn names_lookup_all(
+    state: web::Data<AppState>,
+    query: web::Query<LookupQuery>,
+) -> Result<HttpResponse, KristError> {
+    lookup_names(&state.pool, None, query.into_inner()).await
+}
+
+#[get("/{addresses}")]
+async fn names_lookup(
+    state: web::Data<AppState>,
+    addresses: web::Path<String>,
+    query: web::Query<LookupQuery>,
+) -> Result<HttpResponse, KristError> {
+    let addresses = addresses.into_inner();
+
+    if !validation::is_valid_kromer_address_list(&addresses) {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "addresses".to_string(),
+        )));
+    }
+    let addresses: Vec<&str> = addresses.split(',').collect();
+
+    lookup_names(&state.pool, Some(&addresses), query.into_inner()).await
+}
+
+#[get("/{name}/history")]
+async fn name_history_lookup(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    query: web::Query<LookupQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let name = parse_name(name.into_inner())?;
+    let params = query.into_inner().parse::<TransactionLookupFields>()?;
+    let names = [name.as_str()];
+
+    let mut tx = pool.begin().await?;
+
+    let total = Transaction::count_name_history(&mut *tx, &names).await?;
+    let transactions = Transaction::name_history(&mut *tx, &names, &params).await?;
+
+    tx.commit().await?;
+
+    let transactions: Vec<TransactionJson> =
+        transactions.into_iter().map(|trans| trans.into()).collect();
+
+    let response = HistoryLookupResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/{name}/transactions")]
+async fn name_transactions_lookup(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    query: web::Query<LookupQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let name = parse_name(name.into_inner())?;
+    let params = query.into_inner().parse::<TransactionLookupFields>()?;
+    let names = [name.as_str()];
+
+    let mut tx = pool.begin().await?;
+
+    let total = Transaction::count_sent_to_name(&mut *tx, &names).await?;
+    let transactions = Transaction::sent_to_name(&mut *tx, &names, &params).await?;
+
+    tx.commit().await?;
+
+    let transactions: Vec<TransactionJson> =
+        transactions.into_iter().map(|trans| trans.into()).collect();
+
+    let response = TransactionsLookupResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn lookup_names(
+    pool: &Pool<Postgres>,
+    addresses: Option<&[&str]>,
+    query: LookupQuery,
+) -> Result<HttpResponse, KristError> {
+    let params = query.parse::<NameLookupFields>()?;
+
+    let mut tx = pool.begin().await?;
+
+    let total = Name::count_lookup_owners(&mut *tx, addresses).await?;
+    let names = Name::lookup_owners(&mut *tx, addresses, &params).await?;
+
+    tx.commit().await?;
+
+    let names: Vec<NameJson> = names.into_iter().map(|name| name.into()).collect();
+
+    let response = LookupResponse {
+        ok: true,
+        count: names.len(),
+        total,
+        names,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+fn parse_name(name: String) -> Result<String, KristError> {
+    let name = validation::strip_name_suffix(&name.trim().to_lowercase());
+
+    validation::normalize_name(&name)
+        .ok_or_else(|| KristError::Generic(GenericError::InvalidParameter("name".to_string())))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(names_lookup_all);
+    cfg.service(names_lookup);
+    cfg.service(name_history_lookup);
+    cfg.service(name_transactions_lookup);
+}
@@ -0,0 +1,74 @@
+use actix_web::{HttpResponse, get, web};
+use sqlx::{Pool, Postgres};
+
+use crate::database::transaction::Model as Transaction;
+use crate::errors::krist::generic::GenericError;
+use crate::models::krist::transactions::{MetaQuery, TransactionJson};
+use crate::models::krist::webserver::lookup::transactions::LookupResponse;
+use crate::models::krist::webserver::lookup::{LookupQuery, TransactionLookupFields};
+use crate::utils::validation;
+use crate::{AppState, errors::krist::KristError};
+
+#[get("")]
+async fn transactions_lookup_all(
+    state: web::Data<AppState>,
+    query: web::Query<LookupQuery>,
+    meta: web::Query<MetaQuery>,
+) -> Result<HttpResponse, KristError> {
+    lookup_transactions(&state.pool, None, query.into_inner(), *meta).await
+}
+
+#[get("/{addresses}")]
+async fn transactions_lookup(
+    state: web::Data<AppState>,
+    addresses: web::Path<String>,
+    query: web::Query<LookupQuery>,
+    meta: web::Query<MetaQuery>,
+) -> Result<HttpResponse, KristError> {
+    let addresses = addresses.into_inner();
+
+    if !validation::is_valid_kromer_address_list(&addresses) {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "addresses".to_string(),
+        )));
+    }
+    let addresses: Vec<&str> = addresses.split(',').collect();
+
+    lookup_transactions(&state.pool, Some(&addresses), query.into_inner(), *meta).await
+}
+
+async fn lookup_transactions(
+    pool: &Pool<Postgres>,
+    addresses: Option<&[&str]>,
+    query: LookupQuery,
+    meta: MetaQuery,
+) -> Result<HttpResponse, KristError> {
+    let params = query.parse::<TransactionLookupFields>()?;
+
+    let mut tx = pool.begin().await?;
+
+    let total =
+        Transaction::count_lookup_addresses(&mut *tx, addresses, params.include_mined).await?;
+    let transactions = Transaction::lookup_addresses(&mut *tx, addresses, &params).await?;
+
+    tx.commit().await?;
+
+    let transactions: Vec<TransactionJson> = transactions
+        .into_iter()
+        .map(|trans| meta.apply(trans))
+        .collect();
+
+    let response = LookupResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(transactions_lookup_all);
+    cfg.service(transactions_lookup);
+}
@@ -3,14 +3,18 @@ use rust_decimal::Decimal;
 
 use crate::{
     AppState,
-    database::wallet::Model as Wallet,
-    errors::krist::KristError,
+    config::get_config,
+    database::{motd::Model as Motd, wallet::Model as Wallet},
+    errors::krist::{KristError, address::AddressError},
     models::krist::{
         auth::{AddressAuthenticationResponse, LoginDetails},
-        misc::{MoneySupplyResponse, PrivateKeyAddressResponse, WalletVersionResponse},
-        motd::{Constants, CurrencyInfo, DetailedMotd, DetailedMotdResponse, PackageInfo},
+        misc::{
+            MoneySupplyResponse, PrivateKeyAddressResponse, ReceiptKeysResponse,
+            WalletVersionResponse,
+        },
+        motd::{DetailedMotd, DetailedMotdResponse},
     },
-    utils::crypto,
+    utils::{crypto, receipts},
 };
 
 #[post("/login")]
@@ -23,6 +27,11 @@ async fn login_address(
 
     let private_key = query.private_key;
     let result = Wallet::verify_address(db, private_key).await?;
+    if result.authed && result.model.locked {
+        return Err(KristError::Address(AddressError::Locked(
+            result.model.address,
+        )));
+    }
 
     Ok(HttpResponse::Ok().json(AddressAuthenticationResponse {
         address: result.authed.then_some(result.model.address),
@@ -32,50 +41,13 @@ async fn login_address(
 }
 
 #[get("/motd")]
-async fn get_motd() -> HttpResponse {
-    // This is by far the simplest fucking route in all of Kromer.
-    // TODO: Make this actually better.
-    let motd = DetailedMotd {
-        server_time: "server_time".to_string(),
-        motd: "Message of the day".to_string(),
-        set: None,
-        motd_set: None,
-        public_url: "http://kromer.reconnected.cc".to_string(),
-        public_ws_url: "http://kromer.reconnected.cc/api/krist/ws".to_string(),
-        mining_enabled: false,
-        transactions_enabled: true,
-        debug_mode: true,
-        work: 500,
-        last_block: None,
-        package: PackageInfo {
-            name: "Kromer".to_string(),
-            version: "0.2.0".to_string(),
-            author: "ReconnectedCC Team".to_string(),
-            license: "GPL-3.0".to_string(),
-            repository: "https://github.com/ReconnectedCC/kromer/".to_string(),
-            git_hash: crate::build_info::GIT_COMMIT_HASH.map(|s| s.to_string()),
-        },
-        constants: Constants {
-            wallet_version: 3,
-            nonce_max_size: 500,
-            name_cost: 500,
-            min_work: 50,
-            max_work: 500,
-            work_factor: 500.0,
-            seconds_per_block: 5000,
-        },
-        currency: CurrencyInfo {
-            address_prefix: "k".to_string(),
-            name_suffix: "kro".to_string(),
-            currency_name: "Kromer".to_string(),
-            currency_symbol: "KRO".to_string(),
-        },
-        notice: "Some awesome notice will go here".to_string(),
-    };
+async fn get_motd(state: web::Data<AppState>) -> Result<HttpResponse, KristError> {
+    let current = Motd::fetch_current(&state.pool).await?;
 
+    let motd = DetailedMotd::new(get_config(), current);
     let motd = DetailedMotdResponse { ok: true, motd };
 
-    HttpResponse::Ok().json(motd)
+    Ok(HttpResponse::Ok().json(motd))
 }
 
 #[get("/walletversion")]
@@ -115,12 +87,66 @@ async fn get_kromer_supply(state: web::Data<AppState>) -> Result<HttpResponse, K
     }))
 }
 
+/// The keys transaction receipts can be verified with, see [`receipts::verify`].
+#[get("/receipts/keys")]
+async fn get_receipt_keys() -> HttpResponse {
+    let signer = receipts::get_signer();
+    let response = ReceiptKeysResponse {
+        ok: true,
+        key: signer.public_key(),
+        retired_keys: signer.retired_keys().to_vec(),
+    };
+
+    HttpResponse::Ok().json(response)
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("")
             .service(login_address)
             .service(get_motd)
             .service(get_kromer_supply)
+            .service(get_receipt_keys)
             .service(get_v2_address),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Result;
+    use actix_web::{App, http::StatusCode, test};
+    use sqlx::{Pool, Postgres};
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_locked_wallet_cant_log_in(pool: Pool<Postgres>) -> Result<()> {
+        let state = web::Data::new(AppState { pool: pool.clone() });
+        let app = test::init_service(App::new().app_data(state).configure(config)).await;
+
+        let wallet = Wallet::verify_address(&pool, "stolen").await?.model;
+        let login = || {
+            let details = LoginDetails {
+                private_key: "stolen".to_owned(),
+            };
+            test::TestRequest::post()
+                .uri("/login")
+                .set_json(&details)
+                .to_request()
+        };
+
+        let response: AddressAuthenticationResponse =
+            test::call_and_read_body_json(&app, login()).await;
+        assert!(response.authed);
+
+        wallet
+            .set_locked(&pool, true, "stolen key", "admin")
+            .await?;
+        let response = test::call_service(&app, login()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["error"], "address_locked");
+
+        Ok(())
+    }
+}
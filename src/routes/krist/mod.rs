@@ -1,6 +1,8 @@
 mod lookup;
 mod misc;
 mod names;
+mod schedules;
+mod search;
 mod transactions;
 mod wallet;
 mod ws;
@@ -14,6 +16,7 @@ pub async fn index_get() -> HttpResponse {
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::scope("/lookup").configure(lookup::config));
+    cfg.service(web::scope("/search").configure(search::config));
 
     cfg.service(index_get);
 
@@ -21,5 +24,6 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.configure(transactions::config);
     cfg.configure(ws::config);
     cfg.configure(names::config);
+    cfg.configure(schedules::config);
     cfg.configure(misc::config);
 }
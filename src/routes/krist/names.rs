@@ -1,8 +1,14 @@
 use actix_web::{HttpResponse, get, post, web};
 use rust_decimal::Decimal;
 
+use crate::config::get_config;
 use crate::database::ModelExt;
 use crate::database::name::Model as Name;
+use crate::database::name_listing::Model as NameListing;
+use crate::database::name_price_override::Model as NamePriceOverride;
+use crate::database::name_route::Model as NameRoute;
+use crate::database::outbox::Model as Outbox;
+use crate::database::reserved_name::Model as ReservedName;
 use crate::database::transaction::{Model as Transaction, TransactionCreateData, TransactionType};
 use crate::database::wallet::Model as Wallet;
 
@@ -10,16 +16,49 @@ use crate::errors::krist::address::AddressError;
 use crate::errors::krist::generic::GenericError;
 use crate::errors::krist::name::NameError;
 use crate::errors::krist::transaction::TransactionError;
-use crate::models::krist::motd::MINING_CONSTANTS;
 use crate::models::krist::names::{
+    BulkTransferNameRequest, BulkTransferNameResponse, BuyNameRequest, ListNameRequest,
     NameAvailablityResponse, NameBonusResponse, NameCostResponse, NameDataUpdateBody, NameJson,
-    NameListResponse, NameResponse, RegisterNameRequest, TransferNameRequest,
+    NameListResponse, NameListingJson, NameListingListResponse, NameListingResponse,
+    NameRefundsResponse, NameResponse, NameRoutesResponse, RegisterNameRequest,
+    RemoveNameRouteRequest, RenewNameRequest, SetNameRefundsRequest, SetNameRouteRequest,
+    TransferNameRequest, UnlistNameRequest,
 };
-use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
+use crate::models::krist::websockets::WebSocketEvent;
 use crate::utils::validation;
 use crate::websockets::WebSocketServer;
 use crate::{AppState, errors::krist::KristError, routes::PaginationParams};
 
+/// Validate a name from the request and convert it to the form it is stored in.
+fn parse_name(name: &str) -> Result<String, KristError> {
+    validation::normalize_name(name)
+        .ok_or_else(|| KristError::Generic(GenericError::InvalidParameter("name".to_owned())))
+}
+
+/// Validate an optional metaname from the request, `None` stands for the fallback route.
+fn parse_metaname(metaname: Option<String>) -> Result<Option<String>, KristError> {
+    let Some(metaname) = metaname else {
+        return Ok(None);
+    };
+
+    let metaname = metaname.trim().to_lowercase();
+    match validation::is_valid_metaname(&metaname) {
+        true => Ok(Some(metaname)),
+        false => Err(KristError::Generic(GenericError::InvalidParameter(
+            "metaname".to_owned(),
+        ))),
+    }
+}
+
+/// The most names a single bulk transfer may move.
+const BULK_TRANSFER_MAX_NAMES: usize = 100;
+
+/// The most metaname routes a single name may have.
+const MAX_ROUTES_PER_NAME: usize = 32;
+
+/// The most metanames a single name may accept when refunding everything else.
+const MAX_ALLOWED_METANAMES: usize = 32;
+
 #[get("")]
 async fn name_list(
     state: web::Data<AppState>,
@@ -54,7 +93,27 @@ async fn name_list(
 async fn name_cost() -> Result<HttpResponse, KristError> {
     let response = NameCostResponse {
         ok: true,
-        name_cost: MINING_CONSTANTS.name_cost,
+        name_cost: get_config().names.cost,
+        breakdown: None,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/cost/{name}")]
+async fn name_cost_for(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let name = name.into_inner();
+
+    let name = parse_name(&name)?;
+    let price = NamePriceOverride::quote(pool, &name).await?;
+
+    let response = NameCostResponse {
+        ok: true,
+        name_cost: price.total,
+        breakdown: Some(price),
     };
     Ok(HttpResponse::Ok().json(response))
 }
@@ -67,18 +126,14 @@ async fn name_check(
     let name = name.into_inner();
     let pool = &state.pool;
 
-    if !validation::is_valid_name(&name, true) {
-        return Err(KristError::Generic(GenericError::InvalidParameter(
-            "name".to_string(),
-        )));
-    }
-    let name = name.trim().to_lowercase();
+    let name = parse_name(&name)?;
 
+    let reserved = ReservedName::find_match(pool, &name).await?;
     let name = Name::fetch_by_name(pool, name).await?;
 
     let response = NameAvailablityResponse {
         ok: true,
-        available: name.is_none(),
+        available: name.is_none() && reserved.is_none(),
     };
 
     Ok(HttpResponse::Ok().json(response))
@@ -131,8 +186,9 @@ async fn name_get(
 ) -> Result<HttpResponse, KristError> {
     let pool = &state.pool;
     let name = name.into_inner();
+    let stored_name = validation::normalize_name(&name).unwrap_or_else(|| name.clone());
 
-    let db_name = Name::fetch_by_name(pool, &name).await?;
+    let db_name = Name::fetch_by_name(pool, &stored_name).await?;
 
     db_name
         .map(|name| NameResponse {
@@ -153,8 +209,7 @@ async fn name_register(
     let pool = &state.pool;
     let websocket_server = websocket_server.into_inner();
 
-    let name = name.into_inner().trim().to_lowercase();
-    let new_name_cost = Decimal::new(MINING_CONSTANTS.name_cost, 0);
+    let name = name.into_inner();
 
     let private_key = details.map(|request| request.0.private_key);
     let private_key = match private_key {
@@ -166,19 +221,9 @@ async fn name_register(
         }
     };
 
-    if !validation::is_valid_name(&name, false) {
-        return Err(KristError::Generic(GenericError::InvalidParameter(
-            "name".to_string(),
-        )));
-    }
+    let name = parse_name(&name)?;
 
-    let mut tx = pool.begin().await?;
-
-    if let Some(name) = Name::fetch_by_name(&mut *tx, &name).await? {
-        return Err(KristError::Name(NameError::NameTaken(name.name)));
-    }
-
-    let verify_addr_resp = Wallet::verify_address(&mut *tx, &private_key).await?;
+    let verify_addr_resp = Wallet::verify_address(pool, &private_key).await?;
 
     if !verify_addr_resp.authed {
         tracing::info!(
@@ -188,6 +233,18 @@ async fn name_register(
         return Err(KristError::Address(AddressError::AuthFailed));
     }
 
+    let mut tx = pool.begin().await?;
+
+    if let Some(name) = Name::fetch_by_name(&mut *tx, &name).await? {
+        return Err(KristError::Name(NameError::NameTaken(name.name)));
+    }
+
+    if ReservedName::find_match(&mut *tx, &name).await?.is_some() {
+        return Err(KristError::Name(NameError::NameReserved(name)));
+    }
+
+    let new_name_cost = Decimal::from(NamePriceOverride::quote(&mut *tx, &name).await?.total);
+
     // Reject insufficient funds
     if verify_addr_resp.model.balance < new_name_cost {
         return Err(KristError::Transaction(TransactionError::InsufficientFunds));
@@ -198,6 +255,7 @@ async fn name_register(
         from: verify_addr_resp.model.address.clone(),
         to: "serverwelf".to_string(),
         amount: new_name_cost,
+        name: Some(name.clone()),
         transaction_type: TransactionType::NamePurchase,
         ..Default::default()
     };
@@ -208,10 +266,10 @@ async fn name_register(
         transaction.id
     );
 
-    let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
+    let event = WebSocketEvent::Transaction {
         transaction: transaction.into(),
-    });
-    websocket_server.broadcast_event(event).await;
+    };
+    Outbox::enqueue(&mut *tx, &event).await?;
 
     // Create the new name
     let name = Name::create(&mut *tx, name.clone(), verify_addr_resp.model.address).await?;
@@ -221,12 +279,14 @@ async fn name_register(
     };
 
     tx.commit().await?;
+    websocket_server.notify_outbox();
 
     Ok(HttpResponse::Ok().json(response))
 }
 
 async fn name_update_data(
     state: web::Data<AppState>,
+    websocket_server: web::Data<WebSocketServer>,
     name: web::Path<String>,
     body: web::Json<NameDataUpdateBody>,
 ) -> Result<HttpResponse, KristError> {
@@ -234,7 +294,7 @@ async fn name_update_data(
     let name = name.into_inner();
     let body = body.into_inner();
 
-    let model = Name::ctrl_update_metadata(pool, name, body).await?;
+    let model = Name::ctrl_update_metadata(pool, &websocket_server, name, body).await?;
 
     let name: NameJson = model.into();
     let resp = NameResponse { ok: true, name };
@@ -254,13 +314,7 @@ async fn name_transfer(
     let details = details.into_inner();
     let name = name.into_inner();
 
-    if !validation::is_valid_name(&name, false) {
-        return Err(KristError::Generic(GenericError::InvalidParameter(
-            "name".to_owned(),
-        )));
-    }
-
-    let name = name.trim().to_lowercase();
+    let name = parse_name(&name)?;
 
     let current_owner_response = Wallet::verify_address(pool, details.private_key).await?;
     if !current_owner_response.authed {
@@ -297,17 +351,464 @@ async fn name_transfer(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[post("/transfer")]
+async fn name_transfer_bulk(
+    state: web::Data<AppState>,
+    websocket_server: web::Data<WebSocketServer>,
+    details: web::Json<BulkTransferNameRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let server = websocket_server.into_inner();
+    let details = details.into_inner();
+
+    if details.names.is_empty() || details.names.len() > BULK_TRANSFER_MAX_NAMES {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "names".to_owned(),
+        )));
+    }
+
+    if !validation::is_valid_kromer_address(&details.address) {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "address".to_owned(),
+        )));
+    }
+
+    let mut names: Vec<String> = Vec::with_capacity(details.names.len());
+    for name in &details.names {
+        let name = validation::normalize_name(name).ok_or_else(|| {
+            KristError::Generic(GenericError::InvalidParameter("names".to_owned()))
+        })?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let current_owner_response = Wallet::verify_address(pool, details.private_key).await?;
+    if !current_owner_response.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+    let current_owner = current_owner_response.model;
+
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let transferred = Name::transfer_many(
+        pool,
+        &server,
+        &names,
+        &current_owner.address,
+        &details.address,
+    )
+    .await?;
+
+    let response = BulkTransferNameResponse {
+        ok: true,
+        names: transferred.into_iter().map(|name| name.into()).collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/listings")]
+async fn name_listings(
+    state: web::Data<AppState>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KristError> {
+    let params = query.into_inner();
+    let pool = &state.pool;
+
+    let limit = params.limit.unwrap_or(50);
+    let offset = params.offset.unwrap_or(0);
+
+    let mut tx = pool.begin().await?;
+
+    let total = NameListing::total_count(&mut *tx).await?;
+    let listings = NameListing::fetch_all(&mut *tx, limit, offset).await?;
+
+    tx.commit().await?;
+
+    let listings: Vec<NameListingJson> =
+        listings.into_iter().map(|listing| listing.into()).collect();
+
+    let response = NameListingListResponse {
+        ok: true,
+        count: listings.len(),
+        total,
+        listings,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/list")]
+async fn name_list_for_sale(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<ListNameRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let name = name.into_inner();
+
+    let name = parse_name(&name)?;
+
+    if details.price <= Decimal::ZERO || details.price.round_dp(2) != details.price {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "price".to_owned(),
+        )));
+    }
+
+    let owner = Wallet::verify_address(pool, details.private_key).await?;
+    if !owner.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let name = Name::fetch_by_name(&mut *tx, &name)
+        .await?
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    if name.owner != owner.model.address {
+        return Err(KristError::Name(NameError::NotNameOwner(name.name)));
+    }
+    if name.expired {
+        return Err(KristError::Name(NameError::NameExpired(name.name)));
+    }
+
+    let listing = NameListing::upsert(&mut *tx, &name, details.price).await?;
+
+    tx.commit().await?;
+    tracing::info!("Name {} listed for {}", listing.name, listing.price);
+
+    let response = NameListingResponse {
+        ok: true,
+        listing: listing.into(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/unlist")]
+async fn name_unlist(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<UnlistNameRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let name = parse_name(&name.into_inner())?;
+
+    let owner = Wallet::verify_address(pool, details.private_key).await?;
+    if !owner.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let name = Name::fetch_by_name(&mut *tx, &name)
+        .await?
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    if name.owner != owner.model.address {
+        return Err(KristError::Name(NameError::NotNameOwner(name.name)));
+    }
+
+    if !NameListing::delete_for_name(&mut *tx, name.id).await? {
+        return Err(KristError::Name(NameError::NotForSale(name.name)));
+    }
+
+    tx.commit().await?;
+
+    let response = NameResponse {
+        ok: true,
+        name: name.into(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/renew")]
+async fn name_renew(
+    state: web::Data<AppState>,
+    websocket_server: web::Data<WebSocketServer>,
+    name: web::Path<String>,
+    details: web::Json<RenewNameRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let name = name.into_inner();
+
+    let name = parse_name(&name)?;
+
+    let owner = Wallet::verify_address(pool, details.private_key).await?;
+    if !owner.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let name = Name::fetch_by_name(pool, &name)
+        .await?
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    if name.owner != owner.model.address {
+        return Err(KristError::Name(NameError::NotNameOwner(name.name)));
+    }
+
+    let renewed_name = name.renew(pool, &websocket_server).await?;
+
+    let response = NameResponse {
+        ok: true,
+        name: renewed_name.into(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/buy")]
+async fn name_buy(
+    state: web::Data<AppState>,
+    websocket_server: web::Data<WebSocketServer>,
+    name: web::Path<String>,
+    details: web::Json<BuyNameRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let server = websocket_server.into_inner();
+    let details = details.into_inner();
+    let name = parse_name(&name.into_inner())?;
+
+    let buyer = Wallet::verify_address(pool, details.private_key).await?;
+    if !buyer.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+    let buyer = buyer.model;
+
+    let mut tx = pool.begin().await?;
+
+    let updated_name =
+        NameListing::purchase(&mut *tx, &server, &name, &buyer.address, details.price).await?;
+
+    tx.commit().await?;
+    server.notify_outbox();
+
+    let response = NameResponse {
+        ok: true,
+        name: updated_name.into(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/{name}/routes")]
+async fn name_routes(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let name = parse_name(&name.into_inner())?;
+
+    let name = Name::fetch_by_name(pool, &name)
+        .await?
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    let routes = NameRoute::fetch_for_name(pool, name.id).await?;
+
+    let response = NameRoutesResponse {
+        ok: true,
+        name: name.name,
+        routes: routes.into_iter().map(|route| route.into()).collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/routes")]
+async fn name_route_set(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<SetNameRouteRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let name = parse_name(&name.into_inner())?;
+    let metaname = parse_metaname(details.metaname)?;
+
+    if !validation::is_valid_kromer_address(&details.address) {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "address".to_owned(),
+        )));
+    }
+
+    let owner = Wallet::verify_address(pool, details.private_key).await?;
+    if !owner.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    // Lock the name so a transfer can't clear the routes while we add one.
+    let name = Name::lock_by_names(&mut *tx, &[&name])
+        .await?
+        .pop()
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    if name.owner != owner.model.address {
+        return Err(KristError::Name(NameError::NotNameOwner(name.name)));
+    }
+
+    if Wallet::fetch_by_address(&mut *tx, &details.address)
+        .await?
+        .is_none()
+    {
+        return Err(KristError::Address(AddressError::NotFound(details.address)));
+    }
+
+    let routes = NameRoute::fetch_for_name(&mut *tx, name.id).await?;
+    let is_new = !routes.iter().any(|route| route.metaname == metaname);
+    if is_new && routes.len() >= MAX_ROUTES_PER_NAME {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "metaname".to_owned(),
+        )));
+    }
+
+    NameRoute::set(&mut *tx, name.id, metaname.as_deref(), &details.address).await?;
+    let routes = NameRoute::fetch_for_name(&mut *tx, name.id).await?;
+
+    tx.commit().await?;
+
+    let response = NameRoutesResponse {
+        ok: true,
+        name: name.name,
+        routes: routes.into_iter().map(|route| route.into()).collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/routes/remove")]
+async fn name_route_remove(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<RemoveNameRouteRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let name = parse_name(&name.into_inner())?;
+    let metaname = parse_metaname(details.metaname)?;
+
+    let owner = Wallet::verify_address(pool, details.private_key).await?;
+    if !owner.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let name = Name::lock_by_names(&mut *tx, &[&name])
+        .await?
+        .pop()
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    if name.owner != owner.model.address {
+        return Err(KristError::Name(NameError::NotNameOwner(name.name)));
+    }
+
+    if !NameRoute::delete(&mut *tx, name.id, metaname.as_deref()).await? {
+        let route = format!("{}@{}.kro", metaname.as_deref().unwrap_or("*"), name.name);
+        return Err(KristError::Name(NameError::RouteNotFound(route)));
+    }
+    let routes = NameRoute::fetch_for_name(&mut *tx, name.id).await?;
+
+    tx.commit().await?;
+
+    let response = NameRoutesResponse {
+        ok: true,
+        name: name.name,
+        routes: routes.into_iter().map(|route| route.into()).collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/{name}/refunds")]
+async fn name_refunds(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let name = parse_name(&name.into_inner())?;
+
+    let name = Name::fetch_by_name(pool, &name)
+        .await?
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    let response: NameRefundsResponse = name.into();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{name}/refunds")]
+async fn name_refunds_set(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<SetNameRefundsRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let name = parse_name(&name.into_inner())?;
+
+    if details.allowed_metanames.len() > MAX_ALLOWED_METANAMES {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "allowed_metanames".to_owned(),
+        )));
+    }
+
+    let mut allowlist: Vec<String> = Vec::with_capacity(details.allowed_metanames.len());
+    for metaname in details.allowed_metanames {
+        let metaname = parse_metaname(Some(metaname))?.unwrap_or_default();
+        if !allowlist.contains(&metaname) {
+            allowlist.push(metaname);
+        }
+    }
+
+    let owner = Wallet::verify_address(pool, details.private_key).await?;
+    if !owner.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let name = Name::lock_by_names(&mut *tx, &[&name])
+        .await?
+        .pop()
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+    if name.owner != owner.model.address {
+        return Err(KristError::Name(NameError::NotNameOwner(name.name)));
+    }
+
+    let name = name
+        .set_refund_settings(&mut *tx, details.auto_refund, &allowlist)
+        .await?;
+
+    tx.commit().await?;
+
+    let response: NameRefundsResponse = name.into();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/names")
             .service(name_list)
             .service(name_cost)
+            .service(name_cost_for)
             .service(name_check)
             .service(name_bonus)
             .service(name_new)
+            .service(name_listings)
+            .service(name_transfer_bulk)
             .service(name_get)
             .service(name_register)
             .service(name_transfer)
+            .service(name_list_for_sale)
+            .service(name_unlist)
+            .service(name_renew)
+            .service(name_buy)
+            .service(name_routes)
+            .service(name_route_set)
+            .service(name_route_remove)
+            .service(name_refunds)
+            .service(name_refunds_set)
             .service(
                 web::resource("/{name}/update")
                     .put(name_update_data)
@@ -0,0 +1,189 @@
+use actix_web::{HttpResponse, get, post, web};
+use chrono::Utc;
+use rust_decimal::dec;
+
+use crate::AppState;
+use crate::config::get_config;
+use crate::database::ModelExt;
+use crate::database::payment_schedule::{Model as PaymentSchedule, ScheduleCreateData};
+use crate::database::payment_schedule_run::Model as PaymentScheduleRun;
+use crate::database::wallet::Model as Wallet;
+use crate::errors::krist::KristError;
+use crate::errors::krist::address::AddressError;
+use crate::errors::krist::generic::GenericError;
+use crate::errors::krist::transaction::TransactionError;
+use crate::models::krist::schedules::{
+    CancelScheduleRequest, CreateScheduleRequest, ScheduleResponse,
+};
+use crate::utils::recurrence::Recurrence;
+use crate::utils::validation::{self, ADDRESS_RE_V2};
+
+/// How many of a schedule's latest payments are shown with it.
+const RECENT_RUNS: i64 = 50;
+
+fn invalid_parameter(parameter: &str) -> KristError {
+    KristError::Generic(GenericError::InvalidParameter(parameter.to_string()))
+}
+
+/// Set up a payment from the authenticated wallet that repeats until its end or until cancelled.
+#[post("")]
+async fn schedule_create(
+    state: web::Data<AppState>,
+    details: web::Json<CreateScheduleRequest>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+    let amount = details.amount.round_dp(2); // Do not allow more than 2 decimals after the dot.
+    let now = Utc::now();
+
+    if amount <= dec!(0.00) {
+        return Err(invalid_parameter("amount"));
+    }
+
+    // Names can change owner between payments, so schedules only pay addresses.
+    if !ADDRESS_RE_V2.is_match(&details.to) {
+        return Err(invalid_parameter("to"));
+    }
+
+    if details
+        .metadata
+        .as_deref()
+        .is_some_and(|metadata| !validation::is_valid_metadata(metadata))
+    {
+        return Err(invalid_parameter("metadata"));
+    }
+
+    let invalid_recurrence = || match details.cron {
+        Some(_) => invalid_parameter("cron"),
+        None => invalid_parameter("interval_secs"),
+    };
+    let recurrence = Recurrence::from_parts(details.cron.as_deref(), details.interval_secs)
+        .ok_or_else(invalid_recurrence)?;
+    let next_run_at = recurrence
+        .next_after(now, now)
+        .ok_or_else(invalid_recurrence)?;
+
+    if details.ends_at.is_some_and(|ends_at| ends_at <= now) {
+        return Err(invalid_parameter("ends_at"));
+    }
+
+    let sender = Wallet::verify_address(pool, details.private_key).await?;
+    if !sender.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+    let sender = sender.model;
+
+    let mut tx = pool.begin().await?;
+
+    if sender.address == details.to {
+        return Err(KristError::Transaction(
+            TransactionError::SameWalletTransfer,
+        ));
+    }
+
+    Wallet::fetch_by_address(&mut *tx, &details.to)
+        .await?
+        .ok_or_else(|| KristError::Address(AddressError::NotFound(details.to.clone())))?;
+
+    let active = PaymentSchedule::count_active_for_address(&mut *tx, &sender.address).await?;
+    if active >= get_config().schedules.max_per_wallet {
+        return Err(KristError::Transaction(TransactionError::TooManySchedules(
+            sender.address,
+        )));
+    }
+
+    let creation_data = ScheduleCreateData {
+        from: sender.address,
+        to: details.to,
+        amount,
+        metadata: details.metadata,
+        cron: details.cron,
+        interval_secs: details.interval_secs,
+        ends_at: details.ends_at,
+    };
+    let schedule = PaymentSchedule::create(&mut *tx, creation_data, next_run_at).await?;
+
+    tx.commit().await?;
+
+    let response = ScheduleResponse {
+        ok: true,
+        schedule: schedule.into(),
+        runs: Vec::new(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/{id}")]
+async fn schedule_get(
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, KristError> {
+    let id = id.into_inner();
+    let pool = &state.pool;
+
+    let mut tx = pool.begin().await?;
+
+    let schedule = PaymentSchedule::fetch_by_id(&mut *tx, id)
+        .await?
+        .ok_or_else(|| KristError::Transaction(TransactionError::ScheduleNotFound(id)))?;
+    let runs = PaymentScheduleRun::fetch_for_schedule(&mut *tx, id, RECENT_RUNS, 0).await?;
+
+    tx.commit().await?;
+
+    let response = ScheduleResponse {
+        ok: true,
+        schedule: schedule.into(),
+        runs: runs.into_iter().map(|run| run.into()).collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{id}/cancel")]
+async fn schedule_cancel(
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+    details: web::Json<CancelScheduleRequest>,
+) -> Result<HttpResponse, KristError> {
+    let id = id.into_inner();
+    let pool = &state.pool;
+    let details = details.into_inner();
+
+    let sender = Wallet::verify_address(pool, details.private_key).await?;
+    if !sender.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let schedule = PaymentSchedule::fetch_by_id(&mut *tx, id)
+        .await?
+        .ok_or_else(|| KristError::Transaction(TransactionError::ScheduleNotFound(id)))?;
+    if schedule.from != sender.model.address {
+        return Err(KristError::Transaction(TransactionError::NotScheduleOwner(
+            id,
+        )));
+    }
+
+    let schedule = schedule.cancel(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    let response = ScheduleResponse {
+        ok: true,
+        schedule: schedule.into(),
+        runs: Vec::new(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/schedules")
+            .service(schedule_create)
+            .service(schedule_get)
+            .service(schedule_cancel),
+    );
+}
@@ -0,0 +1,127 @@
+use actix_web::{HttpResponse, get, web};
+
+use crate::database::ModelExt;
+use crate::database::name::Model as Name;
+use crate::database::transaction::Model as Transaction;
+use crate::database::wallet::Model as Wallet;
+use crate::errors::krist::generic::GenericError;
+use crate::models::krist::webserver::search::{
+    ReqSearchQuery, SEARCH_QUERY_MAX_LENGTH, SearchExtendedResult, SearchExtendedResultMatches,
+    SearchExtendedResultTransactions, SearchQueryMatch, SearchResult, SearchResultMatches,
+};
+use crate::{AppState, errors::krist::KristError};
+
+/// Metadata searches shorter than this would match nearly everything.
+const METADATA_QUERY_MIN_LENGTH: usize = 3;
+
+#[get("")]
+async fn search(
+    state: web::Data<AppState>,
+    query: web::Query<ReqSearchQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let query = parse_query(&query)?;
+
+    let mut tx = pool.begin().await?;
+
+    let exact_address = match query.match_address {
+        true => Wallet::fetch_by_address(&mut *tx, &query.original_query.to_lowercase()).await?,
+        false => None,
+    };
+    let exact_name = match query.match_name {
+        true => Name::fetch_by_name(&mut *tx, &query.stripped_name).await?,
+        false => None,
+    };
+    let exact_transaction = match query.clean_id.map(i32::try_from) {
+        Some(Ok(id)) => Transaction::fetch_by_id(&mut *tx, id).await?,
+        _ => None,
+    };
+
+    tx.commit().await?;
+
+    let response = SearchResult {
+        ok: true,
+        query,
+        matches: SearchResultMatches {
+            exact_address: exact_address.map(|wallet| wallet.into()),
+            exact_block: None,
+            exact_name: exact_name.map(|name| name.into()),
+            exact_transaction: exact_transaction.map(|transaction| transaction.into()),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[get("/extended")]
+async fn search_extended(
+    state: web::Data<AppState>,
+    query: web::Query<ReqSearchQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let include_mined = query.include_mined.unwrap_or(false);
+    let query = parse_query(&query)?;
+
+    let mut tx = pool.begin().await?;
+
+    let address_involved = match query.match_address {
+        true => {
+            let address = query.original_query.to_lowercase();
+            let addresses = [address.as_str()];
+            let count =
+                Transaction::count_lookup_addresses(&mut *tx, Some(&addresses), include_mined)
+                    .await?;
+            Some(count)
+        }
+        false => None,
+    };
+    let name_involved = match query.match_name {
+        true => Some(Transaction::count_involving_name(&mut *tx, &query.stripped_name).await?),
+        false => None,
+    };
+    let metadata = match query.original_query.len() >= METADATA_QUERY_MIN_LENGTH {
+        true => Some(
+            Transaction::count_by_metadata(&mut *tx, &query.original_query, include_mined).await?,
+        ),
+        false => None,
+    };
+
+    tx.commit().await?;
+
+    let response = SearchExtendedResult {
+        ok: true,
+        query,
+        matches: SearchExtendedResultMatches {
+            transactions: SearchExtendedResultTransactions {
+                address_involved,
+                name_involved,
+                metadata,
+            },
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+fn parse_query(query: &ReqSearchQuery) -> Result<SearchQueryMatch, KristError> {
+    let q = query.q.as_deref().map(str::trim).unwrap_or_default();
+
+    if q.is_empty() {
+        return Err(KristError::Generic(GenericError::MissingParameter(
+            "q".to_string(),
+        )));
+    }
+
+    if q.len() > SEARCH_QUERY_MAX_LENGTH {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "q".to_string(),
+        )));
+    }
+
+    Ok(SearchQueryMatch::parse(q))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(search);
+    cfg.service(search_extended);
+}
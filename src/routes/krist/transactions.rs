@@ -1,22 +1,25 @@
-use actix_web::{HttpResponse, get, post, web};
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
 use rust_decimal::dec;
 
 use crate::database::ModelExt;
 use crate::database::transaction::{
-    Model as Transaction, TransactionCreateData, TransactionNameData, TransactionType,
+    self, Model as Transaction, TransactionCreateData, TransactionNameData, TransactionType,
 };
 use crate::database::wallet::Model as Wallet;
 
-use crate::database::name::Model as Name;
+use crate::database::name::{self, Model as Name};
+use crate::database::name_route::Model as NameRoute;
+use crate::database::outbox::Model as Outbox;
 use crate::errors::krist::address::AddressError;
 use crate::errors::krist::generic::GenericError;
 use crate::errors::krist::name::NameError;
 use crate::errors::krist::transaction::TransactionError;
 use crate::models::krist::transactions::{
+    BatchTransactionDetails, BatchTransactionResponse, ChainHeadResponse, MetaQuery, RefundRequest,
     TransactionDetails, TransactionJson, TransactionListResponse, TransactionResponse,
 };
-use crate::models::krist::websockets::{WebSocketEvent, WebSocketMessage};
-use crate::utils::validation::NAME_META_RE;
+use crate::models::krist::websockets::WebSocketEvent;
+use crate::utils::validation::{self, NAME_META_RE};
 
 use crate::websockets::WebSocketServer;
 use crate::{AppState, errors::krist::KristError, routes::PaginationParams};
@@ -25,6 +28,7 @@ use crate::{AppState, errors::krist::KristError, routes::PaginationParams};
 pub async fn transaction_list(
     state: web::Data<AppState>,
     query: web::Query<PaginationParams>,
+    meta: web::Query<MetaQuery>,
 ) -> Result<HttpResponse, KristError> {
     let params = query.into_inner();
     let pool = &state.pool;
@@ -36,8 +40,10 @@ pub async fn transaction_list(
 
     tx.commit().await?;
 
-    let transactions: Vec<TransactionJson> =
-        transactions.into_iter().map(|trans| trans.into()).collect();
+    let transactions: Vec<TransactionJson> = transactions
+        .into_iter()
+        .map(|trans| meta.apply(trans))
+        .collect();
 
     let response = TransactionListResponse {
         ok: true,
@@ -49,16 +55,33 @@ pub async fn transaction_list(
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// Optional header carrying a client-chosen key, retries with the same key return the original transaction.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
 #[post("")]
 async fn transaction_create(
+    req: HttpRequest,
     state: web::Data<AppState>,
     server: web::Data<WebSocketServer>,
     details: web::Json<TransactionDetails>,
+    meta: web::Query<MetaQuery>,
 ) -> Result<HttpResponse, KristError> {
     let pool = &state.pool;
     let details = details.into_inner();
     let amount = details.amount.round_dp(2); // Do not allow more than 2 decimals after the dot.
 
+    let idempotency_key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => match value.to_str() {
+            Ok(key) if validation::is_valid_idempotency_key(key) => Some(key.to_owned()),
+            _ => {
+                return Err(KristError::Generic(GenericError::InvalidParameter(
+                    "idempotency_key".to_string(),
+                )));
+            }
+        },
+        None => None,
+    };
+
     // Check if the `to` field is not empty and must be below or equal to 64.
     // The length check is for making sure there is enough space for metaname too.
     if details.to.is_empty() && details.to.len() <= 64 {
@@ -74,15 +97,43 @@ async fn transaction_create(
         )));
     }
 
-    let mut tx = pool.begin().await?;
+    if details
+        .metadata
+        .as_deref()
+        .is_some_and(|metadata| !validation::is_valid_metadata(metadata))
+    {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "metadata".to_string(),
+        )));
+    }
 
     let sender_verify_response = Wallet::verify_address(pool, details.private_key).await?;
     if !sender_verify_response.authed {
         return Err(KristError::Address(AddressError::AuthFailed));
     }
 
+    let mut tx = pool.begin().await?;
+
     let sender = sender_verify_response.model;
 
+    let request_hash = idempotency_key
+        .as_ref()
+        .map(|_| transaction::request_hash(&details.to, amount, details.metadata.as_deref()));
+    if let (Some(key), Some(hash)) = (&idempotency_key, &request_hash)
+        && let Some(original) =
+            Transaction::find_replay(&mut *tx, &sender.address, key, hash).await?
+    {
+        tx.commit().await?;
+        tracing::info!("Replayed transaction {} for idempotency key", original.id);
+
+        let response = TransactionResponse {
+            ok: true,
+            transaction: meta.apply(original).with_receipt(),
+            refund: None,
+        };
+        return Ok(HttpResponse::Ok().json(response));
+    }
+
     let is_name = NAME_META_RE.is_match(&details.to);
 
     let name_data = is_name.then(|| TransactionNameData::parse(&details.to));
@@ -91,7 +142,7 @@ async fn transaction_create(
         None => (None, None),
     };
 
-    let recipient = match is_name {
+    let (recipient, auto_refund) = match is_name {
         true => {
             // Cursed but makes borrow checker happy, lol.
             let name = sent_name.as_deref().unwrap_or_default();
@@ -100,18 +151,21 @@ async fn transaction_create(
                 .await?
                 .ok_or_else(|| KristError::Name(NameError::NameNotFound(details.to.clone())))?;
 
-            let owner = name.owner(&mut *tx).await?;
-            owner.ok_or_else(|| KristError::Name(NameError::NameNotFound(details.to.clone())))?
+            let address = NameRoute::resolve(&mut *tx, &name, sent_metaname.as_deref()).await?;
+            let recipient = Wallet::fetch_by_address(&mut *tx, &address)
+                .await?
+                .ok_or_else(|| KristError::Address(AddressError::NotFound(address)))?;
+
+            (recipient, name.should_refund(sent_metaname.as_deref()))
         }
-        false => Wallet::fetch_by_address(&mut *tx, &details.to)
-            .await?
-            .ok_or_else(|| KristError::Address(AddressError::NotFound(details.to.clone())))?,
-    };
+        false => {
+            let recipient = Wallet::fetch_by_address(&mut *tx, &details.to)
+                .await?
+                .ok_or_else(|| KristError::Address(AddressError::NotFound(details.to.clone())))?;
 
-    // Make sure to check the request to see if the funds are available.
-    if sender.balance < amount {
-        return Err(KristError::Transaction(TransactionError::InsufficientFunds));
-    }
+            (recipient, false)
+        }
+    };
 
     if sender.address == recipient.address {
         return Err(KristError::Transaction(
@@ -127,31 +181,111 @@ async fn transaction_create(
         sent_name,
         metadata: details.metadata,
         transaction_type: TransactionType::Transfer,
+        idempotency_key,
+        request_hash,
         ..Default::default()
     };
 
+    if auto_refund {
+        Transaction::lock_for_refund(&mut *tx, &creation_data).await?;
+    }
+
+    // The balance check happens inside `Transaction::create` while the sender row is locked.
     let transaction = Transaction::create(&mut *tx, creation_data).await?;
+    let transaction_id = transaction.id;
     let transaction_json: TransactionJson = transaction.into();
 
-    tx.commit().await?;
-
-    let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
+    let event = WebSocketEvent::Transaction {
         transaction: transaction_json.clone(),
-    });
-    server.broadcast_event(event).await;
+    };
+    Outbox::enqueue(&mut *tx, &event).await?;
+
+    let refund = match auto_refund {
+        true => {
+            let refund = Transaction::refund(
+                &mut *tx,
+                transaction_id,
+                amount,
+                Some(name::AUTO_REFUND_MESSAGE),
+            )
+            .await?;
+            let event = WebSocketEvent::Transaction {
+                transaction: refund.clone().into(),
+            };
+            Outbox::enqueue(&mut *tx, &event).await?;
+
+            Some(refund)
+        }
+        false => None,
+    };
+
+    tx.commit().await?;
+    server.notify_outbox();
 
     let final_response = TransactionResponse {
         ok: true,
-        transaction: transaction_json,
+        transaction: meta.apply(transaction_json).with_receipt(),
+        refund: refund.map(|refund| meta.apply(refund).with_receipt()),
     };
 
     Ok(HttpResponse::Ok().json(final_response))
 }
 
+/// Send several transfers from one wallet at once, all of them or none.
+#[post("/batch")]
+async fn transaction_batch(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    details: web::Json<BatchTransactionDetails>,
+    meta: web::Query<MetaQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let details = details.into_inner();
+
+    let sender = Wallet::verify_address(pool, details.private_key).await?;
+    if !sender.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let result =
+        Transaction::create_batch(&mut *tx, &sender.model.address, &details.transfers).await?;
+
+    let mut made: Vec<&Transaction> = result.transactions.iter().chain(&result.refunds).collect();
+    made.sort_by_key(|transaction| transaction.id);
+    for transaction in made {
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.clone().into(),
+        };
+        Outbox::enqueue(&mut *tx, &event).await?;
+    }
+
+    tx.commit().await?;
+    server.notify_outbox();
+
+    let response = BatchTransactionResponse {
+        ok: true,
+        transactions: result
+            .transactions
+            .into_iter()
+            .map(|transaction| meta.apply(transaction).with_receipt())
+            .collect(),
+        refunds: result
+            .refunds
+            .into_iter()
+            .map(|refund| meta.apply(refund).with_receipt())
+            .collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 #[get("/latest")]
 async fn transaction_latest(
     state: web::Data<AppState>,
     query: web::Query<PaginationParams>,
+    meta: web::Query<MetaQuery>,
 ) -> Result<HttpResponse, KristError> {
     let params = query.into_inner();
     let pool = &state.pool;
@@ -159,8 +293,10 @@ async fn transaction_latest(
     let total = Transaction::total_count_no_mined(pool, &params).await?;
     let transactions = Transaction::sorted_by_date(pool, &params).await?;
 
-    let transactions: Vec<TransactionJson> =
-        transactions.into_iter().map(|trans| trans.into()).collect();
+    let transactions: Vec<TransactionJson> = transactions
+        .into_iter()
+        .map(|trans| meta.apply(trans))
+        .collect();
 
     let response = TransactionListResponse {
         ok: true,
@@ -172,10 +308,19 @@ async fn transaction_latest(
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// The newest transaction in the hash chain, for outside observers to pin.
+#[get("/head")]
+async fn transaction_chain_head(state: web::Data<AppState>) -> Result<HttpResponse, KristError> {
+    let head = Transaction::chain_head(&state.pool).await?;
+
+    Ok(HttpResponse::Ok().json(ChainHeadResponse { ok: true, head }))
+}
+
 #[get("/{id}")]
 async fn transaction_get(
     state: web::Data<AppState>,
     id: web::Path<i64>,
+    meta: web::Query<MetaQuery>,
 ) -> Result<HttpResponse, KristError> {
     let id = id.into_inner();
     let pool = &state.pool;
@@ -184,17 +329,94 @@ async fn transaction_get(
 
     slim.map(|trans| TransactionResponse {
         ok: true,
-        transaction: trans.into(),
+        transaction: meta.apply(trans).with_receipt(),
+        refund: None,
     })
     .map(|response| HttpResponse::Ok().json(response))
     .ok_or_else(|| KristError::Transaction(TransactionError::NotFound))
 }
 
+#[post("/{id}/refund")]
+async fn transaction_refund(
+    state: web::Data<AppState>,
+    server: web::Data<WebSocketServer>,
+    id: web::Path<i32>,
+    details: web::Json<RefundRequest>,
+    meta: web::Query<MetaQuery>,
+) -> Result<HttpResponse, KristError> {
+    let pool = &state.pool;
+    let id = id.into_inner();
+    let details = details.into_inner();
+
+    let amount = details.amount.map(|amount| amount.round_dp(2));
+    if amount.is_some_and(|amount| amount <= dec!(0.00)) {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "amount".to_string(),
+        )));
+    }
+
+    // The message ends up in CommonMeta, so it can't contain the separator.
+    if details
+        .message
+        .as_deref()
+        .is_some_and(|message| message.contains(';') || !validation::is_valid_metadata(message))
+    {
+        return Err(KristError::Generic(GenericError::InvalidParameter(
+            "message".to_string(),
+        )));
+    }
+
+    let refunder = Wallet::verify_address(pool, details.private_key).await?;
+    if !refunder.authed {
+        return Err(KristError::Address(AddressError::AuthFailed));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    // Only whoever received the transaction can send it back.
+    let original = Transaction::fetch_by_id(&mut *tx, id)
+        .await?
+        .ok_or_else(|| KristError::Transaction(TransactionError::NotFound))?;
+    if original.to != refunder.model.address {
+        return Err(KristError::Transaction(TransactionError::NotRefundable(id)));
+    }
+
+    let amount = match amount {
+        Some(amount) => amount,
+        None => original.amount - Transaction::refunded_amount(&mut *tx, id).await?,
+    };
+    if amount <= dec!(0.00) {
+        return Err(KristError::Transaction(TransactionError::RefundTooLarge(
+            id,
+        )));
+    }
+
+    let refund = Transaction::refund(&mut *tx, id, amount, details.message.as_deref()).await?;
+    let event = WebSocketEvent::Transaction {
+        transaction: refund.clone().into(),
+    };
+    Outbox::enqueue(&mut *tx, &event).await?;
+
+    tx.commit().await?;
+    server.notify_outbox();
+
+    let response = TransactionResponse {
+        ok: true,
+        transaction: meta.apply(refund).with_receipt(),
+        refund: None,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/transactions")
             .service(transaction_create)
+            .service(transaction_batch)
             .service(transaction_latest)
+            .service(transaction_chain_head)
+            .service(transaction_refund)
             .service(transaction_get)
             .service(transaction_list),
     );
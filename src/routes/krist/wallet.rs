@@ -2,6 +2,7 @@ use actix_web::{HttpResponse, get, web};
 
 use crate::AppState;
 
+use crate::database::payment_schedule::Model as PaymentSchedule;
 use crate::database::{ModelExt, wallet::Model as Wallet};
 use crate::errors::krist::KristError;
 use crate::errors::krist::address::AddressError;
@@ -9,7 +10,8 @@ use crate::models::krist::addresses::{
     AddressGetQuery, AddressJson, AddressListResponse, AddressResponse,
 };
 use crate::models::krist::names::{NameJson, NameListResponse};
-use crate::models::krist::transactions::{TransactionJson, TransactionListResponse};
+use crate::models::krist::schedules::{ScheduleJson, ScheduleListResponse};
+use crate::models::krist::transactions::{MetaQuery, TransactionJson, TransactionListResponse};
 use crate::routes::PaginationParams;
 
 #[get("")]
@@ -95,6 +97,7 @@ async fn wallet_get_transactions(
     state: web::Data<AppState>,
     address: web::Path<String>,
     params: web::Query<PaginationParams>,
+    meta: web::Query<MetaQuery>,
 ) -> Result<HttpResponse, KristError> {
     let address = address.into_inner();
     let params = params.into_inner();
@@ -114,8 +117,10 @@ async fn wallet_get_transactions(
 
     tx.commit().await?;
 
-    let transactions: Vec<TransactionJson> =
-        transactions.into_iter().map(|trans| trans.into()).collect();
+    let transactions: Vec<TransactionJson> = transactions
+        .into_iter()
+        .map(|trans| meta.apply(trans))
+        .collect();
 
     let response = TransactionListResponse {
         ok: true,
@@ -160,6 +165,45 @@ async fn wallet_get_names(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[get("/{address}/schedules")]
+async fn wallet_get_schedules(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KristError> {
+    let address = address.into_inner();
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+    let pool = &state.pool;
+
+    let mut tx = pool.begin().await?;
+
+    let wallet = Wallet::fetch_by_address(&mut *tx, &address)
+        .await
+        .map_err(KristError::from)?
+        .ok_or_else(|| KristError::Address(AddressError::NotFound(address)))?;
+
+    let total = PaymentSchedule::count_for_address(&mut *tx, &wallet.address).await?;
+    let schedules =
+        PaymentSchedule::fetch_for_address(&mut *tx, &wallet.address, limit, offset).await?;
+
+    tx.commit().await?;
+
+    let schedules: Vec<ScheduleJson> = schedules
+        .into_iter()
+        .map(|schedule| schedule.into())
+        .collect();
+    let response = ScheduleListResponse {
+        ok: true,
+        count: schedules.len(),
+        total,
+        schedules,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/addresses")
@@ -167,6 +211,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(wallet_get)
             .service(wallet_get_transactions)
             .service(wallet_get_names)
+            .service(wallet_get_schedules)
             .service(wallet_list),
     );
 }
@@ -13,6 +13,7 @@ use tokio::sync::Mutex;
 use uuid::Uuid;
 
 use crate::AppState;
+use crate::config::get_config;
 use crate::database::wallet::Model as Wallet;
 use crate::errors::krist::{KristError, address::AddressError, websockets::WebSocketError};
 use crate::models::krist::websockets::{WebSocketMessage, WebSocketMessageInner};
@@ -40,6 +41,11 @@ pub async fn setup_ws(
             let wallet = Wallet::verify_address(pool, &private_key)
                 .await
                 .map_err(|_| KristError::Address(AddressError::AuthFailed))?;
+            if wallet.authed && wallet.model.locked {
+                return Err(KristError::Address(AddressError::Locked(
+                    wallet.model.address,
+                )));
+            }
             let model = wallet.model;
 
             let token_data = WebSocketTokenData::new(model.address, Some(private_key));
@@ -108,7 +114,7 @@ pub async fn gateway(
     let alive2 = alive.clone();
     let session_closed2 = session_closed.clone();
 
-    handler::send_hello_message(&mut session).await;
+    handler::send_hello_message(&state.pool, &mut session).await;
 
     let cleanup_session =
         |server: Arc<WebSocketServer>, uuid: Uuid, session_closed: Arc<AtomicBool>| async move {
@@ -175,24 +181,25 @@ pub async fn gateway(
         while let Some(Ok(msg)) = stream.recv().await {
             match msg {
                 AggregatedMessage::Ping(bytes) => {
-                    if session.pong(&bytes).await.is_err() {
+                    let Ok(()) = session.pong(&bytes).await else {
                         tracing::error!("Failed to send pong back to session");
                         return;
-                    }
+                    };
                 }
 
                 AggregatedMessage::Text(string) => {
-                    if string.chars().count() > 512 {
+                    let max_length = get_config().limits.max_ws_message_length;
+                    if string.chars().count() > max_length {
                         // TODO: Possibly use error message struct in models
                         // This isn't super necessary though and this shortcut saves some unnecessary error handling...
                         let error_msg = json!({
                             "ok": "false",
                             "error": "message_too_long",
-                            "message": "Message larger than 512 characters",
+                            "message": format!("Message larger than {max_length} characters"),
                             "type": "error"
                         })
                         .to_string();
-                        tracing::info!("Message received was larger than 512 characters");
+                        tracing::info!("Message received was larger than {max_length} characters");
 
                         let _ = session.text(error_msg).await;
                     } else {
@@ -0,0 +1,330 @@
+//! [CommonMeta](https://docs.krist.dev/docs/commonmeta.html), the format Krist clients use for
+//! transaction metadata: `;`-separated entries that are either `key=value` pairs or plain values.
+//!
+//! The first entry may be the recipient (`meta@name.kro`), and the `return` key tells where
+//! refunds and replies should be sent.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::utils::validation;
+
+/// A single `key=value` pair, or a plain value if `key` is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaEntry {
+    pub key: Option<String>,
+    pub value: String,
+}
+
+/// Where a `meta@name.kro` recipient or a `return` field points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaRecipient {
+    Name {
+        metaname: Option<String>,
+        /// The name as stored, punycode-encoded if it is internationalized.
+        name: String,
+    },
+    Address(String),
+}
+
+impl MetaRecipient {
+    /// Parse `meta@name.kro`, `name.kro` or a plain address.
+    pub fn parse(value: &str) -> Option<Self> {
+        if validation::is_valid_kromer_address(value) {
+            return Some(Self::Address(value.to_owned()));
+        }
+
+        let captures = validation::NAME_META_RE.captures(value)?;
+        let name = validation::normalize_name(captures.get(2)?.as_str())?;
+        let metaname = captures.get(1).map(|m| m.as_str().to_owned());
+
+        Some(Self::Name { metaname, name })
+    }
+}
+
+impl fmt::Display for MetaRecipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name {
+                metaname: Some(metaname),
+                name,
+            } => write!(f, "{metaname}@{name}.kro"),
+            Self::Name {
+                metaname: None,
+                name,
+            } => write!(f, "{name}.kro"),
+            Self::Address(address) => write!(f, "{address}"),
+        }
+    }
+}
+
+/// Parsed CommonMeta. Serializing it with [`ToString`] gives back exactly what was parsed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommonMeta {
+    pub entries: Vec<MetaEntry>,
+}
+
+impl CommonMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse metadata, this never fails as any string is valid CommonMeta.
+    ///
+    /// # Examples
+    /// ```
+    /// use kromer::utils::common_meta::CommonMeta;
+    /// let meta = CommonMeta::parse("shop@store.kro;message=thanks;return=kaaaaaaaaa");
+    /// assert_eq!(meta.get("message"), Some("thanks"));
+    /// assert_eq!(meta.to_string(), "shop@store.kro;message=thanks;return=kaaaaaaaaa");
+    /// ```
+    pub fn parse(metadata: &str) -> Self {
+        let entries = metadata
+            .split(';')
+            .map(|entry| match entry.split_once('=') {
+                Some((key, value)) => MetaEntry {
+                    key: Some(key.to_owned()),
+                    value: value.to_owned(),
+                },
+                None => MetaEntry {
+                    key: None,
+                    value: entry.to_owned(),
+                },
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// The value of `key`, later entries win like they do in Krist.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key.as_deref() == Some(key))
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Set `key` to `value`, replacing the last entry with that key or adding a new one.
+    ///
+    /// Returns `false` and leaves the metadata alone if the pair can't be written as CommonMeta,
+    /// i.e. the key contains `=` or `;`, or the value contains `;`.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        if key.contains(['=', ';']) || value.contains(';') {
+            return false;
+        }
+
+        match self
+            .entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.key.as_deref() == Some(key))
+        {
+            Some(entry) => entry.value = value.to_owned(),
+            None => self.entries.push(MetaEntry {
+                key: Some(key.to_owned()),
+                value: value.to_owned(),
+            }),
+        }
+
+        true
+    }
+
+    /// The `meta@name.kro` recipient, which has to be the first entry.
+    pub fn recipient(&self) -> Option<MetaRecipient> {
+        let first = self.entries.first().filter(|entry| entry.key.is_none())?;
+
+        match MetaRecipient::parse(&first.value)? {
+            recipient @ MetaRecipient::Name { .. } => Some(recipient),
+            MetaRecipient::Address(_) => None,
+        }
+    }
+
+    /// Where refunds and replies should go, from the `return` field.
+    pub fn return_to(&self) -> Option<MetaRecipient> {
+        MetaRecipient::parse(self.get("return")?)
+    }
+
+    /// The flat object Krist clients expect: every `key=value` pair, plain values keyed by their
+    /// position, and the `metaname`, `name` and `recipient` of the first entry.
+    pub fn to_fields(&self) -> BTreeMap<String, String> {
+        let mut fields = BTreeMap::new();
+
+        let recipient = self.recipient();
+        for (index, entry) in self.entries.iter().enumerate() {
+            match &entry.key {
+                Some(key) => fields.insert(key.clone(), entry.value.clone()),
+                None if index == 0 && recipient.is_some() => None,
+                None => fields.insert(index.to_string(), entry.value.clone()),
+            };
+        }
+
+        if let Some(recipient @ MetaRecipient::Name { metaname, name }) = &recipient {
+            if let Some(metaname) = metaname {
+                fields.insert("metaname".to_owned(), metaname.clone());
+            }
+            fields.insert("name".to_owned(), name.clone());
+            fields.insert("recipient".to_owned(), recipient.to_string());
+        }
+
+        fields
+    }
+}
+
+impl fmt::Display for CommonMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                write!(f, ";")?;
+            }
+
+            match &entry.key {
+                Some(key) => write!(f, "{key}={}", entry.value)?,
+                None => write!(f, "{}", entry.value)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const ROUND_TRIP: &[&str] = &[
+        "",
+        ";",
+        ";;",
+        "=",
+        "==",
+        "a=",
+        "=b",
+        "a=b=c",
+        "plain",
+        "shop@store.kro",
+        "shop@store.kro;message=hello world;return=kaaaaaaaaa",
+        "store.kro;1;2;3",
+        "key=value;key=other",
+        "message=naïve ☃;username=名前",
+        " spaced = out ; ",
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        for metadata in ROUND_TRIP {
+            assert_eq!(CommonMeta::parse(metadata).to_string(), *metadata);
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        let meta = CommonMeta::parse("shop@名前.kro;hello;message=a=b;return=refunds@shop.kro");
+        let fields = meta.to_fields();
+
+        assert_eq!(fields["metaname"], "shop");
+        assert_eq!(fields["name"], "xn--ldr85b");
+        assert_eq!(fields["recipient"], "shop@xn--ldr85b.kro");
+        assert_eq!(fields["1"], "hello");
+        assert_eq!(fields["message"], "a=b");
+        assert_eq!(fields["return"], "refunds@shop.kro");
+        assert!(!fields.contains_key("0"));
+
+        assert_eq!(
+            meta.return_to(),
+            Some(MetaRecipient::Name {
+                metaname: Some("refunds".to_owned()),
+                name: "shop".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_recipient_must_come_first() {
+        let meta = CommonMeta::parse("message=hi;shop@store.kro");
+        assert_eq!(meta.recipient(), None);
+        assert_eq!(meta.to_fields()["1"], "shop@store.kro");
+
+        // Krist only treats names as recipients, an address is just a plain value.
+        let meta = CommonMeta::parse("kaaaaaaaaa;return=kbbbbbbbbb");
+        assert_eq!(meta.recipient(), None);
+        assert_eq!(meta.to_fields()["0"], "kaaaaaaaaa");
+        assert_eq!(
+            meta.return_to(),
+            Some(MetaRecipient::Address("kbbbbbbbbb".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let mut meta = CommonMeta::parse("store.kro;ref=1;ref=2");
+        assert!(meta.set("ref", "3"));
+        assert!(meta.set("type", "refund"));
+        assert_eq!(meta.to_string(), "store.kro;ref=1;ref=3;type=refund");
+        assert_eq!(meta.get("ref"), Some("3"));
+
+        assert!(!meta.set("a=b", "c"));
+        assert!(!meta.set("a;b", "c"));
+        assert!(!meta.set("a", "b;c"));
+        assert_eq!(meta.to_string(), "store.kro;ref=1;ref=3;type=refund");
+
+        let mut meta = CommonMeta::new();
+        assert!(meta.set("return", "kaaaaaaaaa"));
+        assert_eq!(meta.to_string(), "return=kaaaaaaaaa");
+    }
+
+    /// A random string biased towards the characters CommonMeta cares about.
+    fn random_string(rng: &mut StdRng, max_len: usize) -> String {
+        const ALPHABET: &[char] = &[';', '=', '@', '.', 'a', 'k', 'r', 'o', '0', ' ', 'é', '名'];
+
+        let len = rng.random_range(0..=max_len);
+        (0..len)
+            .map(|_| ALPHABET[rng.random_range(0..ALPHABET.len())])
+            .collect()
+    }
+
+    #[test]
+    fn test_fuzz_parse_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0x6b726f6d6572);
+
+        for _ in 0..10_000 {
+            let metadata = random_string(&mut rng, 32);
+            let meta = CommonMeta::parse(&metadata);
+
+            assert_eq!(meta.to_string(), metadata);
+            assert_eq!(meta.entries.len(), metadata.matches(';').count() + 1);
+            let _ = meta.to_fields();
+        }
+    }
+
+    #[test]
+    fn test_fuzz_set_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0x6b726f6d6572 + 1);
+
+        for _ in 0..2_000 {
+            let mut meta = CommonMeta::new();
+            let mut expected = BTreeMap::new();
+
+            for _ in 0..rng.random_range(0..8) {
+                let key = random_string(&mut rng, 6);
+                let value = random_string(&mut rng, 12);
+
+                let valid = !key.contains(['=', ';']) && !value.contains(';');
+                assert_eq!(meta.set(&key, &value), valid);
+                if valid {
+                    expected.insert(key, value);
+                }
+            }
+
+            let parsed = CommonMeta::parse(&meta.to_string());
+            for (key, value) in &expected {
+                assert_eq!(parsed.get(key), Some(value.as_str()));
+            }
+            if !meta.entries.is_empty() {
+                assert_eq!(parsed, meta);
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Internationalized names. Unicode names are stored and looked up in their punycode (`xn--`)
+//! form, and only converted back to Unicode for display.
+
+use unicode_security::{RestrictionLevel, RestrictionLevelDetection, skeleton};
+
+/// The prefix every punycode-encoded name starts with.
+pub const ACE_PREFIX: &str = "xn--";
+
+/// Convert a name to the ASCII form it is stored in, `None` if it is not a valid name.
+///
+/// The input must already be lowercase ASCII or Unicode, without the `.kro` suffix. Names that
+/// could be mistaken for a different name (see [`is_confusable`]) are rejected.
+pub fn to_ascii(name: &str) -> Option<String> {
+    if name.is_empty() || name.contains('.') {
+        return None;
+    }
+
+    // Also maps full width and other compatibility characters onto their usual form.
+    let ascii = idna::domain_to_ascii(name).ok()?;
+    if ascii.is_empty() || ascii.contains('.') {
+        return None;
+    }
+
+    let unicode = to_unicode(&ascii)?;
+    if is_confusable(&unicode) {
+        return None;
+    }
+
+    Some(ascii)
+}
+
+/// Convert a stored name back to Unicode, `None` if it is not valid punycode.
+pub fn to_unicode(ascii: &str) -> Option<String> {
+    if !ascii.starts_with(ACE_PREFIX) {
+        return Some(ascii.to_owned());
+    }
+
+    match idna::domain_to_unicode(ascii) {
+        (unicode, Ok(())) if !unicode.starts_with(ACE_PREFIX) => Some(unicode),
+        _ => None,
+    }
+}
+
+/// Whether a Unicode name could be mistaken for another name.
+///
+/// This rejects names that mix scripts in ways that are not common in real text (like Latin and
+/// Cyrillic), use characters that are not recommended in identifiers, or that are made entirely
+/// of characters that look like ASCII ones, like an all-Cyrillic `аре`.
+pub fn is_confusable(name: &str) -> bool {
+    if name.is_ascii() {
+        return false;
+    }
+
+    // Separators are common to every script.
+    let letters: String = name.chars().filter(|c| !matches!(c, '-' | '_')).collect();
+    if letters.as_str().detect_restriction_level() > RestrictionLevel::HighlyRestrictive {
+        return true;
+    }
+
+    skeleton(&letters).all(|c| c.is_ascii())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names that must be accepted, with the form they are stored in.
+    const VALID: &[(&str, &str)] = &[
+        ("example", "example"),
+        ("my-shop_2", "my-shop_2"),
+        ("名前", "xn--ldr85b"),
+        ("münchen", "xn--mnchen-3ya"),
+        ("日本語abc", "xn--abc-s08fl0dtz6h"),
+        ("한국어", "xn--3e0bk47br7k"),
+        ("ελληνικά", "xn--hxargifdar"),
+        ("москва", "xn--80adxhks"),
+        // Full width forms are folded into plain ASCII.
+        ("ｓｈｏｐ", "shop"),
+    ];
+
+    /// Names that look like another name and must be rejected.
+    const CONFUSABLE: &[&str] = &[
+        "pаypal",  // Cyrillic а
+        "gооgle",  // Cyrillic о
+        "аре",     // all Cyrillic, looks like `ape`
+        "ѕсоре",   // all Cyrillic, looks like `scope`
+        "αpple",   // Greek alpha
+        "krоmer",  // Cyrillic о
+        "名前аbc", // Han, Latin and Cyrillic
+        "ехаmple", // Cyrillic е and х
+        "🙂",
+    ];
+
+    #[test]
+    fn test_valid_names() {
+        for (name, ascii) in VALID {
+            assert_eq!(to_ascii(name).as_deref(), Some(*ascii), "{name}");
+
+            let expected = name.replace("ｓｈｏｐ", "shop");
+            assert_eq!(to_unicode(ascii).as_deref(), Some(expected.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_confusable_names() {
+        for name in CONFUSABLE {
+            assert_eq!(to_ascii(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_confusable_punycode_is_rejected() {
+        // Someone could try to skip the Unicode check by sending the punycode directly.
+        let ascii = idna::domain_to_ascii("pаypal").unwrap();
+        assert_eq!(to_ascii(&ascii), None);
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        assert_eq!(to_ascii(""), None);
+        assert_eq!(to_ascii("a.b"), None);
+        assert_eq!(to_ascii("名前.kro"), None);
+        assert_eq!(to_unicode("xn--"), None);
+        assert_eq!(to_unicode("xn--zzzz-"), None);
+    }
+}
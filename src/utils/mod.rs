@@ -1,2 +1,8 @@
+pub mod clock;
+pub mod common_meta;
 pub mod crypto;
+pub mod idn;
+pub mod name_pricing;
+pub mod receipts;
+pub mod recurrence;
 pub mod validation;
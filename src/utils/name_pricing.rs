@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::NamesConfig;
+
+/// What registering a name costs, and how that price came to be.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
+pub struct NamePrice {
+    /// The price before any surcharge, either `names.cost` or the matching length tier.
+    pub base_cost: i64,
+    /// The `max_length` of the length tier the base cost came from, if any.
+    pub length_tier: Option<usize>,
+    /// The first reserved word found in the name.
+    pub reserved_word: Option<String>,
+    pub reserved_surcharge: i64,
+    /// Set by an admin for this exact name, replaces everything above.
+    pub override_cost: Option<i64>,
+    pub total: i64,
+}
+
+impl NamePrice {
+    /// Price `name` (without the `.kro` suffix) using the rules in `config`.
+    pub fn compute(config: &NamesConfig, name: &str, override_cost: Option<i64>) -> Self {
+        let length = name.chars().count();
+
+        let tier = config
+            .length_tiers
+            .iter()
+            .filter(|tier| length <= tier.max_length)
+            .min_by_key(|tier| tier.max_length);
+        let base_cost = tier.map_or(config.cost, |tier| tier.cost);
+
+        let reserved_word = config
+            .reserved_words
+            .iter()
+            .find(|word| name.contains(word.as_str()))
+            .cloned();
+        let reserved_surcharge = match reserved_word {
+            Some(_) => config.reserved_word_surcharge,
+            None => 0,
+        };
+
+        Self {
+            base_cost,
+            length_tier: tier.map(|tier| tier.max_length),
+            reserved_word,
+            reserved_surcharge,
+            override_cost,
+            total: override_cost.unwrap_or(base_cost + reserved_surcharge),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NameLengthTier;
+
+    fn config() -> NamesConfig {
+        NamesConfig {
+            cost: 500,
+            length_tiers: vec![
+                NameLengthTier {
+                    max_length: 3,
+                    cost: 1000,
+                },
+                NameLengthTier {
+                    max_length: 1,
+                    cost: 5000,
+                },
+            ],
+            reserved_words: vec!["kromer".to_owned(), "admin".to_owned()],
+            reserved_word_surcharge: 250,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_length_tiers() {
+        let config = config();
+
+        let price = NamePrice::compute(&config, "a", None);
+        assert_eq!((price.base_cost, price.length_tier), (5000, Some(1)));
+
+        let price = NamePrice::compute(&config, "abc", None);
+        assert_eq!((price.base_cost, price.length_tier), (1000, Some(3)));
+
+        let price = NamePrice::compute(&config, "abcd", None);
+        assert_eq!((price.base_cost, price.length_tier), (500, None));
+        assert_eq!(price.total, 500);
+    }
+
+    #[test]
+    fn test_reserved_word_surcharge() {
+        let price = NamePrice::compute(&config(), "mykromershop", None);
+
+        assert_eq!(price.reserved_word.as_deref(), Some("kromer"));
+        assert_eq!(price.reserved_surcharge, 250);
+        assert_eq!(price.total, 750);
+    }
+
+    #[test]
+    fn test_override_replaces_price() {
+        let price = NamePrice::compute(&config(), "admin", Some(10));
+
+        assert_eq!(price.reserved_surcharge, 250);
+        assert_eq!(price.override_cost, Some(10));
+        assert_eq!(price.total, 10);
+    }
+}
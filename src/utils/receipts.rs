@@ -0,0 +1,304 @@
+//! Transaction receipts signed by the server, so shops can check a payment offline.
+//!
+//! A receipt carries the signed message itself, an ed25519 signature over it and the public key
+//! that made the signature. The message is the transaction's id, sender, recipient, value, sent
+//! name, sent metaname and time joined by `;`, with missing values left empty:
+//!
+//! ```text
+//! 12;kaaaaaaaaa;kbbbbbbbbb;1.5;shop;sales;2025-07-19T11:34:26.123456+00:00
+//! ```
+//!
+//! None of these fields can contain `;`. Verifying a receipt means checking the key is one the
+//! server publishes (see `/receipts/keys`), checking the signature, and then reading the message.
+
+use std::sync::OnceLock;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::Rng;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ReceiptsConfig;
+use crate::models::krist::transactions::TransactionJson;
+
+static SIGNER: OnceLock<ReceiptSigner> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Serialize, Deserialize)]
+pub struct Receipt {
+    /// The signed message, see the module docs for its format.
+    pub message: String,
+    /// The hex ed25519 signature over `message`.
+    pub signature: String,
+    /// The hex public key that made the signature.
+    pub key: String,
+}
+
+/// The fields a receipt vouches for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptData {
+    pub id: i32,
+    pub from: Option<String>,
+    pub to: String,
+    pub value: Decimal,
+    pub sent_name: Option<String>,
+    pub sent_metaname: Option<String>,
+    pub time: String,
+}
+
+impl ReceiptData {
+    pub fn message(&self) -> String {
+        format!(
+            "{};{};{};{};{};{};{}",
+            self.id,
+            self.from.as_deref().unwrap_or_default(),
+            self.to,
+            self.value.normalize(),
+            self.sent_name.as_deref().unwrap_or_default(),
+            self.sent_metaname.as_deref().unwrap_or_default(),
+            self.time,
+        )
+    }
+
+    /// Read the fields back out of a receipt message.
+    pub fn parse(message: &str) -> Option<Self> {
+        let fields: Vec<&str> = message.splitn(7, ';').collect();
+        let [id, from, to, value, sent_name, sent_metaname, time] = fields[..] else {
+            return None;
+        };
+        let optional = |field: &str| (!field.is_empty()).then(|| field.to_owned());
+
+        Some(Self {
+            id: id.parse().ok()?,
+            from: optional(from),
+            to: to.to_owned(),
+            value: value.parse().ok()?,
+            sent_name: optional(sent_name),
+            sent_metaname: optional(sent_metaname),
+            time: time.to_owned(),
+        })
+    }
+}
+
+impl From<&TransactionJson> for ReceiptData {
+    fn from(transaction: &TransactionJson) -> Self {
+        Self {
+            id: transaction.id,
+            from: transaction.from.clone(),
+            to: transaction.to.clone(),
+            value: transaction.value,
+            sent_name: transaction.sent_name.clone(),
+            sent_metaname: transaction.sent_metaname.clone(),
+            time: transaction.time.clone(),
+        }
+    }
+}
+
+/// Check `receipt` against the keys the server published and return what it vouches for.
+///
+/// Returns `None` if the key isn't trusted, the signature doesn't match or the message is
+/// malformed.
+pub fn verify(receipt: &Receipt, trusted_keys: &[String]) -> Option<ReceiptData> {
+    if !trusted_keys.contains(&receipt.key) {
+        return None;
+    }
+
+    let key = parse_public_key(&receipt.key)?;
+    let signature: [u8; 64] = hex::decode(&receipt.signature).ok()?.try_into().ok()?;
+    key.verify(
+        receipt.message.as_bytes(),
+        &Signature::from_bytes(&signature),
+    )
+    .ok()?;
+
+    ReceiptData::parse(&receipt.message)
+}
+
+pub fn parse_signing_key(key: &str) -> Option<SigningKey> {
+    let seed: [u8; 32] = hex::decode(key).ok()?.try_into().ok()?;
+
+    Some(SigningKey::from_bytes(&seed))
+}
+
+pub fn parse_public_key(key: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(key).ok()?.try_into().ok()?;
+
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// Signs receipts with the current key and knows the keys that were rotated out.
+#[derive(Debug, Clone)]
+pub struct ReceiptSigner {
+    signing_key: SigningKey,
+    retired_keys: Vec<String>,
+}
+
+impl ReceiptSigner {
+    pub fn new(signing_key: SigningKey, retired_keys: Vec<String>) -> Self {
+        Self {
+            signing_key,
+            retired_keys,
+        }
+    }
+
+    /// A signer with a fresh random key, its receipts only verify while this process runs.
+    pub fn generate() -> Self {
+        let seed: [u8; 32] = rand::rng().random();
+
+        Self::new(SigningKey::from_bytes(&seed), Vec::new())
+    }
+
+    /// The signer for an already validated config, generating a temporary key if none is
+    /// configured. The server refuses to start without one, see
+    /// [`crate::config::Config::require_signing_key`].
+    pub fn from_config(config: &ReceiptsConfig) -> Self {
+        match parse_signing_key(&config.signing_key) {
+            Some(signing_key) => Self::new(signing_key, config.retired_keys.clone()),
+            None => {
+                tracing::warn!(
+                    "No receipts.signing_key configured, receipts are signed with a temporary key"
+                );
+                Self {
+                    retired_keys: config.retired_keys.clone(),
+                    ..Self::generate()
+                }
+            }
+        }
+    }
+
+    /// The hex public key receipts are signed with now.
+    pub fn public_key(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().as_bytes())
+    }
+
+    pub fn retired_keys(&self) -> &[String] {
+        &self.retired_keys
+    }
+
+    /// Every key a valid receipt may have been signed with, the current one first.
+    pub fn trusted_keys(&self) -> Vec<String> {
+        let mut keys = vec![self.public_key()];
+        keys.extend(self.retired_keys.iter().cloned());
+
+        keys
+    }
+
+    pub fn sign(&self, data: &ReceiptData) -> Receipt {
+        let message = data.message();
+        let signature = self.signing_key.sign(message.as_bytes());
+
+        Receipt {
+            message,
+            signature: hex::encode(signature.to_bytes()),
+            key: self.public_key(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("The receipt signer was already initialised")]
+pub struct SignerAlreadyInitialised;
+
+/// Make `signer` the one [`get_signer`] returns. Fails if there already is one, e.g. because
+/// [`get_signer`] was called first and made a temporary one.
+pub fn init_signer(signer: ReceiptSigner) -> Result<(), SignerAlreadyInitialised> {
+    SIGNER.set(signer).map_err(|_| SignerAlreadyInitialised)
+}
+
+/// The loaded signer, or one with a temporary key if none was loaded (e.g. in tests).
+pub fn get_signer() -> &'static ReceiptSigner {
+    SIGNER.get_or_init(ReceiptSigner::generate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    fn data() -> ReceiptData {
+        ReceiptData {
+            id: 12,
+            from: Some("kaaaaaaaaa".to_owned()),
+            to: "kbbbbbbbbb".to_owned(),
+            value: dec!(1.50),
+            sent_name: Some("shop".to_owned()),
+            sent_metaname: None,
+            time: "2025-07-19T11:34:26.123456+00:00".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = ReceiptSigner::generate();
+        let receipt = signer.sign(&data());
+
+        assert_eq!(
+            receipt.message,
+            "12;kaaaaaaaaa;kbbbbbbbbb;1.5;shop;;2025-07-19T11:34:26.123456+00:00"
+        );
+        assert_eq!(verify(&receipt, &signer.trusted_keys()), Some(data()));
+
+        let mut tampered = receipt.clone();
+        tampered.message = tampered.message.replace(";1.5;", ";150;");
+        assert_eq!(verify(&tampered, &signer.trusted_keys()), None);
+
+        let mut tampered = receipt.clone();
+        let flipped = match &tampered.signature[..2] {
+            "00" => "ff",
+            _ => "00",
+        };
+        tampered.signature.replace_range(0..2, flipped);
+        assert_eq!(verify(&tampered, &signer.trusted_keys()), None);
+
+        // A valid signature from a key the server never published is worthless.
+        let forger = ReceiptSigner::generate();
+        assert_eq!(verify(&forger.sign(&data()), &signer.trusted_keys()), None);
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old = ReceiptSigner::generate();
+        let old_receipt = old.sign(&data());
+
+        let rotated = ReceiptSigner::from_config(&ReceiptsConfig {
+            signing_key: "11".repeat(32),
+            retired_keys: vec![old.public_key()],
+        });
+        let new_receipt = rotated.sign(&data());
+
+        assert_ne!(new_receipt.key, old_receipt.key);
+        assert_eq!(rotated.trusted_keys()[0], rotated.public_key());
+        assert_eq!(verify(&old_receipt, &rotated.trusted_keys()), Some(data()));
+        assert_eq!(verify(&new_receipt, &rotated.trusted_keys()), Some(data()));
+
+        // Dropping a retired key revokes every receipt it signed.
+        let revoked = ReceiptSigner::from_config(&ReceiptsConfig {
+            signing_key: "11".repeat(32),
+            retired_keys: Vec::new(),
+        });
+        assert_eq!(revoked.public_key(), rotated.public_key());
+        assert_eq!(verify(&old_receipt, &revoked.trusted_keys()), None);
+        assert_eq!(verify(&new_receipt, &revoked.trusted_keys()), Some(data()));
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let data = ReceiptData {
+            from: None,
+            sent_name: None,
+            ..data()
+        };
+        assert_eq!(ReceiptData::parse(&data.message()), Some(data));
+
+        assert_eq!(ReceiptData::parse("12;kaaaaaaaaa"), None);
+        assert_eq!(ReceiptData::parse("x;;kbbbbbbbbb;1;;;now"), None);
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let signer = ReceiptSigner::generate();
+
+        assert!(parse_public_key(&signer.public_key()).is_some());
+        assert!(parse_public_key("not hex").is_none());
+        assert!(parse_signing_key(&"ab".repeat(31)).is_none());
+    }
+}
@@ -0,0 +1,149 @@
+//! When recurring payments are due.
+//!
+//! A recurrence is either a fixed interval or a five field cron expression
+//! (`minute hour day-of-month month day-of-week`), like `0 12 * * MON` for every Monday at noon
+//! UTC. Days of the week are best given by name, as numbers they run from 1 (Sunday) to 7.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// The shortest interval accepted, the same resolution cron expressions have.
+pub const MIN_INTERVAL_SECS: i64 = 60;
+/// The longest interval accepted, a year.
+pub const MAX_INTERVAL_SECS: i64 = 365 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    Interval(TimeDelta),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Recurrence {
+    pub fn interval(secs: i64) -> Option<Self> {
+        if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&secs) {
+            return None;
+        }
+
+        TimeDelta::try_seconds(secs).map(Self::Interval)
+    }
+
+    pub fn cron(expression: &str) -> Option<Self> {
+        if expression.split_whitespace().count() != 5 {
+            return None;
+        }
+
+        // The cron crate wants seconds too, payments go out at the start of the minute.
+        let schedule = cron::Schedule::from_str(&format!("0 {expression}")).ok()?;
+
+        Some(Self::Cron(Box::new(schedule)))
+    }
+
+    /// The recurrence stored as a cron expression or an interval, whichever is set.
+    pub fn from_parts(cron: Option<&str>, interval_secs: Option<i64>) -> Option<Self> {
+        match (cron, interval_secs) {
+            (Some(expression), None) => Self::cron(expression),
+            (None, Some(secs)) => Self::interval(secs),
+            _ => None,
+        }
+    }
+
+    /// The first time after `now` a payment is due, given the previous one was due at `previous`.
+    ///
+    /// Intervals stay in step with `previous`, occurrences that were missed while nothing ran are
+    /// skipped rather than made up. `None` if there is no next time, including when it would be
+    /// out of range.
+    pub fn next_after(&self, previous: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Interval(interval) => {
+                let next = previous.checked_add_signed(*interval)?;
+                if next > now {
+                    return Some(next);
+                }
+
+                let missed = (now - previous).num_seconds() / interval.num_seconds();
+                let steps = i32::try_from(missed.checked_add(1)?).ok()?;
+                previous.checked_add_signed(interval.checked_mul(steps)?)
+            }
+            Self::Cron(schedule) => schedule.after(&previous.max(now)).next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::{Clock, FakeClock};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_interval() {
+        let clock = FakeClock::new(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap());
+        let hourly = Recurrence::interval(3600).unwrap();
+        let start = clock.now();
+
+        let next = hourly.next_after(start, clock.now()).unwrap();
+        assert_eq!(next, start + TimeDelta::hours(1));
+
+        // Running late doesn't shift the schedule.
+        clock.advance(TimeDelta::minutes(70));
+        let after = hourly.next_after(next, clock.now()).unwrap();
+        assert_eq!(after, start + TimeDelta::hours(2));
+
+        // A long outage skips what was missed.
+        clock.advance(TimeDelta::hours(5));
+        let after = hourly.next_after(next, clock.now()).unwrap();
+        assert_eq!(after, start + TimeDelta::hours(7));
+
+        assert_eq!(Recurrence::interval(59), None);
+        assert_eq!(Recurrence::interval(-3600), None);
+    }
+
+    #[test]
+    fn test_oversized_interval() {
+        assert!(Recurrence::interval(MAX_INTERVAL_SECS).is_some());
+        assert_eq!(Recurrence::interval(MAX_INTERVAL_SECS + 1), None);
+        assert_eq!(Recurrence::interval(10_i64.pow(15)), None);
+        assert_eq!(Recurrence::interval(i64::MAX), None);
+        assert_eq!(Recurrence::from_parts(None, Some(i64::MAX)), None);
+
+        // Times past the end of the calendar are no next time rather than a panic.
+        let yearly = Recurrence::interval(MAX_INTERVAL_SECS).unwrap();
+        let end = DateTime::<Utc>::MAX_UTC;
+        assert_eq!(yearly.next_after(end - TimeDelta::days(1), end), None);
+
+        let minutely = Recurrence::interval(MIN_INTERVAL_SECS).unwrap();
+        let start = DateTime::<Utc>::MIN_UTC;
+        assert_eq!(minutely.next_after(start, end), None);
+    }
+
+    #[test]
+    fn test_cron() {
+        // A Sunday.
+        let clock = FakeClock::new(Utc.with_ymd_and_hms(2026, 10, 18, 12, 30, 0).unwrap());
+        let weekly = Recurrence::cron("0 12 * * MON").unwrap();
+
+        let next = weekly.next_after(clock.now(), clock.now()).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap());
+
+        clock.set(next);
+        let after = weekly.next_after(next, clock.now()).unwrap();
+        assert_eq!(after, Utc.with_ymd_and_hms(2026, 10, 26, 12, 0, 0).unwrap());
+
+        let monthly = Recurrence::cron("0 0 1 * *").unwrap();
+        let next = monthly.next_after(clock.now(), clock.now()).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap());
+
+        assert_eq!(Recurrence::cron("0 0 1 * * *"), None);
+        assert_eq!(Recurrence::cron("every day"), None);
+        assert_eq!(Recurrence::cron("61 * * * *"), None);
+    }
+
+    #[test]
+    fn test_from_parts() {
+        assert!(Recurrence::from_parts(Some("* * * * *"), None).is_some());
+        assert!(Recurrence::from_parts(None, Some(3600)).is_some());
+        assert_eq!(Recurrence::from_parts(Some("* * * * *"), Some(3600)), None);
+        assert_eq!(Recurrence::from_parts(None, None), None);
+    }
+}
@@ -1,6 +1,9 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use crate::config::get_config;
+use crate::utils::idn;
+
 pub static ADDRESS_RE_V2: Lazy<Regex> = Lazy::new(|| Regex::new(r"^k[a-z0-9]{9}$").unwrap());
 pub static ADDRESS_LIST_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^(?:k[a-z0-9]{9}|[a-f0-9]{10})(?:,(?:k[a-z0-9]{9}|[a-f0-9]{10}))*$").unwrap()
@@ -9,8 +12,10 @@ pub static NAME_FETCH_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^(?:xn--)?[a-z0-9-_]{1,64}$").unwrap());
 pub static NAME_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z0-9_-]{1,64}$").unwrap());
 pub static NAME_A_RECORD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[^\s.?#].[^\s]*$").unwrap());
+pub static METANAME_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z0-9-_]{1,32}$").unwrap());
+/// The name part may be Unicode, see [`normalize_name`].
 pub static NAME_META_RE: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([a-z0-9]{1,64})\.kro$").unwrap());
+    Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([^\s@.]{1,64})\.kro$").unwrap());
 
 #[inline(always)]
 pub fn is_valid_name(name: &str, fetching: bool) -> bool {
@@ -23,6 +28,15 @@ pub fn is_valid_name(name: &str, fetching: bool) -> bool {
     }
 }
 
+/// Convert a name from a request to the form it is stored and looked up in: trimmed, lowercase
+/// and punycode-encoded if it is not plain ASCII. `None` if it is not a valid name.
+pub fn normalize_name(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    let ascii = idn::to_ascii(&name)?;
+
+    NAME_RE.is_match(&ascii).then_some(ascii)
+}
+
 #[inline(always)]
 pub fn is_valid_kromer_address(address: &str) -> bool {
     ADDRESS_RE_V2.is_match(address)
@@ -33,11 +47,26 @@ pub fn is_valid_kromer_address_list(address_list: &str) -> bool {
     ADDRESS_LIST_RE.is_match(address_list)
 }
 
+#[inline(always)]
+pub fn is_valid_metaname(metaname: &str) -> bool {
+    METANAME_RE.is_match(metaname)
+}
+
 #[inline(always)]
 pub fn is_valid_a_record(a: &str) -> bool {
     !a.is_empty() && a.len() <= 255 && NAME_A_RECORD_RE.is_match(a)
 }
 
+#[inline(always)]
+pub fn is_valid_idempotency_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 128
+}
+
+#[inline(always)]
+pub fn is_valid_metadata(metadata: &str) -> bool {
+    metadata.chars().count() <= get_config().limits.max_metadata_length
+}
+
 #[inline(always)]
 pub fn strip_name_suffix(name: &str) -> String {
     name.replace(".kro", "")
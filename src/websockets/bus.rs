@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use actix_web::rt::time;
+use sqlx::postgres::PgListener;
+use sqlx::{Pool, Postgres};
+
+use super::WebSocketServer;
+use crate::database::Result;
+use crate::database::outbox::{EVENT_CHANNEL, Model as Outbox, OUTBOX_CHANNEL};
+
+const LISTENER_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const CATCH_UP_BATCH_SIZE: i64 = 100;
+
+/// Connect to the event bus and subscribe to its channels.
+///
+/// Events that already exist are not delivered to this instance. Kept separate from
+/// [`run_listener`] so callers know the instance is listening before any event gets published.
+pub async fn listen(pool: &Pool<Postgres>, server: &WebSocketServer) -> Result<PgListener> {
+    server.skip_events_until(Outbox::latest_id(pool).await?);
+
+    subscribe(pool).await
+}
+
+async fn subscribe(pool: &Pool<Postgres>) -> Result<PgListener> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen_all([EVENT_CHANNEL, OUTBOX_CHANNEL]).await?;
+
+    Ok(listener)
+}
+
+/// Deliver events published by any instance to the websocket clients of this one, forever.
+///
+/// Notifications only say that something new was committed: every time one arrives, and after
+/// (re)connecting, the instance catches up on all events newer than the last one it delivered.
+/// Events committed while the connection was down are delivered once it is back.
+pub async fn run_listener(mut listener: PgListener, pool: Pool<Postgres>, server: WebSocketServer) {
+    catch_up_logged(&pool, &server).await;
+
+    loop {
+        let notification = match listener.try_recv().await {
+            Ok(Some(notification)) => notification,
+            Ok(None) => {
+                tracing::warn!("Event bus connection lost, reconnecting");
+                listener = reconnect(&pool).await;
+                catch_up_logged(&pool, &server).await;
+                continue;
+            }
+            Err(err) => {
+                tracing::error!("Event bus listener failed: {err}");
+                time::sleep(LISTENER_RETRY_INTERVAL).await;
+                listener = reconnect(&pool).await;
+                catch_up_logged(&pool, &server).await;
+                continue;
+            }
+        };
+
+        if notification.channel() == OUTBOX_CHANNEL {
+            server.notify_outbox();
+        }
+
+        catch_up_logged(&pool, &server).await;
+    }
+}
+
+/// Subscribe on a fresh connection, retrying until it works.
+async fn reconnect(pool: &Pool<Postgres>) -> PgListener {
+    loop {
+        match subscribe(pool).await {
+            Ok(listener) => return listener,
+            Err(err) => {
+                tracing::error!("Failed to reconnect to the event bus: {err}");
+                time::sleep(LISTENER_RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn catch_up_logged(pool: &Pool<Postgres>, server: &WebSocketServer) {
+    if let Err(err) = catch_up(pool, server).await {
+        tracing::error!("Failed to catch up on events: {err}");
+    }
+}
+
+/// Deliver every committed event newer than the last one this instance delivered, in id order.
+///
+/// Returns how many events were delivered.
+pub async fn catch_up(pool: &Pool<Postgres>, server: &WebSocketServer) -> Result<usize> {
+    let mut delivered = 0;
+
+    loop {
+        let entries =
+            Outbox::fetch_after(pool, server.last_event_id(), CATCH_UP_BATCH_SIZE).await?;
+        if entries.is_empty() {
+            return Ok(delivered);
+        }
+
+        for entry in entries {
+            match entry.event() {
+                Ok(event) => server.deliver_event(entry.id, event).await,
+                Err(err) => {
+                    tracing::error!("Dropping malformed outbox event {}: {err}", entry.id);
+                    server.skip_events_until(entry.id);
+                }
+            }
+            delivered += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::krist::names::NameJson;
+    use crate::models::krist::websockets::WebSocketEvent;
+    use crate::websockets::outbox::dispatch_pending;
+
+    fn name_event(name: &str) -> WebSocketEvent {
+        WebSocketEvent::Name {
+            name: NameJson {
+                name: name.to_owned(),
+                unicode_name: name.to_owned(),
+                owner: "kaaaaaaaaa".to_owned(),
+                original_owner: None,
+                registered: "2025-01-01T00:00:00+00:00".to_owned(),
+                updated: None,
+                transfered: None,
+                a: None,
+                unpaid: 0,
+                expires: "2025-02-01T00:00:00+00:00".to_owned(),
+                expired: false,
+            },
+        }
+    }
+
+    async fn wait_for_event(server: &WebSocketServer, event_id: i64) {
+        let wait = async {
+            while server.last_event_id() < event_id {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+
+        time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("event should reach every instance");
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_events_reach_every_instance(pool: Pool<Postgres>) -> Result<()> {
+        let first = WebSocketServer::new();
+        let second = WebSocketServer::new();
+
+        for server in [&first, &second] {
+            let listener = listen(&pool, server).await?;
+            tokio::spawn(run_listener(listener, pool.clone(), server.clone()));
+        }
+
+        let mut tx = pool.begin().await?;
+        let event = Outbox::enqueue(&mut *tx, &name_event("fanout")).await?;
+        tx.commit().await?;
+
+        assert_eq!(dispatch_pending(&pool).await?, 1);
+        wait_for_event(&first, event.id).await;
+        wait_for_event(&second, event.id).await;
+
+        // Nothing is left to publish, so neither instance sees the event twice.
+        assert_eq!(dispatch_pending(&pool).await?, 0);
+        assert_eq!(first.last_event_id(), event.id);
+        assert_eq!(second.last_event_id(), event.id);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_only_one_instance_dispatches(pool: Pool<Postgres>) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        Outbox::enqueue(&mut *tx, &name_event("contended")).await?;
+        tx.commit().await?;
+
+        let mut other = pool.begin().await?;
+        assert!(Outbox::try_lock_dispatcher(&mut *other).await?);
+        assert_eq!(dispatch_pending(&pool).await?, 0);
+        other.rollback().await?;
+
+        assert_eq!(dispatch_pending(&pool).await?, 1);
+        assert!(Outbox::fetch_pending(&pool, 10).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_events_missed_while_disconnected_are_delivered(
+        pool: Pool<Postgres>,
+    ) -> Result<()> {
+        let server = WebSocketServer::new();
+        let listener = listen(&pool, &server).await?;
+        tokio::spawn(run_listener(listener, pool.clone(), server.clone()));
+
+        let mut tx = pool.begin().await?;
+        let before = Outbox::enqueue(&mut *tx, &name_event("before")).await?;
+        tx.commit().await?;
+        dispatch_pending(&pool).await?;
+        wait_for_event(&server, before.id).await;
+
+        // Drop the listener's connection, so the next notification goes nowhere.
+        let q = "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE datname = current_database() AND query ILIKE 'LISTEN%' AND pid <> pg_backend_pid()";
+        let terminated: Vec<bool> = sqlx::query_scalar(q).fetch_all(&pool).await?;
+        assert_eq!(terminated, [true]);
+
+        let mut tx = pool.begin().await?;
+        let missed = Outbox::enqueue(&mut *tx, &name_event("missed")).await?;
+        tx.commit().await?;
+        assert_eq!(dispatch_pending(&pool).await?, 1);
+
+        wait_for_event(&server, missed.id).await;
+
+        let q = "SELECT COUNT(*) FROM pg_stat_activity WHERE datname = current_database() AND query ILIKE 'LISTEN%'";
+        let listening: i64 = sqlx::query_scalar(q).fetch_one(&pool).await?;
+        assert_eq!(listening, 1, "the listener should have reconnected");
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_existing_events_are_not_replayed(pool: Pool<Postgres>) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        let old = Outbox::enqueue(&mut *tx, &name_event("old")).await?;
+        tx.commit().await?;
+
+        let server = WebSocketServer::new();
+        let _listener = listen(&pool, &server).await?;
+        assert_eq!(server.last_event_id(), old.id);
+        assert_eq!(catch_up(&pool, &server).await?, 0);
+
+        let mut tx = pool.begin().await?;
+        let new = Outbox::enqueue(&mut *tx, &name_event("new")).await?;
+        tx.commit().await?;
+        assert_eq!(catch_up(&pool, &server).await?, 1);
+        assert_eq!(server.last_event_id(), new.id);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_duplicate_events_are_skipped() {
+        let server = WebSocketServer::new();
+
+        server.deliver_event(2, name_event("second")).await;
+        server.deliver_event(1, name_event("first")).await;
+        server.deliver_event(2, name_event("second")).await;
+
+        assert_eq!(server.last_event_id(), 2);
+    }
+}
@@ -1,12 +1,13 @@
-use chrono::Utc;
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
-use super::{WebSocketServer, types::convert_to_iso_string};
+use super::WebSocketServer;
 use crate::{
+    config::get_config,
+    database::motd::Model as Motd,
     errors::{KromerError, websocket::WebSocketError},
     models::krist::{
-        motd::{Constants, CurrencyInfo, DetailedMotd, PackageInfo},
+        motd::DetailedMotd,
         websockets::{WebSocketMessage, WebSocketMessageInner},
     },
     websockets::routes,
@@ -67,35 +68,33 @@ pub async fn process_text_msg(
             to,
             amount,
             metadata,
+            idempotency_key,
         } => {
-            let private_key = match private_key {
-                Some(key) => key,
-                None => {
-                    let session_data = server.fetch_session_data(uuid).await;
-
-                    if let Some(session_data) = session_data
-                        && let Some(private_key) = session_data.private_key
-                    {
-                        private_key
-                    } else {
-                        return Ok(WebSocketMessage {
-                            ok: Some(false),
-                            id: msg_id,
-                            r#type: WebSocketMessageInner::Error {
-                                error: "unauthorized".into(),
-                                message: "You are not logged in.".into(),
-                            },
-                        });
-                    }
-                }
+            let Some(private_key) = session_private_key(server, uuid, private_key).await else {
+                return Ok(unauthorized(msg_id));
             };
 
-            routes::transactions::make_transaction(
-                pool,
-                private_key,
+            let request = routes::transactions::TransactionRequest {
                 to,
                 amount,
                 metadata,
+                idempotency_key,
+            };
+
+            routes::transactions::make_transaction(pool, private_key, request, msg_id, server).await
+        }
+        WebSocketMessageInner::MakeBatchTransaction {
+            private_key,
+            transfers,
+        } => {
+            let Some(private_key) = session_private_key(server, uuid, private_key).await else {
+                return Ok(unauthorized(msg_id));
+            };
+
+            routes::transactions::make_batch_transaction(
+                pool,
+                private_key,
+                transfers,
                 msg_id,
                 server,
             )
@@ -122,50 +121,43 @@ pub async fn process_text_msg(
     Ok(msg)
 }
 
-pub async fn send_hello_message(session: &mut actix_ws::Session) {
-    let cur_time = convert_to_iso_string(Utc::now());
+/// The privatekey sent with a message, or else the one the session logged in with.
+async fn session_private_key(
+    server: &WebSocketServer,
+    uuid: &Uuid,
+    private_key: Option<String>,
+) -> Option<String> {
+    match private_key {
+        Some(key) => Some(key),
+        None => server
+            .fetch_session_data(uuid)
+            .await
+            .and_then(|session_data| session_data.private_key),
+    }
+}
+
+fn unauthorized(msg_id: Option<usize>) -> WebSocketMessage {
+    WebSocketMessage {
+        ok: Some(false),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Error {
+            error: "unauthorized".into(),
+            message: "You are not logged in.".into(),
+        },
+    }
+}
+
+pub async fn send_hello_message(pool: &Pool<Postgres>, session: &mut actix_ws::Session) {
+    let current = Motd::fetch_current(pool).await.unwrap_or_else(|err| {
+        tracing::error!("Failed to fetch the MOTD for the hello message: {err}");
+        None
+    });
 
     let hello_message = WebSocketMessage {
         ok: Some(true),
         id: None,
         r#type: WebSocketMessageInner::Hello {
-            motd: Box::new(DetailedMotd {
-                server_time: cur_time,
-                motd: "Message of the day".to_string(),
-                set: None,
-                motd_set: None,
-                public_url: "http://kromer.reconnected.cc".to_string(),
-                public_ws_url: "http://kromer.reconnected.cc/api/krist/ws".to_string(),
-                mining_enabled: false,
-                transactions_enabled: true,
-                debug_mode: true,
-                work: 500,
-                last_block: None,
-                package: PackageInfo {
-                    name: crate::build_info::PKG_NAME.to_string(),
-                    version: crate::build_info::PKG_VERSION.to_string(),
-                    author: "ReconnectedCC Team".to_string(),
-                    license: "GPL-3.0".to_string(),
-                    repository: "https://github.com/ReconnectedCC/kromer/".to_string(),
-                    git_hash: crate::build_info::GIT_COMMIT_HASH.map(|s| s.to_string()),
-                },
-                constants: Constants {
-                    wallet_version: 3,
-                    nonce_max_size: 500,
-                    name_cost: 500,
-                    min_work: 50,
-                    max_work: 500,
-                    work_factor: 500.0,
-                    seconds_per_block: 5000,
-                },
-                currency: CurrencyInfo {
-                    address_prefix: "k".to_string(),
-                    name_suffix: "kro".to_string(),
-                    currency_name: "Kromer".to_string(),
-                    currency_symbol: "KRO".to_string(),
-                },
-                notice: "Some awesome notice will go here".to_string(),
-            }),
+            motd: Box::new(DetailedMotd::new(get_config(), current)),
         },
     };
 
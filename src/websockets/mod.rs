@@ -1,5 +1,7 @@
+pub mod bus;
 pub mod errors;
 pub mod handler;
+pub mod outbox;
 pub mod routes;
 pub mod types;
 pub mod utils;
@@ -10,8 +12,14 @@ use bytestring::ByteString;
 use dashmap::{DashMap, DashSet};
 use errors::WebSocketServerError;
 use futures_util::{StreamExt, stream::FuturesUnordered};
-use std::{sync::Arc, time::Duration};
-use tokio::sync::Mutex;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicI64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::sync::{Mutex, Notify};
 use uuid::Uuid;
 
 use types::common::{WebSocketSessionData, WebSocketSubscriptionType, WebSocketTokenData};
@@ -25,6 +33,10 @@ pub const TOKEN_EXPIRATION: Duration = Duration::from_secs(30);
 #[derive(Clone)]
 pub struct WebSocketServer {
     pub inner: Arc<Mutex<WebSocketServerInner>>,
+    /// Wakes the outbox dispatcher after a transaction holding new events commits.
+    pub outbox_signal: Arc<Notify>,
+    /// Id of the newest outbox event delivered to the sessions of this instance.
+    last_event_id: Arc<AtomicI64>,
 }
 
 #[derive(Clone)]
@@ -48,9 +60,41 @@ impl WebSocketServer {
 
         Self {
             inner: Arc::new(Mutex::new(inner)),
+            outbox_signal: Arc::new(Notify::new()),
+            last_event_id: Arc::new(AtomicI64::new(0)),
         }
     }
 
+    /// Let the outbox dispatcher know that new events were committed.
+    pub fn notify_outbox(&self) {
+        self.outbox_signal.notify_one();
+    }
+
+    /// Id of the newest outbox event delivered by [`WebSocketServer::deliver_event`].
+    pub fn last_event_id(&self) -> i64 {
+        self.last_event_id.load(Ordering::Acquire)
+    }
+
+    /// Treat every event up to `event_id` as delivered, so they are never broadcast.
+    pub fn skip_events_until(&self, event_id: i64) {
+        self.last_event_id.fetch_max(event_id, Ordering::AcqRel);
+    }
+
+    /// Broadcast an event received from the event bus, unless it was already delivered.
+    ///
+    /// Events are published in id order, so anything at or below the last delivered id is a
+    /// duplicate.
+    pub async fn deliver_event(&self, event_id: i64, event: WebSocketEvent) {
+        let previous = self.last_event_id.fetch_max(event_id, Ordering::AcqRel);
+        if previous >= event_id {
+            tracing::debug!("Skipping already delivered event {event_id}");
+            return;
+        }
+
+        self.broadcast_event(WebSocketMessage::new_outbox_event(event_id, event))
+            .await;
+    }
+
     #[tracing::instrument(skip_all, fields(address = data.address))]
     pub async fn insert_session(&self, uuid: Uuid, session: Session, data: WebSocketTokenData) {
         let subscriptions = DashSet::from_iter([
@@ -155,30 +199,67 @@ impl WebSocketServer {
 
     /// Broadcast an event to all connected clients
     #[tracing::instrument(skip_all)]
-    pub async fn broadcast_event(&self, event: WebSocketMessage) {
+    pub async fn broadcast_event(&self, mut event: WebSocketMessage) {
+        // Receipts are signed at delivery rather than stored with the outbox event, so they are
+        // always made with the current key.
+        if let WebSocketMessageInner::Event {
+            event: WebSocketEvent::Transaction { transaction },
+            ..
+        } = &mut event.r#type
+        {
+            *transaction = transaction.clone().with_receipt();
+        }
+
         let msg =
             serde_json::to_string(&event).expect("Failed to turn event message into a string");
         tracing::debug!("Broadcasting event: {msg}");
 
+        // Clients subscribed to `transactionMeta` get transactions with their metadata parsed.
+        let meta_msg = match &event.r#type {
+            WebSocketMessageInner::Event {
+                event_id,
+                event: WebSocketEvent::Transaction { transaction },
+            } => {
+                let meta_event = WebSocketMessage {
+                    ok: event.ok,
+                    id: event.id,
+                    r#type: WebSocketMessageInner::Event {
+                        event_id: *event_id,
+                        event: WebSocketEvent::Transaction {
+                            transaction: transaction.clone().with_meta(),
+                        },
+                    },
+                };
+                serde_json::to_string(&meta_event)
+                    .expect("Failed to turn event message into a string")
+            }
+            _ => msg.clone(),
+        };
+
         let inner = self.inner.lock().await;
         let sessions = inner.sessions.iter_mut();
 
         for mut session in sessions {
             let (uuid, client_data) = session.pair_mut();
 
-            if let WebSocketMessageInner::Event { ref event } = event.r#type {
+            if let WebSocketMessageInner::Event { ref event, .. } = event.r#type {
                 match event {
                     WebSocketEvent::Block { .. } => todo!(),
                     WebSocketEvent::Transaction { transaction } => {
-                        let mut subs = client_data.subscriptions.iter();
+                        let subs = &client_data.subscriptions;
                         let transaction_from = transaction.from.as_deref().unwrap_or_default();
                         if (!client_data.is_guest()
                             && (client_data.address == transaction.to
                                 || client_data.address == transaction_from)
-                            && subs.any(|t| t.eq(&WebSocketSubscriptionType::OwnTransactions)))
-                            || subs.any(|t| t.eq(&WebSocketSubscriptionType::Transactions))
+                            && subs.contains(&WebSocketSubscriptionType::OwnTransactions))
+                            || subs.contains(&WebSocketSubscriptionType::Transactions)
                         {
-                            let result = client_data.session.text(msg.clone()).await;
+                            let msg =
+                                match subs.contains(&WebSocketSubscriptionType::TransactionMeta) {
+                                    true => meta_msg.clone(),
+                                    false => msg.clone(),
+                                };
+                            let result = client_data.session.text(msg).await;
                             if result.is_err() {
                                 tracing::warn!(
                                     "Got an unexpected closed session in transactions branch"
@@ -189,16 +270,27 @@ impl WebSocketServer {
                         }
                     }
                     WebSocketEvent::Name { name } => {
-                        let mut subs = client_data.subscriptions.iter();
-                        if !client_data.is_guest()
-                            && (client_data.address == name.owner)
-                            && subs.any(|t| t.eq(&WebSocketSubscriptionType::OwnNames))
-                            || subs.any(|t| t.eq(&WebSocketSubscriptionType::Names))
+                        let subs = &client_data.subscriptions;
+                        if (!client_data.is_guest()
+                            && client_data.address == name.owner
+                            && subs.contains(&WebSocketSubscriptionType::OwnNames))
+                            || subs.contains(&WebSocketSubscriptionType::Names)
                         {
                             let result = client_data.session.text(msg.clone()).await;
                             if result.is_err() {
                                 tracing::warn!("Got an unexpected closed session in name branch");
 
+                                self.cleanup_session(uuid).await;
+                            }
+                        }
+                    }
+                    WebSocketEvent::Motd { .. } => {
+                        let mut subs = client_data.subscriptions.iter();
+                        if subs.any(|t| t.eq(&WebSocketSubscriptionType::Motd)) {
+                            let result = client_data.session.text(msg.clone()).await;
+                            if result.is_err() {
+                                tracing::warn!("Got an unexpected closed session in motd branch");
+
                                 self.cleanup_session(uuid).await;
                             }
                         }
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use actix_web::rt::time;
+use sqlx::{Pool, Postgres};
+
+use super::WebSocketServer;
+use crate::database::Result;
+use crate::database::outbox::Model as Outbox;
+
+/// How often the outbox is checked when nobody wakes the dispatcher up.
+pub const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const OUTBOX_BATCH_SIZE: i64 = 100;
+const OUTBOX_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Publish committed outbox events to every instance through the event bus, forever.
+///
+/// Each instance runs a dispatcher, but only one of them publishes at a time. Events are marked
+/// as dispatched in the same transaction that announces them, so they are published exactly once
+/// and in id order; see [`super::bus`] for the receiving side.
+pub async fn run_dispatcher(pool: Pool<Postgres>, server: WebSocketServer) {
+    let mut prune_interval = time::interval(OUTBOX_PRUNE_INTERVAL);
+
+    loop {
+        if let Err(err) = dispatch_pending(&pool).await {
+            tracing::error!("Failed to dispatch outbox events: {err}");
+        }
+
+        tokio::select! {
+            _ = server.outbox_signal.notified() => {}
+            _ = time::sleep(OUTBOX_POLL_INTERVAL) => {}
+            _ = prune_interval.tick() => {
+                match Outbox::prune_dispatched(&pool).await {
+                    Ok(pruned) => tracing::debug!("Pruned {pruned} dispatched outbox events"),
+                    Err(err) => tracing::error!("Failed to prune outbox: {err}"),
+                }
+            }
+        }
+    }
+}
+
+/// Publish every pending outbox event in id order, returning how many were published.
+///
+/// Returns early without publishing anything if another instance is already dispatching.
+pub async fn dispatch_pending(pool: &Pool<Postgres>) -> Result<usize> {
+    let mut dispatched = 0;
+
+    loop {
+        let mut tx = pool.begin().await?;
+        if !Outbox::try_lock_dispatcher(&mut *tx).await? {
+            return Ok(dispatched);
+        }
+
+        let pending = Outbox::fetch_pending(&mut *tx, OUTBOX_BATCH_SIZE).await?;
+        if pending.is_empty() {
+            return Ok(dispatched);
+        }
+
+        for entry in &pending {
+            Outbox::publish(&mut *tx, entry.id).await?;
+        }
+
+        tx.commit().await?;
+        dispatched += pending.len();
+    }
+}
@@ -26,6 +26,16 @@ pub async fn perform_login(
         Ok(response) => {
             if response.authed {
                 let wallet = response.model;
+                if wallet.locked {
+                    return WebSocketMessage {
+                        ok: Some(false),
+                        id: msg_id,
+                        r#type: WebSocketMessageInner::Error {
+                            error: "address_locked".to_owned(),
+                            message: format!("Address {} is locked", wallet.address),
+                        },
+                    };
+                }
 
                 let inner = server.inner.lock().await;
                 let mut session = inner
@@ -95,3 +105,36 @@ pub async fn perform_logout(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Result;
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_locked_wallet_cant_log_in(pool: Pool<Postgres>) -> Result<()> {
+        let server = WebSocketServer::new();
+        let wallet = Wallet::verify_address(&pool, "stolen").await?.model;
+        wallet
+            .set_locked(&pool, true, "stolen key", "admin")
+            .await?;
+
+        // Rejected before the session is looked at, so there doesn't need to be one.
+        let message = perform_login(
+            &pool,
+            &server,
+            &Uuid::new_v4(),
+            "stolen".to_owned(),
+            Some(1),
+        )
+        .await;
+        assert_eq!(message.ok, Some(false));
+        assert!(matches!(
+            message.r#type,
+            WebSocketMessageInner::Error { ref error, .. } if error == "address_locked"
+        ));
+
+        Ok(())
+    }
+}
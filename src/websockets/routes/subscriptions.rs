@@ -118,6 +118,7 @@ pub async fn get_valid_subscription_levels(msg_id: Option<usize>) -> WebSocketMe
         WebSocketSubscriptionType::Names,
         WebSocketSubscriptionType::OwnNames,
         WebSocketSubscriptionType::Motd,
+        WebSocketSubscriptionType::TransactionMeta,
     ];
     let subscription_list: Vec<String> = subscription_list
         .into_iter()
@@ -2,138 +2,581 @@ use rust_decimal::{Decimal, dec};
 use sqlx::{Pool, Postgres};
 
 use crate::{
-    database::transaction::{TransactionCreateData, TransactionType},
+    database::DatabaseError,
+    database::transaction::{
+        self, BatchTransfer, TransactionCreateData, TransactionNameData, TransactionType,
+    },
+    errors::krist::{KristError, KristErrorExt},
+    errors::transaction::TransactionError,
+    errors::wallet::WalletError,
+    models::krist::transactions::TransactionJson,
     models::krist::websockets::{
         WebSocketEvent, WebSocketMessage, WebSocketMessageInner, WebSocketMessageResponse,
     },
+    utils::validation,
     websockets::WebSocketServer,
 };
 
+use crate::database::name::{self, Model as Name};
+use crate::database::name_route::Model as NameRoute;
+use crate::database::outbox::Model as Outbox;
 use crate::database::transaction::Model as Transaction;
 use crate::database::wallet::Model as Wallet;
 
+/// The body of a `make_transaction` message, minus the credentials.
+#[derive(Debug)]
+pub struct TransactionRequest {
+    pub to: String,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+    pub idempotency_key: Option<String>,
+}
+
 #[tracing::instrument(skip(pool, server, msg_id, private_key))]
 pub async fn make_transaction(
     pool: &Pool<Postgres>,
     private_key: String,
-    to: String,
-    amount: Decimal,
-    metadata: Option<String>,
+    request: TransactionRequest,
     msg_id: Option<usize>,
     server: &WebSocketServer,
 ) -> WebSocketMessage {
+    let TransactionRequest {
+        to,
+        amount,
+        metadata,
+        idempotency_key,
+    } = request;
     let amount = amount.round_dp(2); // Make sure we do not support 2 decimals after the dot.
 
     if amount <= dec!(0.00) {
-        return WebSocketMessage {
-            ok: Some(false),
-            id: msg_id,
-            r#type: WebSocketMessageInner::Error {
-                error: "invalid_parameter".to_owned(),
-                message: "Invalid parameter amount".to_owned(),
-            },
-        };
+        return error_message(msg_id, "invalid_parameter", "Invalid parameter amount");
+    }
+
+    if metadata
+        .as_deref()
+        .is_some_and(|metadata| !validation::is_valid_metadata(metadata))
+    {
+        return error_message(msg_id, "invalid_parameter", "Invalid parameter metadata");
+    }
+
+    if idempotency_key
+        .as_deref()
+        .is_some_and(|key| !validation::is_valid_idempotency_key(key))
+    {
+        return error_message(
+            msg_id,
+            "invalid_parameter",
+            "Invalid parameter idempotency_key",
+        );
     }
 
     let resp = match Wallet::verify_address(pool, private_key).await {
         Ok(resp) => resp,
-        Err(_) => {
-            return WebSocketMessage {
-                ok: Some(false),
-                id: msg_id,
-                r#type: WebSocketMessageInner::Error {
-                    error: "database_error".to_owned(),
-                    message: "An error occured in the database".to_owned(),
-                },
-            };
-        }
+        Err(_) => return database_error(msg_id),
     };
     if !resp.authed {
-        return WebSocketMessage {
-            ok: Some(false),
-            id: msg_id,
-            r#type: WebSocketMessageInner::Error {
-                error: "invalid_parameter".to_owned(),
-                message: "Invalid parameter privatekey".to_owned(),
-            },
-        };
+        return error_message(msg_id, "invalid_parameter", "Invalid parameter privatekey");
     }
 
+    let Ok(mut tx) = pool.begin().await else {
+        return database_error(msg_id);
+    };
+
     let sender = resp.model;
 
-    let recipient = match Wallet::fetch_by_address(pool, to.clone()).await {
-        Ok(model) => model,
-        Err(_) => {
-            return WebSocketMessage {
-                ok: Some(false),
-                id: msg_id,
-                r#type: WebSocketMessageInner::Error {
-                    error: "database_error".to_owned(),
-                    message: "An error occured in the database".to_owned(),
-                },
+    let request_hash = idempotency_key
+        .as_ref()
+        .map(|_| transaction::request_hash(&to, amount, metadata.as_deref()));
+    if let (Some(key), Some(hash)) = (&idempotency_key, &request_hash) {
+        match Transaction::find_replay(&mut *tx, &sender.address, key, hash).await {
+            Ok(Some(original)) => {
+                if tx.commit().await.is_err() {
+                    return database_error(msg_id);
+                }
+
+                return WebSocketMessage {
+                    ok: Some(true),
+                    id: msg_id,
+                    r#type: WebSocketMessageInner::Response {
+                        data: WebSocketMessageResponse::MakeTransaction {
+                            transaction: TransactionJson::from(original).with_receipt(),
+                            refund: None,
+                        },
+                    },
+                };
+            }
+            Ok(None) => (),
+            Err(DatabaseError::Transaction(err @ TransactionError::IdempotencyKeyReused(_))) => {
+                return error_message(msg_id, "idempotency_key_reused", &err.to_string());
+            }
+            Err(_) => return database_error(msg_id),
+        }
+    }
+
+    // Payments to `meta@name.kro` go wherever the name routes that metaname.
+    let is_name = validation::NAME_META_RE.is_match(&to);
+    let name_data = is_name.then(|| TransactionNameData::parse(&to));
+    let (sent_metaname, sent_name) = match name_data {
+        Some(name_data) => (name_data.metaname, name_data.name),
+        None => (None, None),
+    };
+
+    let (recipient_address, auto_refund) = match is_name {
+        true => {
+            let name = sent_name.as_deref().unwrap_or_default();
+            let name = match Name::fetch_by_name(&mut *tx, name).await {
+                Ok(Some(name)) => name,
+                Ok(None) => {
+                    return error_message(
+                        msg_id,
+                        "name_not_found",
+                        &format!("Name {to} not found"),
+                    );
+                }
+                Err(_) => return database_error(msg_id),
             };
+
+            match NameRoute::resolve(&mut *tx, &name, sent_metaname.as_deref()).await {
+                Ok(address) => (address, name.should_refund(sent_metaname.as_deref())),
+                Err(_) => return database_error(msg_id),
+            }
         }
+        false => (to.clone(), false),
+    };
+
+    let recipient = match Wallet::fetch_by_address(&mut *tx, &recipient_address).await {
+        Ok(model) => model,
+        Err(_) => return database_error(msg_id),
     };
 
     let recipient = match recipient {
         Some(wallet) => wallet,
         None => {
-            return WebSocketMessage {
-                ok: Some(false),
-                id: msg_id,
-                r#type: WebSocketMessageInner::Error {
-                    error: "address_not_found".to_owned(),
-                    message: format!("Address {to} not found"),
-                },
-            };
+            return error_message(
+                msg_id,
+                "address_not_found",
+                &format!("Address {recipient_address} not found"),
+            );
         }
     };
 
-    if sender.balance < amount {
-        return WebSocketMessage {
-            ok: Some(false),
-            id: msg_id,
-            r#type: WebSocketMessageInner::Error {
-                error: "insufficient_funds".to_owned(),
-                message: "Insufficient funds".to_owned(),
-            },
-        };
+    // Checked after resolving, a name can route back to the sender.
+    if recipient.address == sender.address {
+        return error_message(
+            msg_id,
+            "same_wallet_transfer",
+            "Same wallet transfer is not allowed",
+        );
     }
 
     let creation_data = TransactionCreateData {
         from: sender.address.clone(),
         to: recipient.address.clone(),
         amount,
+        sent_metaname,
+        sent_name,
         metadata: metadata.clone(),
         transaction_type: TransactionType::Transfer,
+        idempotency_key,
+        request_hash,
         ..Default::default()
     };
 
-    let response = Transaction::create(pool, creation_data).await;
+    if auto_refund
+        && Transaction::lock_for_refund(&mut *tx, &creation_data)
+            .await
+            .is_err()
+    {
+        return database_error(msg_id);
+    }
 
-    if response.is_err() {
-        return WebSocketMessage {
-            ok: Some(false),
-            id: msg_id,
-            r#type: WebSocketMessageInner::Error {
-                error: "database_error".to_owned(),
-                message: "An error occured in the database".to_owned(),
+    let transaction = match Transaction::create(&mut *tx, creation_data).await {
+        Ok(transaction) => transaction,
+        Err(DatabaseError::Transaction(TransactionError::InsufficientFunds)) => {
+            return error_message(msg_id, "insufficient_funds", "Insufficient funds");
+        }
+        Err(DatabaseError::Wallet(WalletError::Locked(address))) => {
+            return error_message(
+                msg_id,
+                "address_locked",
+                &format!("Address {address} is locked"),
+            );
+        }
+        Err(_) => return database_error(msg_id),
+    };
+
+    let event = WebSocketEvent::Transaction {
+        transaction: transaction.clone().into(),
+    };
+    if Outbox::enqueue(&mut *tx, &event).await.is_err() {
+        return database_error(msg_id);
+    }
+
+    let refund = match auto_refund {
+        true => {
+            let refund = match Transaction::refund(
+                &mut *tx,
+                transaction.id,
+                amount,
+                Some(name::AUTO_REFUND_MESSAGE),
+            )
+            .await
+            {
+                Ok(refund) => refund,
+                Err(_) => return database_error(msg_id),
+            };
+
+            let event = WebSocketEvent::Transaction {
+                transaction: refund.clone().into(),
+            };
+            if Outbox::enqueue(&mut *tx, &event).await.is_err() {
+                return database_error(msg_id);
+            }
+
+            Some(Box::new(TransactionJson::from(refund).with_receipt()))
+        }
+        false => None,
+    };
+
+    if tx.commit().await.is_err() {
+        return database_error(msg_id);
+    }
+    server.notify_outbox();
+
+    WebSocketMessage {
+        ok: Some(true),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Response {
+            data: WebSocketMessageResponse::MakeTransaction {
+                transaction: TransactionJson::from(transaction).with_receipt(),
+                refund,
             },
+        },
+    }
+}
+
+#[tracing::instrument(skip(pool, server, msg_id, private_key))]
+pub async fn make_batch_transaction(
+    pool: &Pool<Postgres>,
+    private_key: String,
+    transfers: Vec<BatchTransfer>,
+    msg_id: Option<usize>,
+    server: &WebSocketServer,
+) -> WebSocketMessage {
+    let resp = match Wallet::verify_address(pool, private_key).await {
+        Ok(resp) => resp,
+        Err(_) => return database_error(msg_id),
+    };
+    if !resp.authed {
+        return error_message(msg_id, "invalid_parameter", "Invalid parameter privatekey");
+    }
+
+    let Ok(mut tx) = pool.begin().await else {
+        return database_error(msg_id);
+    };
+
+    let result = match Transaction::create_batch(&mut *tx, &resp.model.address, &transfers).await {
+        Ok(result) => result,
+        Err(DatabaseError::Sqlx(_)) => return database_error(msg_id),
+        // The same error types and messages as `/transactions/batch`, naming the transfer.
+        Err(err) => {
+            let err = KristError::from(err);
+            return error_message(msg_id, err.error_type(), &err.to_string());
+        }
+    };
+
+    let mut made: Vec<&Transaction> = result.transactions.iter().chain(&result.refunds).collect();
+    made.sort_by_key(|transaction| transaction.id);
+    for transaction in made {
+        let event = WebSocketEvent::Transaction {
+            transaction: transaction.clone().into(),
         };
+        if Outbox::enqueue(&mut *tx, &event).await.is_err() {
+            return database_error(msg_id);
+        }
     }
-    let transaction = response.unwrap();
-    let event = WebSocketMessage::new_event(WebSocketEvent::Transaction {
-        transaction: transaction.clone().into(),
-    });
-    server.broadcast_event(event).await;
 
+    if tx.commit().await.is_err() {
+        return database_error(msg_id);
+    }
+    server.notify_outbox();
+
+    let signed = |transaction: Transaction| TransactionJson::from(transaction).with_receipt();
     WebSocketMessage {
         ok: Some(true),
         id: msg_id,
         r#type: WebSocketMessageInner::Response {
-            data: WebSocketMessageResponse::MakeTransaction {
-                transaction: transaction.into(),
+            data: WebSocketMessageResponse::MakeBatchTransaction {
+                transactions: result.transactions.into_iter().map(signed).collect(),
+                refunds: result.refunds.into_iter().map(signed).collect(),
             },
         },
     }
 }
+
+fn error_message(msg_id: Option<usize>, error: &str, message: &str) -> WebSocketMessage {
+    WebSocketMessage {
+        ok: Some(false),
+        id: msg_id,
+        r#type: WebSocketMessageInner::Error {
+            error: error.to_owned(),
+            message: message.to_owned(),
+        },
+    }
+}
+
+fn database_error(msg_id: Option<usize>) -> WebSocketMessage {
+    error_message(msg_id, "database_error", "An error occured in the database")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Result;
+    use crate::utils::receipts::{self, ReceiptData};
+    use futures_util::future::join_all;
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_make_transaction_follows_routes(pool: Pool<Postgres>) -> Result<()> {
+        let server = WebSocketServer::new();
+        let sender = Wallet::verify_address(&pool, "sender").await?.model;
+        sender.set_balance(&pool, dec!(100)).await.unwrap();
+        Wallet::create_wallet(&pool, "kowner0000", "hash", None).await?;
+        Wallet::create_wallet(&pool, "ksales0000", "hash", None).await?;
+
+        let shop = Name::create(&pool, "shop".to_owned(), "kowner0000".to_owned()).await?;
+        NameRoute::set(&pool, shop.id, Some("sales"), "ksales0000").await?;
+
+        for (to, recipient) in [
+            ("sales@shop.kro", "ksales0000"),
+            ("other@shop.kro", "kowner0000"),
+        ] {
+            let request = TransactionRequest {
+                to: to.to_owned(),
+                amount: dec!(5),
+                metadata: None,
+                idempotency_key: None,
+            };
+            let message =
+                make_transaction(&pool, "sender".to_owned(), request, None, &server).await;
+
+            let WebSocketMessageInner::Response {
+                data: WebSocketMessageResponse::MakeTransaction { transaction, .. },
+            } = message.r#type
+            else {
+                panic!("expected a transaction response for {to}");
+            };
+            assert_eq!(transaction.to, recipient);
+            assert_eq!(transaction.sent_name.as_deref(), Some("shop"));
+
+            let receipt = transaction.receipt.as_deref().expect("a signed receipt");
+            let signed = receipts::verify(receipt, &receipts::get_signer().trusted_keys());
+            assert_eq!(signed, Some(ReceiptData::from(&transaction)));
+        }
+
+        let request = TransactionRequest {
+            to: "sales@missing.kro".to_owned(),
+            amount: dec!(5),
+            metadata: None,
+            idempotency_key: None,
+        };
+        let message = make_transaction(&pool, "sender".to_owned(), request, None, &server).await;
+        assert!(matches!(
+            message.r#type,
+            WebSocketMessageInner::Error { ref error, .. } if error == "name_not_found"
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_make_transaction_to_self(pool: Pool<Postgres>) -> Result<()> {
+        let server = WebSocketServer::new();
+        let sender = Wallet::verify_address(&pool, "sender").await?.model;
+        sender.set_balance(&pool, dec!(100)).await.unwrap();
+
+        let shop = Name::create(&pool, "shop".to_owned(), "kowner0000".to_owned()).await?;
+        NameRoute::set(&pool, shop.id, Some("me"), &sender.address).await?;
+
+        for to in [sender.address.clone(), "me@shop.kro".to_owned()] {
+            let request = TransactionRequest {
+                to: to.clone(),
+                amount: dec!(5),
+                metadata: None,
+                idempotency_key: None,
+            };
+            let message =
+                make_transaction(&pool, "sender".to_owned(), request, None, &server).await;
+            assert!(
+                matches!(
+                    message.r#type,
+                    WebSocketMessageInner::Error { ref error, .. } if error == "same_wallet_transfer"
+                ),
+                "transfer to {to}"
+            );
+        }
+
+        let sender = Wallet::fetch_by_address(&pool, &sender.address)
+            .await?
+            .unwrap();
+        assert_eq!(sender.balance, dec!(100));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_make_transaction_auto_refunds(pool: Pool<Postgres>) -> Result<()> {
+        let server = WebSocketServer::new();
+        let sender = Wallet::verify_address(&pool, "sender").await?.model;
+        sender.set_balance(&pool, dec!(100)).await.unwrap();
+        Wallet::create_wallet(&pool, "kowner0000", "hash", None).await?;
+
+        let shop = Name::create(&pool, "shop".to_owned(), "kowner0000".to_owned()).await?;
+        shop.set_refund_settings(&pool, true, &["sales".to_owned()])
+            .await?;
+
+        for (to, refunded) in [
+            ("sales@shop.kro", false),
+            ("typo@shop.kro", true),
+            ("shop.kro", false),
+        ] {
+            let request = TransactionRequest {
+                to: to.to_owned(),
+                amount: dec!(5),
+                metadata: None,
+                idempotency_key: None,
+            };
+            let message =
+                make_transaction(&pool, "sender".to_owned(), request, None, &server).await;
+
+            let WebSocketMessageInner::Response {
+                data:
+                    WebSocketMessageResponse::MakeTransaction {
+                        transaction,
+                        refund,
+                    },
+            } = message.r#type
+            else {
+                panic!("expected a transaction response for {to}");
+            };
+            assert_eq!(refund.is_some(), refunded, "refund for {to}");
+            if let Some(refund) = refund {
+                assert_eq!(refund.to, sender.address);
+                assert_eq!(refund.original_id, Some(transaction.id));
+                assert_eq!(refund.transaction_type, TransactionType::Refund);
+            }
+        }
+
+        let owner = Wallet::fetch_by_address(&pool, "kowner0000")
+            .await?
+            .unwrap();
+        assert_eq!(owner.balance, dec!(10));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_auto_refunds_alongside_transfers(pool: Pool<Postgres>) -> Result<()> {
+        let server = WebSocketServer::new();
+        // Refunds go to a wallet that sorts before the name owner, which pays the owner itself.
+        let returns = Wallet::create_wallet(&pool, "kreturn000", "hash", Some(dec!(100))).await?;
+        let sender = Wallet::verify_address(&pool, "sender").await?.model;
+        sender.set_balance(&pool, dec!(100)).await.unwrap();
+        Wallet::create_wallet(&pool, "kowner0000", "hash", None).await?;
+
+        let shop = Name::create(&pool, "shop".to_owned(), "kowner0000".to_owned()).await?;
+        shop.set_refund_settings(&pool, true, &[]).await?;
+
+        let refunded = (0..50).map(|_| {
+            let request = TransactionRequest {
+                to: "typo@shop.kro".to_owned(),
+                amount: dec!(1),
+                metadata: Some("return=kreturn000".to_owned()),
+                idempotency_key: None,
+            };
+            make_transaction(&pool, "sender".to_owned(), request, None, &server)
+        });
+        let transfers = (0..50).map(|_| {
+            let creation_data = TransactionCreateData {
+                from: returns.address.clone(),
+                to: "kowner0000".to_owned(),
+                amount: dec!(1),
+                transaction_type: TransactionType::Transfer,
+                ..Default::default()
+            };
+            Transaction::create(&pool, creation_data)
+        });
+
+        let (refunded, transfers) = tokio::join!(join_all(refunded), join_all(transfers));
+        for message in refunded {
+            assert_eq!(message.ok, Some(true), "{:?}", message.r#type);
+        }
+        for transfer in transfers {
+            transfer?;
+        }
+
+        let returns = Wallet::fetch_by_address(&pool, "kreturn000")
+            .await?
+            .unwrap();
+        assert_eq!(returns.balance, dec!(100));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    #[ignore = "requires a Postgres database in DATABASE_URL"]
+    async fn test_make_batch_transaction(pool: Pool<Postgres>) -> Result<()> {
+        let server = WebSocketServer::new();
+        let sender = Wallet::verify_address(&pool, "sender").await?.model;
+        sender.set_balance(&pool, dec!(100)).await.unwrap();
+        Wallet::create_wallet(&pool, "kowner0000", "hash", None).await?;
+
+        let shop = Name::create(&pool, "shop".to_owned(), "kowner0000".to_owned()).await?;
+        shop.set_refund_settings(&pool, true, &["sales".to_owned()])
+            .await?;
+
+        let transfer = |to: &str| BatchTransfer {
+            to: to.to_owned(),
+            amount: dec!(5),
+            metadata: None,
+        };
+
+        let transfers = vec![transfer("sales@shop.kro"), transfer("missing.kro")];
+        let message =
+            make_batch_transaction(&pool, "sender".to_owned(), transfers, None, &server).await;
+        let WebSocketMessageInner::Error { error, message } = message.r#type else {
+            panic!("expected the batch to fail");
+        };
+        assert_eq!(error, "name_not_found");
+        assert_eq!(message, "Transfer 1: Name missing.kro not found");
+
+        let transfers = vec![transfer("sales@shop.kro"), transfer("typo@shop.kro")];
+        let message =
+            make_batch_transaction(&pool, "sender".to_owned(), transfers, None, &server).await;
+        let WebSocketMessageInner::Response {
+            data:
+                WebSocketMessageResponse::MakeBatchTransaction {
+                    transactions,
+                    refunds,
+                },
+        } = message.r#type
+        else {
+            panic!("expected a batch response");
+        };
+        assert_eq!(transactions.len(), 2);
+        assert!(transactions.iter().all(|t| t.receipt.is_some()));
+        assert_eq!(refunds.len(), 1);
+        assert_eq!(refunds[0].original_id, Some(transactions[1].id));
+
+        let owner = Wallet::fetch_by_address(&pool, "kowner0000")
+            .await?
+            .unwrap();
+        assert_eq!(owner.balance, dec!(5));
+
+        Ok(())
+    }
+}
@@ -26,6 +26,8 @@ pub enum WebSocketSubscriptionType {
     Names,
     OwnNames,
     Motd,
+    /// Not an event of its own, adds the parsed CommonMeta to transaction events.
+    TransactionMeta,
 }
 
 impl WebSocketSubscriptionType {
@@ -44,6 +46,7 @@ impl WebSocketSubscriptionType {
             WebSocketSubscriptionType::Names => "names".to_owned(),
             WebSocketSubscriptionType::OwnNames => "ownNames".to_owned(),
             WebSocketSubscriptionType::Motd => "motd".to_owned(),
+            WebSocketSubscriptionType::TransactionMeta => "transactionMeta".to_owned(),
         }
     }
 }
@@ -66,6 +69,7 @@ impl std::str::FromStr for WebSocketSubscriptionType {
             "names" => Ok(Self::Names),
             "ownNames" => Ok(Self::OwnNames),
             "motd" => Ok(Self::Motd),
+            "transactionMeta" => Ok(Self::TransactionMeta),
             _ => Err(()),
         }
     }
@@ -81,6 +85,7 @@ impl std::fmt::Display for WebSocketSubscriptionType {
             Self::Names => write!(f, "names"),
             Self::OwnNames => write!(f, "ownNames"),
             Self::Motd => write!(f, "motd"),
+            Self::TransactionMeta => write!(f, "transactionMeta"),
         }
     }
 }